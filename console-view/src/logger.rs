@@ -6,11 +6,7 @@ use std::io;
 use std::path::Path;
 
 fn log_file<P: AsRef<Path>>(path: P) -> io::Result<File> {
-    OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(true)
-        .open(path)
+    OpenOptions::new().create(true).append(true).open(path)
 }
 
 pub fn init_logger() {
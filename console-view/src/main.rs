@@ -1,16 +1,15 @@
-use log::{debug, error, trace};
+use log::{debug, error, trace, warn};
 use logger::init_logger;
 
 #[path = "logger.rs"]
 pub mod logger;
 
 extern crate parser_graphml;
-use parser_graphml::parser::{read_graphml, Edge, Vertex};
+use parser_graphml::parser::{
+    validate, write_dot, Ending, Imported, Quest, QuestLoaders, Severity,
+};
+use quest_engine::{Availability, ErrorChoice, GameSession};
 
-use petgraph::graph::EdgeReference;
-use petgraph::graph::{Graph, NodeIndex};
-use petgraph::visit::EdgeRef;
-use petgraph::Direction;
 use std::io::stdin;
 
 use std::fs::File;
@@ -23,78 +22,174 @@ fn main() {
 
     const PATH: &str = "scenes-choices.graphml";
 
-    let xml_doc = match load_file(PATH) {
+    // Файл квеста можно передать аргументом, по умолчанию - пример в GraphML.
+    // С флагом `--dot` квест не запускается, а печатается в Graphviz DOT,
+    // с флагом `--check` - проверяется перед публикацией.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let export_dot = args.iter().any(|x| x == "--dot");
+    let check = args.iter().any(|x| x == "--check");
+    let path = args
+        .into_iter()
+        .find(|x| !x.starts_with("--"))
+        .unwrap_or_else(|| PATH.to_string());
+
+    let bytes = match load_file(&path) {
         Ok(doc) => doc,
         Err(e) => {
-            const MSG: &str = "Ошибка чтения файла GraphML";
+            const MSG: &str = "Ошибка чтения файла квеста";
             error!("{} {:?}", MSG, e);
-            panic!(MSG);
+            panic!("{}", MSG);
         }
     };
 
-    let graph = match read_graphml(&xml_doc) {
-        Ok(graph) => graph,
+    // Формат выбирается по расширению файла или по содержимому
+    let quest = match QuestLoaders::default().load(&path, &bytes) {
+        Ok(Imported { quest, warnings }) => {
+            for warning in &warnings {
+                warn!("{}", warning);
+                eprintln!("{}", warning);
+            }
+            quest
+        }
         Err(e) => {
-            const MSG: &str =
-                "Ошибка парсинга графа из GraphML формата";
-            error!("{} {:?}", MSG, e);
-            panic!(MSG);
+            const MSG: &str = "Ошибка загрузки квеста";
+            error!("{} {}", MSG, e);
+            eprintln!("{}", e);
+            panic!("{}", MSG);
         }
     };
 
-    start_game(&graph);
+    if export_dot {
+        print!("{}", write_dot(&quest));
+        return;
+    }
+    if check {
+        let findings = validate(&quest);
+        for finding in &findings {
+            println!("{}", finding);
+        }
+        if findings.iter().any(|x| x.severity == Severity::Error) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    start_game(quest);
 }
 
-fn load_file(path: &str) -> io::Result<String> {
-    println!("Файл найден: {}", Path::new(path).exists());
+fn load_file(path: &str) -> io::Result<Vec<u8>> {
+    eprintln!("Файл найден: {}", Path::new(path).exists());
 
-    let mut file = File::open(&path)?;
-    let mut text = String::new();
+    let mut file = File::open(path)?;
+    let mut bytes = Vec::new();
 
-    file.read_to_string(&mut text)?;
+    file.read_to_end(&mut bytes)?;
 
-    Ok(text)
+    Ok(bytes)
 }
 
-fn start_game(graph: &Graph<Vertex, Edge>) {
-    const EXIT_CODE: usize = 0;
+/// Язык системы из переменных окружения, например `ru_RU.UTF-8`.
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|x| std::env::var(x).ok())
+        .find(|x| !x.is_empty())
+}
 
-    let mut input = String::new();
-    let mut number: usize;
-
-    let mut vertex_ix: NodeIndex = match graph.node_indices().take(1).next() {
-        Some(vertex) => vertex,
-        None => {
-            const MSG: &str =
-                "Не удалось получить первую вершину в графе";
-            error!("{}", MSG);
-            panic!(MSG);
+/// Экран концовки: название концовки, а если оно задано автором, то и ее тип.
+fn show_ending(ending: &Ending) {
+    println!();
+    println!("*** {} ***", ending.display_title());
+    if ending.title.is_some() {
+        println!("{}", ending.kind.title());
+    }
+    println!("Игра окончена.");
+}
+
+/// Варианты выбора текущей сцены с номерами, недоступные - с причиной.
+fn print_choices(session: &GameSession, locale: Option<&str>) {
+    for (i, choice) in session.choices().iter().enumerate() {
+        let text = choice.edge.rich_text_in(locale).to_ansi();
+        match choice.availability {
+            Availability::Locked(reason) => {
+                println!("{}. {} (недоступно: {})", i + 1, text, reason)
+            }
+            _ => println!("{}. {}", i + 1, text),
         }
-    };
+    }
+}
+
+fn start_game(quest: Quest) {
+    const EXIT_CODE: usize = 0;
 
-    let mut out_edges: Vec<EdgeReference<'_, Edge>>;
+    let mut session = GameSession::new(quest);
+    let mut input = String::new();
+    let mut shown_chapter = None;
+    let mut locale = system_locale().and_then(|x| session.quest().pick_locale(&x));
+
+    let locales = session.quest().locales();
+    if !locales.is_empty() {
+        println!(
+            "Доступные языки: {}. Сменить язык: `язык <код>`.",
+            std::iter::once(session.quest().locale.as_deref().unwrap_or("основной"))
+                .chain(locales.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
 
     loop {
         input.clear();
-        println!("Сцена: {}", graph[vertex_ix].text);
-        out_edges = graph
-            .edges_directed(vertex_ix, Direction::Outgoing)
-            .collect();
+        let quest = session.quest();
+        let scene = session.scene();
+        if scene.chapter != shown_chapter {
+            shown_chapter = scene.chapter;
+            if let Some(chapter) = quest.chapter_of(session.current()) {
+                println!("=== {} ===", chapter.title);
+            }
+        }
+        let mut blocks = scene.rich_blocks_in(locale.as_deref()).into_iter();
+        if let Some(text) = blocks.next() {
+            println!("Сцена: {}", text.to_ansi());
+        }
+        for paragraph in blocks {
+            println!("\n{}", paragraph.to_ansi());
+        }
 
-        if out_edges.is_empty() {
+        if let Some(ending) = session.ending() {
+            debug!("Достигнута концовка `{}`: {}", scene.id, ending);
+            show_ending(ending);
+            break;
+        }
+
+        if session.is_stuck() {
+            println!("Все варианты выбора недоступны:");
+            print_choices(&session, locale.as_deref());
+            println!("Пройти дальше нельзя, выхожу...");
+            break;
+        }
+
+        if session.is_over() {
             println!("Больше нету действий, выхожу...");
             break;
         }
 
         println!("Выберите действие: ");
-        let mut i = 1;
-        for edge in &out_edges {
-            println!("{}. {}", i, edge.weight().text.clone());
-            i += 1;
-        }
+        print_choices(&session, locale.as_deref());
         match stdin().read_line(&mut input) {
             Ok(_) => {
-                number = match input.trim_end().parse::<usize>() {
+                if let Some(code) = input.trim().strip_prefix("язык ") {
+                    locale = quest.pick_locale(code.trim());
+                    println!(
+                        "Язык: {}",
+                        locale
+                            .as_deref()
+                            .or(quest.locale.as_deref())
+                            .unwrap_or("основной")
+                    );
+                    continue;
+                }
+
+                let number = match input.trim_end().parse::<usize>() {
                     Ok(x) => x,
                     Err(_) => {
                         println!("Введен некорректный номер.");
@@ -107,24 +202,19 @@ fn start_game(graph: &Graph<Vertex, Edge>) {
                     break;
                 }
 
-                if 1 <= number && number <= i {
-                    trace!(
-                        "Получен верный номер варианта {}",
-                        number
-                    );
-                    trace!("out edges count: {}", out_edges.len());
-                    trace!("out edges: {:?}", out_edges);
-
-                    if let Some(found_vertex_ix) = out_edges.get(number - 1) {
-                        vertex_ix = found_vertex_ix.target();
-                    } else {
-                        println!("Не получилось получить вариант");
+                trace!("Получен номер варианта {}", number);
+                match session.choose(number - 1) {
+                    Ok(ix) => {
+                        debug!("vertex_ix {:?}", session.quest().graph[ix]);
+                        debug!("Переменные: {:?}", session.variables());
+                    }
+                    Err(ErrorChoice::Locked(_, reason)) => {
+                        println!("Этот вариант недоступен: {}.", reason);
+                    }
+                    Err(e) => {
+                        trace!("Неверный номер варианта: {}", e);
+                        println!("Введен неверный номер варианта, {}.", e);
                     }
-                    debug!("vertex_ix {:?}", graph[vertex_ix]);
-                } else {
-                    println!(
-                        "Введен неверный номер варианта, попробуйте снова."
-                    );
                 }
             }
             Err(e) => {
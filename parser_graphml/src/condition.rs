@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::script::{ErrorScript, Lexer, Token, Value, VarType, Variable};
+
+/// Операторы условий в порядке проверки: сначала длинные.
+const PUNCTS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "(", ")",
+];
+
+/// Унарная операция условия.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnaryOp {
+    /// `!`, для `bool`
+    Not,
+    /// `-`, для `int`
+    Neg,
+}
+
+/// Бинарная операция условия.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+/// Выражение условия, например `gold >= 10 && !met_guard`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    Value(Value),
+    Variable(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// Условие варианта выбора: выражение и причина, которую видит игрок,
+/// когда условие не выполнено.
+///
+/// Без причины вариант с невыполненным условием скрывается, с причиной -
+/// показывается недоступным.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Condition {
+    pub expr: Expr,
+    /// Причина из ключа `locked`, например `Нужно 10 золотых`.
+    pub locked: Option<String>,
+}
+
+impl UnaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Not => "!",
+            UnaryOp::Neg => "-",
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+        }
+    }
+
+    /// Приоритет: чем больше, тем сильнее связывает операнды.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 3,
+            BinaryOp::Add | BinaryOp::Sub => 4,
+        }
+    }
+
+    fn is_comparison(self) -> bool {
+        self.precedence() == 3
+    }
+
+    /// Операции одного приоритета, которые разбирает уровень `precedence`.
+    fn of_precedence(precedence: u8) -> &'static [BinaryOp] {
+        match precedence {
+            1 => &[BinaryOp::Or],
+            2 => &[BinaryOp::And],
+            3 => &[
+                BinaryOp::Eq,
+                BinaryOp::Ne,
+                BinaryOp::Le,
+                BinaryOp::Ge,
+                BinaryOp::Lt,
+                BinaryOp::Gt,
+            ],
+            _ => &[BinaryOp::Add, BinaryOp::Sub],
+        }
+    }
+}
+
+/// Приоритет унарных операций - выше любой бинарной.
+const UNARY_PRECEDENCE: u8 = 5;
+
+/// Наибольшая вложенность операций и скобок в условии. Глубже разбор не идет,
+/// чтобы условие из файла квеста не переполнило стек ни при разборе,
+/// ни при проверке и вычислении дерева выражения.
+const MAX_DEPTH: usize = 256;
+
+/// Ошибка условия глубже [`MAX_DEPTH`].
+const TOO_DEEP: &str = "выражение не глубже 256 уровней";
+
+/// Разобранное выражение и высота его дерева.
+type Parsed = (Expr, usize);
+
+/// Высота узла над поддеревом высоты `height`, если она не больше [`MAX_DEPTH`].
+fn nested(lexer: &Lexer, height: usize) -> Result<usize, ErrorScript> {
+    if height >= MAX_DEPTH {
+        return Err(lexer.error(TOO_DEEP));
+    }
+    Ok(height + 1)
+}
+
+impl Expr {
+    /// Тип выражения по объявлениям переменных квеста.
+    ///
+    /// # Errors
+    ///
+    /// Необъявленная переменная, операция над значением не того типа
+    /// или сравнение значений разных типов.
+    pub fn check(&self, variables: &[Variable]) -> Result<VarType, ErrorScript> {
+        let expect = |op: &'static str, expected: VarType, found: VarType| {
+            if found == expected {
+                Ok(())
+            } else {
+                Err(ErrorScript::OperandType(op, expected, found))
+            }
+        };
+
+        match self {
+            Expr::Value(x) => Ok(x.var_type()),
+            Expr::Variable(name) => variables
+                .iter()
+                .find(|x| x.name == *name)
+                .map(|x| x.var_type)
+                .ok_or_else(|| ErrorScript::UnknownVariable(name.clone())),
+            Expr::Unary(op, operand) => {
+                let expected = match op {
+                    UnaryOp::Not => VarType::Bool,
+                    UnaryOp::Neg => VarType::Int,
+                };
+                expect(op.symbol(), expected, operand.check(variables)?)?;
+                Ok(expected)
+            }
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.check(variables)?, right.check(variables)?);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => {
+                        expect(op.symbol(), VarType::Bool, left)?;
+                        expect(op.symbol(), VarType::Bool, right)?;
+                        Ok(VarType::Bool)
+                    }
+                    BinaryOp::Eq | BinaryOp::Ne if left == right => Ok(VarType::Bool),
+                    BinaryOp::Eq | BinaryOp::Ne => {
+                        Err(ErrorScript::CompareMismatch(op.symbol(), left, right))
+                    }
+                    BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                        expect(op.symbol(), VarType::Int, left)?;
+                        expect(op.symbol(), VarType::Int, right)?;
+                        Ok(VarType::Bool)
+                    }
+                    BinaryOp::Add | BinaryOp::Sub => {
+                        expect(op.symbol(), VarType::Int, left)?;
+                        expect(op.symbol(), VarType::Int, right)?;
+                        Ok(VarType::Int)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Значение выражения при текущих значениях переменных.
+    ///
+    /// `None`, если переменной нет или значение не того типа - такое
+    /// выражение не прошло бы [`Expr::check`]. `&&` и `||` не вычисляют
+    /// правый операнд, если результат известен по левому.
+    pub fn eval(&self, variables: &BTreeMap<String, Value>) -> Option<Value> {
+        let int = |x: &Expr| match x.eval(variables)? {
+            Value::Int(x) => Some(x),
+            _ => None,
+        };
+        let bool = |x: &Expr| match x.eval(variables)? {
+            Value::Bool(x) => Some(x),
+            _ => None,
+        };
+
+        let value = match self {
+            Expr::Value(x) => x.clone(),
+            Expr::Variable(name) => variables.get(name)?.clone(),
+            Expr::Unary(UnaryOp::Not, x) => Value::Bool(!bool(x)?),
+            Expr::Unary(UnaryOp::Neg, x) => Value::Int(int(x)?.saturating_neg()),
+            Expr::Binary(op, left, right) => match op {
+                BinaryOp::Or => Value::Bool(bool(left)? || bool(right)?),
+                BinaryOp::And => Value::Bool(bool(left)? && bool(right)?),
+                BinaryOp::Eq => Value::Bool(left.eval(variables)? == right.eval(variables)?),
+                BinaryOp::Ne => Value::Bool(left.eval(variables)? != right.eval(variables)?),
+                BinaryOp::Lt => Value::Bool(int(left)? < int(right)?),
+                BinaryOp::Le => Value::Bool(int(left)? <= int(right)?),
+                BinaryOp::Gt => Value::Bool(int(left)? > int(right)?),
+                BinaryOp::Ge => Value::Bool(int(left)? >= int(right)?),
+                BinaryOp::Add => Value::Int(int(left)?.saturating_add(int(right)?)),
+                BinaryOp::Sub => Value::Int(int(left)?.saturating_sub(int(right)?)),
+            },
+        };
+        Some(value)
+    }
+
+    /// Записать выражение, расставляя скобки только там, где их требует приоритет.
+    fn write(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        match self {
+            Expr::Value(x) => write!(f, "{}", x),
+            Expr::Variable(name) => write!(f, "{}", name),
+            Expr::Unary(op, operand) => {
+                write!(f, "{}", op.symbol())?;
+                operand.write(f, UNARY_PRECEDENCE)
+            }
+            Expr::Binary(op, left, right) => {
+                let precedence = op.precedence();
+                let parens = precedence < min_precedence;
+                if parens {
+                    write!(f, "(")?;
+                }
+                // Сравнения не цепляются друг за друга: `a < b < c` - ошибка
+                let left_precedence = if op.is_comparison() {
+                    precedence + 1
+                } else {
+                    precedence
+                };
+                left.write(f, left_precedence)?;
+                write!(f, " {} ", op.symbol())?;
+                right.write(f, precedence + 1)?;
+                if parens {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Condition {
+    /// Выполнено ли условие при текущих значениях переменных.
+    pub fn is_met(&self, variables: &BTreeMap<String, Value>) -> bool {
+        self.expr.eval(variables) == Some(Value::Bool(true))
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Выражение, которое [`parse_condition`] читает обратно: `gold >= 10 && !met_guard`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+/// Разобрать условие варианта выбора и проверить его типы по объявлениям `variables`.
+///
+/// Условие - логическое выражение над переменными и значениями:
+/// `||`, `&&`, `!`, сравнения `==`, `!=`, `<`, `<=`, `>`, `>=`, `+` и `-`
+/// над числами и скобки, например `gold >= 10 && !met_guard`.
+///
+/// # Errors
+///
+/// Синтаксическая ошибка с положением в тексте, необъявленная переменная,
+/// операция над значением не того типа или нелогическое условие.
+pub fn parse_condition(text: &str, variables: &[Variable]) -> Result<Expr, ErrorScript> {
+    let mut lexer = Lexer::with_puncts(text, PUNCTS)?.without_line_breaks();
+    let (expr, _) = parse_binary(&mut lexer, 1, 0)?;
+    match lexer.peek() {
+        None => (),
+        // Условие - одно выражение, `;` после него не разделяет операторы
+        Some(Token::Separator) => return Err(lexer.error("конец условия")),
+        Some(_) => return Err(lexer.error("оператор")),
+    }
+
+    match expr.check(variables)? {
+        VarType::Bool => Ok(expr),
+        found => Err(ErrorScript::ConditionType(found)),
+    }
+}
+
+/// Разобрать операции приоритета `precedence` и выше на вложенности `depth`.
+fn parse_binary(lexer: &mut Lexer, precedence: u8, depth: usize) -> Result<Parsed, ErrorScript> {
+    if precedence >= UNARY_PRECEDENCE {
+        return parse_unary(lexer, depth);
+    }
+
+    let (mut left, mut height) = parse_binary(lexer, precedence + 1, depth)?;
+    while let Some(op) = BinaryOp::of_precedence(precedence)
+        .iter()
+        .copied()
+        .find(|x| lexer.eat(x.symbol()))
+    {
+        let (right, right_height) = parse_binary(lexer, precedence + 1, depth)?;
+        height = nested(lexer, height.max(right_height))?;
+        left = Expr::Binary(op, Box::new(left), Box::new(right));
+        if op.is_comparison() {
+            break;
+        }
+    }
+    Ok((left, height))
+}
+
+fn parse_unary(lexer: &mut Lexer, depth: usize) -> Result<Parsed, ErrorScript> {
+    if depth > MAX_DEPTH {
+        return Err(lexer.error(TOO_DEEP));
+    }
+
+    for op in &[UnaryOp::Not, UnaryOp::Neg] {
+        if lexer.eat(op.symbol()) {
+            let (operand, height) = parse_unary(lexer, depth + 1)?;
+            return Ok((Expr::Unary(*op, Box::new(operand)), nested(lexer, height)?));
+        }
+    }
+
+    if lexer.eat("(") {
+        let parsed = parse_binary(lexer, 1, depth + 1)?;
+        lexer.expect(")", "`)`")?;
+        return Ok(parsed);
+    }
+    match lexer.peek() {
+        Some(Token::Ident(name)) if name != "true" && name != "false" => {
+            Ok((Expr::Variable(lexer.ident()?), 0))
+        }
+        Some(Token::Ident(_)) | Some(Token::Int(_)) | Some(Token::Str(_)) => {
+            Ok((Expr::Value(lexer.value()?), 0))
+        }
+        _ => Err(lexer.error("значение, переменная или `(`")),
+    }
+}
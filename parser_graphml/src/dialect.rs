@@ -0,0 +1,427 @@
+use mdo::option::bind;
+use roxmltree::{Document, Node};
+
+use crate::errors::*;
+use crate::keys::{find_data_by_key, find_data_value, GraphMLKey, KeyDomain, KeyTable};
+use crate::source::Splice;
+use crate::types::Rect;
+use crate::writer::escape;
+
+/// Диалект GraphML, зависящий от программы, в которой создан файл.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Dialect {
+    /// yFiles for HTML (yEd Live): текст в `data > x:List > y:Label > y:Label.Text`.
+    YFilesHtml,
+    /// yEd desktop (yFiles for Java): текст в `data > y:ShapeNode > y:NodeLabel`
+    ///     и `data > y:PolyLineEdge > y:EdgeLabel`.
+    YedDesktop,
+    /// Обычный GraphML (Gephi, NetworkX, igraph): текст прямо в `<data>`
+    ///     по ключу с именем `label`, `text` или `description`.
+    Plain,
+}
+
+/// Ключи, по которым хранятся подписи вершин и ребер.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LabelKeys<'a> {
+    pub node: &'a GraphMLKey,
+    pub edge: Option<&'a GraphMLKey>,
+}
+
+/// Имена ключей с текстом в обычном GraphML в порядке приоритета.
+const PLAIN_TEXT_KEY_NAMES: [&str; 3] = ["label", "text", "description"];
+
+/// Шаг пути от `<data>` до элемента с текстом подписи:
+/// локальное имя существующего элемента (`*` - любой) и тег для нового элемента.
+type LabelStep = (&'static str, &'static str);
+
+impl Dialect {
+    /// Определить диалект документа.
+    ///
+    /// Сначала смотрит на комментарий `<!--Created by ...-->`, затем на пространства имен.
+    /// Если документ не похож на созданный в yEd, то считает его обычным GraphML.
+    pub fn detect(doc: &Document<'_>) -> Dialect {
+        Dialect::detect_by_comment(doc)
+            .or_else(|| Dialect::detect_by_namespaces(doc))
+            .unwrap_or(Dialect::Plain)
+    }
+
+    fn detect_by_comment(doc: &Document<'_>) -> Option<Dialect> {
+        const CREATED_BY: &str = "Created by";
+
+        let producer = doc
+            .root()
+            .children()
+            .chain(doc.root_element().children())
+            .filter(|x| x.is_comment())
+            .filter_map(|x| x.text())
+            .find_map(|x| x.trim().strip_prefix(CREATED_BY))?
+            .trim();
+
+        if producer.starts_with("yFiles for HTML") {
+            Some(Dialect::YFilesHtml)
+        } else if producer.starts_with("yEd") || producer.starts_with("yFiles for Java") {
+            Some(Dialect::YedDesktop)
+        } else {
+            None
+        }
+    }
+
+    fn detect_by_namespaces(doc: &Document<'_>) -> Option<Dialect> {
+        const YED_DESKTOP_NS: &str = "http://www.yworks.com/xml/graphml";
+        const YFILES_HTML_NS: [&str; 2] = [
+            "http://www.yworks.com/xml/yfiles-for-html/",
+            "http://www.yworks.com/xml/yfiles-common/3.0",
+        ];
+
+        let namespaces = doc.root_element().namespaces();
+        if namespaces.iter().any(|x| x.uri() == YED_DESKTOP_NS) {
+            Some(Dialect::YedDesktop)
+        } else if namespaces
+            .iter()
+            .any(|x| YFILES_HTML_NS.iter().any(|ns| x.uri().starts_with(ns)))
+        {
+            Some(Dialect::YFilesHtml)
+        } else {
+            None
+        }
+    }
+
+    /// Найти ключи подписей вершин и ребер в таблице ключей.
+    ///
+    /// `location` - положение корневого элемента документа для сообщения об ошибке.
+    pub(crate) fn label_keys(
+        self,
+        keys: &KeyTable,
+        location: Location,
+    ) -> Result<LabelKeys<'_>, Error> {
+        let (node_key, edge_key) = match self {
+            Dialect::YFilesHtml => (
+                keys.find_by_name(KeyDomain::Node, "NodeLabels"),
+                keys.find_by_name(KeyDomain::Edge, "EdgeLabels"),
+            ),
+            Dialect::YedDesktop => (
+                keys.find_by_yfiles_type(KeyDomain::Node, "nodegraphics"),
+                keys.find_by_yfiles_type(KeyDomain::Edge, "edgegraphics"),
+            ),
+            Dialect::Plain => (
+                find_plain_text_key(keys, KeyDomain::Node),
+                find_plain_text_key(keys, KeyDomain::Edge),
+            ),
+        };
+
+        match node_key {
+            Some(node_key) => Ok(LabelKeys {
+                node: node_key,
+                edge: edge_key,
+            }),
+            None => Err(Error::PrepareGraphml(
+                ErrorPrepareGraphML::NotFoundKeyByName(
+                    self.node_labels_key_name().to_string(),
+                    location,
+                ),
+            )),
+        }
+    }
+
+    fn node_labels_key_name(self) -> &'static str {
+        match self {
+            Dialect::YFilesHtml => "NodeLabels",
+            Dialect::YedDesktop => "nodegraphics",
+            Dialect::Plain => PLAIN_TEXT_KEY_NAMES[0],
+        }
+    }
+
+    /// Найти текст подписи вершины.
+    pub(crate) fn find_node_text(self, node: &Node<'_, '_>, key: &GraphMLKey) -> Option<String> {
+        match self {
+            Dialect::YFilesHtml => find_yfiles_html_label_text(node, &key.id).map(String::from),
+            Dialect::YedDesktop => {
+                find_yed_desktop_label_text(node, &key.id, "NodeLabel").map(String::from)
+            }
+            Dialect::Plain => find_data_value(node, key),
+        }
+    }
+
+    /// Найти тексты остальных подписей вершины после первой, по порядку.
+    ///
+    /// Пустые подписи пропускаются. В обычном GraphML подпись всегда одна.
+    /// Подписи с языковой меткой (`[en] ...`) - это переводы, а не абзацы.
+    pub(crate) fn find_node_paragraphs(self, node: &Node<'_, '_>, key: &GraphMLKey) -> Vec<String> {
+        self.find_labels(node, key, KeyDomain::Node)
+            .into_iter()
+            .skip(1)
+            .filter(|x| !x.is_empty() && split_locale_tag(x).is_none())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Найти переводы подписей с языковой меткой: `[en] Hello` после первой подписи.
+    ///
+    /// Возвращает языки и тексты переводов по порядку подписей. Первая подпись -
+    /// всегда основной текст, даже если у нее есть метка.
+    pub(crate) fn find_translated_labels(
+        self,
+        node: &Node<'_, '_>,
+        key: &GraphMLKey,
+        domain: KeyDomain,
+    ) -> Vec<(String, String)> {
+        self.find_labels(node, key, domain)
+            .into_iter()
+            .skip(1)
+            .filter_map(split_locale_tag)
+            .map(|(locale, text)| (locale.to_string(), text.to_string()))
+            .collect()
+    }
+
+    fn find_labels<'a>(
+        self,
+        node: &Node<'a, 'a>,
+        key: &GraphMLKey,
+        domain: KeyDomain,
+    ) -> Vec<&'a str> {
+        let label_tag = match domain {
+            KeyDomain::Node => "NodeLabel",
+            _ => "EdgeLabel",
+        };
+        match self {
+            Dialect::YFilesHtml => find_yfiles_html_label_texts(node, &key.id),
+            Dialect::YedDesktop => find_yed_desktop_label_texts(node, &key.id, label_tag),
+            Dialect::Plain => Vec::new(),
+        }
+    }
+
+    /// Найти положение вершины на схеме.
+    ///
+    /// yFiles for HTML хранит его в `y:RectD` по ключу `NodeGeometry`, yEd desktop -
+    /// в `y:Geometry` внутри графики вершины, а обычный GraphML - в ключах
+    /// `x`, `y`, `width` и `height` (размеры необязательны).
+    pub(crate) fn find_node_position(self, node: &Node<'_, '_>, keys: &KeyTable) -> Option<Rect> {
+        match self {
+            Dialect::YFilesHtml => {
+                let key = keys.find_by_name(KeyDomain::Node, "NodeGeometry")?;
+                let rect = find_data_by_key(node, &key.id)?
+                    .descendants()
+                    .find(|x| x.tag_name().name() == "RectD")?;
+                read_rect(|name| rect.attribute(name), ["X", "Y", "Width", "Height"])
+            }
+            Dialect::YedDesktop => {
+                let key = keys.find_by_yfiles_type(KeyDomain::Node, "nodegraphics")?;
+                let geometry = find_data_by_key(node, &key.id)?
+                    .descendants()
+                    .find(|x| x.tag_name().name() == "Geometry")?;
+                read_rect(
+                    |name| geometry.attribute(name),
+                    ["x", "y", "width", "height"],
+                )
+            }
+            Dialect::Plain => {
+                let value = |name: &str| {
+                    keys.find_by_name(KeyDomain::Node, name)
+                        .and_then(|key| find_data_value(node, key))
+                };
+                let x = value("x")?.parse().ok()?;
+                let y = value("y")?.parse().ok()?;
+                let size = |name| value(name).and_then(|x| x.parse().ok()).unwrap_or(0.0);
+                Some(Rect {
+                    x,
+                    y,
+                    width: size("width"),
+                    height: size("height"),
+                })
+            }
+        }
+    }
+
+    /// Найти текст подписи ребра.
+    pub(crate) fn find_edge_text(self, node: &Node<'_, '_>, key: &GraphMLKey) -> Option<String> {
+        match self {
+            Dialect::YFilesHtml => find_yfiles_html_label_text(node, &key.id).map(String::from),
+            Dialect::YedDesktop => {
+                find_yed_desktop_label_text(node, &key.id, "EdgeLabel").map(String::from)
+            }
+            Dialect::Plain => find_data_value(node, key),
+        }
+    }
+
+    fn label_steps(self, domain: KeyDomain) -> &'static [LabelStep] {
+        match (self, domain) {
+            (Dialect::YFilesHtml, _) => &[
+                ("List", "x:List"),
+                ("Label", "y:Label"),
+                ("Label.Text", "y:Label.Text"),
+            ],
+            (Dialect::YedDesktop, KeyDomain::Node) => {
+                &[("*", "y:ShapeNode"), ("NodeLabel", "y:NodeLabel")]
+            }
+            (Dialect::YedDesktop, _) => &[("*", "y:PolyLineEdge"), ("EdgeLabel", "y:EdgeLabel")],
+            (Dialect::Plain, _) => &[],
+        }
+    }
+
+    /// Открывающая и закрывающая разметка `<data>` с подписью вершины
+    /// (`domain` - [`KeyDomain::Node`]) или ребра, между которыми пишется текст.
+    pub(crate) fn label_markup(self, key: &str, domain: KeyDomain) -> (String, String) {
+        let (open, close) = steps_markup(self.label_steps(domain));
+        (
+            format!("<data key=\"{}\">{}", escape(key), open),
+            format!("{}</data>", close),
+        )
+    }
+
+    /// Куда записать новый текст подписи вершины или ребра `node`.
+    ///
+    /// Если подписи нет, то недостающая разметка вставляется
+    /// в уже существующие элементы, не трогая остальные данные.
+    pub(crate) fn label_splice(
+        self,
+        text: &str,
+        node: &Node<'_, '_>,
+        key: &GraphMLKey,
+        domain: KeyDomain,
+    ) -> Splice {
+        let steps = self.label_steps(domain);
+        let mut current = match find_data_by_key(node, &key.id) {
+            Some(data) => data,
+            None => {
+                let (open, close) = self.label_markup(&key.id, domain);
+                return Splice::content_start(text, node).wrap(&open, &close);
+            }
+        };
+
+        for (i, (name, _)) in steps.iter().enumerate() {
+            let matching: Vec<_> = current
+                .children()
+                .filter(|x| x.is_element() && (*name == "*" || x.tag_name().name() == *name))
+                .collect();
+            // Как и при чтении, предпочитаем подпись с текстом.
+            let next = matching
+                .iter()
+                .find(|x| i + 1 == steps.len() && x.text().is_some())
+                .or_else(|| matching.first());
+            match next {
+                Some(next) => current = *next,
+                None => {
+                    let (open, close) = steps_markup(&steps[i..]);
+                    return Splice::content_start(text, &current).wrap(&open, &close);
+                }
+            }
+        }
+
+        Splice::text_of(text, &current)
+    }
+}
+
+fn steps_markup(steps: &[LabelStep]) -> (String, String) {
+    let open = steps.iter().map(|(_, tag)| format!("<{}>", tag)).collect();
+    let close = steps
+        .iter()
+        .rev()
+        .map(|(_, tag)| format!("</{}>", tag))
+        .collect();
+    (open, close)
+}
+
+fn read_rect<'a>(attribute: impl Fn(&str) -> Option<&'a str>, names: [&str; 4]) -> Option<Rect> {
+    let [x, y, width, height] = names;
+    let number = |name| attribute(name)?.trim().parse::<f64>().ok();
+
+    Some(Rect {
+        x: number(x)?,
+        y: number(y)?,
+        width: number(width).unwrap_or(0.0),
+        height: number(height).unwrap_or(0.0),
+    })
+}
+
+fn find_plain_text_key(keys: &KeyTable, domain: KeyDomain) -> Option<&GraphMLKey> {
+    PLAIN_TEXT_KEY_NAMES
+        .iter()
+        .find_map(|name| keys.find_by_name(domain, name))
+}
+
+fn find_yfiles_html_label_text<'a>(node: &Node<'a, 'a>, key: &str) -> Option<&'a str> {
+    const TAG_LIST: &str = "List";
+    const TAG_LABEL: &str = "Label";
+    const TAG_LABEL_TEXT: &str = "Label.Text";
+
+    mdo! {
+        data =<< find_data_by_key(node, key);
+        l =<< data.children()
+            .find(|x| x.tag_name().name() == TAG_LIST);
+        lbl =<< l.children()
+            .find(|x| x.tag_name().name() == TAG_LABEL);
+        lbl_txt =<< lbl.children()
+            .find(|x| x.tag_name().name() == TAG_LABEL_TEXT);
+
+        ret Some(lbl_txt.text().unwrap_or(""))
+    }
+}
+
+fn find_yfiles_html_label_texts<'a>(node: &Node<'a, 'a>, key: &str) -> Vec<&'a str> {
+    const TAG_LIST: &str = "List";
+    const TAG_LABEL: &str = "Label";
+    const TAG_LABEL_TEXT: &str = "Label.Text";
+
+    let list = find_data_by_key(node, key)
+        .and_then(|data| data.children().find(|x| x.tag_name().name() == TAG_LIST));
+    list.iter()
+        .flat_map(|x| x.children())
+        .filter(|x| x.tag_name().name() == TAG_LABEL)
+        .filter_map(|x| x.children().find(|x| x.tag_name().name() == TAG_LABEL_TEXT))
+        .map(|x| x.text().unwrap_or(""))
+        .collect()
+}
+
+/// Коды языков ISO 639-1.
+const LANGUAGE_CODES: &str = "aa ab ae af ak am an ar as av ay az ba be bg bh bi bm bn bo br bs \
+    ca ce ch co cr cs cu cv cy da de dv dz ee el en eo es et eu fa ff fi fj fo fr fy ga gd gl gn \
+    gu gv ha he hi ho hr ht hu hy hz ia id ie ig ii ik io is it iu ja jv ka kg ki kj kk kl km kn \
+    ko kr ks ku kv kw ky la lb lg li ln lo lt lu lv mg mh mi mk ml mn mr ms mt my na nb nd ne ng \
+    nl nn no nr nv ny oc oj om or os pa pi pl ps pt qu rm rn ro ru rw sa sc sd se sg si sk sl sm \
+    sn so sq sr ss st su sv sw ta te tg th ti tk tl tn to tr ts tt tw ty ug uk ur uz ve vi vo wa \
+    wo xh yi yo za zh zu";
+
+/// Отделить языковую метку в начале подписи: `[en] Hello` -> (`en`, `Hello`).
+///
+/// Метка - код языка ISO 639-1 в нижнем регистре, за которым может идти
+/// регион через `-` или `_` (`[en-US]`). Остальное в скобках, например имя
+/// персонажа (`[Bob] Hi there`), остается частью текста.
+pub(crate) fn split_locale_tag(text: &str) -> Option<(&str, &str)> {
+    let rest = text.trim_start().strip_prefix('[')?;
+    let end = rest.find(']')?;
+    let locale = &rest[..end];
+
+    let mut parts = locale.split(['-', '_']);
+    let language = parts.next()?;
+    let valid_language = LANGUAGE_CODES.split_whitespace().any(|x| x == language);
+    let valid_region = parts.all(|x| !x.is_empty() && x.chars().all(|x| x.is_ascii_alphanumeric()));
+    if !valid_language || !valid_region {
+        return None;
+    }
+
+    Some((locale, rest[end + 1..].trim_start()))
+}
+
+fn find_yed_desktop_label_text<'a>(
+    node: &Node<'a, 'a>,
+    key: &str,
+    label_tag: &str,
+) -> Option<&'a str> {
+    find_yed_desktop_label_texts(node, key, label_tag)
+        .into_iter()
+        .next()
+}
+
+fn find_yed_desktop_label_texts<'a>(
+    node: &Node<'a, 'a>,
+    key: &str,
+    label_tag: &str,
+) -> Vec<&'a str> {
+    find_data_by_key(node, key)
+        .iter()
+        .flat_map(|x| x.descendants())
+        .filter(|x| x.tag_name().name() == label_tag)
+        .filter_map(|x| x.text())
+        .collect()
+}
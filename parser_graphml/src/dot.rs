@@ -0,0 +1,868 @@
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::condition::{parse_condition, Condition};
+use crate::parser_impl::{find_chapter_entry, find_start_scene, is_true_value, ErrorStartScene};
+use crate::rich_text::{parse_tag, STYLE_TAGS};
+use crate::script::{parse_effects, parse_variables, ErrorScript};
+use crate::types::*;
+
+/// Ошибки чтения квеста из Graphviz DOT.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorDot {
+    /// Синтаксическая ошибка: что ожидалось, строка и столбец (с 1).
+    Syntax(&'static str, usize, usize),
+    /// Атрибут `quest_start` указывает на несуществующую сцену.
+    NotFoundScene(String),
+    /// Не удалось определить стартовую сцену: нет ни отмеченной сцены,
+    ///     ни сцены без входящих ребер.
+    NotFoundStartScene,
+    /// Стартовая сцена неоднозначна: подходят сцены с перечисленными идентификаторами.
+    AmbiguousStartScene(Vec<String>),
+    /// Ошибка в атрибуте `variables` графа или в `effects` и `condition` ребра
+    /// с указанным идентификатором.
+    InvalidScript(String, ErrorScript),
+}
+
+impl fmt::Display for ErrorDot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorDot::Syntax(expected, line, column) => write!(
+                f,
+                "ожидается {} (строка {}, столбец {})",
+                expected, line, column
+            ),
+            ErrorDot::NotFoundScene(id) => {
+                write!(f, "не найдена сцена с идентификатором `{}`", id)
+            }
+            ErrorDot::NotFoundStartScene => write!(
+                f,
+                "не удалось определить стартовую сцену, отметьте ее атрибутом `start=true`"
+            ),
+            ErrorDot::AmbiguousStartScene(ids) => write!(
+                f,
+                "стартовая сцена неоднозначна, подходят: {}",
+                ids.join(", ")
+            ),
+            ErrorDot::InvalidScript(id, e) => write!(f, "ошибка в скрипте `{}`: {}", id, e),
+        }
+    }
+}
+
+impl std::error::Error for ErrorDot {}
+
+/// Прочитать квест из Graphviz DOT.
+///
+/// Вершины становятся сценами, а ребра - вариантами выбора: текст берется
+/// из атрибута `label` (у вершины без него, как и в Graphviz, это ее имя),
+/// абзацы сцены разделяются пустой строкой (`\n\n`). Атрибуты `tooltip`,
+/// `URL` и `class` задают описание, ссылку и теги через пробел, `id` ребра -
+/// идентификатор варианта выбора. Кластеры (`subgraph cluster_...`)
+/// становятся главами с названием из `label`.
+///
+/// Стартовая сцена отмечается атрибутом `start=true` у вершины или
+/// `quest_start` у графа, иначе это единственная сцена без входящих ребер.
+/// Атрибут `start` графа Graphviz использует при раскладке, поэтому сцены
+/// он не отмечает. Начальная сцена главы задается атрибутом `quest_start`
+/// кластера.
+///
+/// # Errors
+///
+/// Синтаксическая ошибка DOT или неоднозначная стартовая сцена.
+pub fn read_dot(text: &str) -> Result<Quest, ErrorDot> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+        nodes: Vec::new(),
+        node_indexes: HashMap::new(),
+        edges: Vec::new(),
+        chapters: Vec::new(),
+        attributes: Vec::new(),
+    };
+    parser.parse_graph()?;
+    parser.into_quest()
+}
+
+/// Записать квест в Graphviz DOT, например для `dot -Tsvg`.
+///
+/// Пишет то, что читает [`read_dot`]: тексты, описания, ссылки, теги, главы,
+/// отметку стартовой сцены, переменные, действия и условия вариантов выбора, поэтому
+/// квест читается обратно без изменений.
+/// Переводы и положения сцен в DOT не записываются.
+pub fn write_dot(quest: &Quest) -> String {
+    let graph = &quest.graph;
+    let mut out = String::new();
+    out.push_str("digraph quest {\n");
+    out.push_str("\tnode [shape=box, style=rounded];\n");
+    if !quest.variables.is_empty() {
+        out.push_str(&format!(
+            "\t{}={};\n",
+            VARIABLES_ATTRIBUTE,
+            quote(&join_script(&quest.variables))
+        ));
+    }
+
+    write_dot_nodes(&mut out, quest, None, 1);
+
+    for edge in graph.edge_references() {
+        let weight = edge.weight();
+        let mut attributes = vec![("id", weight.id.clone())];
+        if !weight.text.is_empty() {
+            attributes.push(("label", weight.text.clone()));
+        }
+        push_annotations(
+            &mut attributes,
+            &weight.description,
+            &weight.url,
+            &weight.tags,
+        );
+        if !weight.effects.is_empty() {
+            attributes.push((EFFECTS_ATTRIBUTE, join_script(&weight.effects)));
+        }
+        if let Some(condition) = &weight.condition {
+            attributes.push((CONDITION_ATTRIBUTE, condition.expr.to_string()));
+            attributes.extend(condition.locked.clone().map(|x| (LOCKED_ATTRIBUTE, x)));
+        }
+        out.push_str(&format!(
+            "\t{} -> {}{};\n",
+            quote(&graph[edge.source()].id),
+            quote(&graph[edge.target()].id),
+            attribute_list(&attributes),
+        ));
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+/// Объявления переменных или действия одной строкой через `;`.
+fn join_script<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Записать сцены и кластеры глав, которые непосредственно входят в главу `chapter`.
+fn write_dot_nodes(out: &mut String, quest: &Quest, chapter: Option<usize>, depth: usize) {
+    let graph = &quest.graph;
+    let pad = "\t".repeat(depth);
+
+    for ix in graph
+        .node_indices()
+        .filter(|ix| quest.chapter_to_write(*ix) == chapter)
+    {
+        let vertex = &graph[ix];
+        let mut attributes = vec![("label", vertex.blocks().collect::<Vec<_>>().join("\n\n"))];
+        if ix == quest.start {
+            attributes.push(("start", "true".to_string()));
+        }
+        push_annotations(
+            &mut attributes,
+            &vertex.description,
+            &vertex.url,
+            &vertex.tags,
+        );
+        attributes.extend(
+            vertex
+                .ending_to_write()
+                .map(|x| (ENDING_TAG, x.to_string())),
+        );
+        out.push_str(&format!(
+            "{}{}{};\n",
+            pad,
+            quote(&vertex.id),
+            attribute_list(&attributes)
+        ));
+    }
+
+    for (i, x) in quest.chapters.iter().enumerate() {
+        if x.parent != chapter {
+            continue;
+        }
+        out.push_str(&format!(
+            "{}subgraph {} {{\n",
+            pad,
+            quote(&format!("{}{}", CLUSTER_PREFIX, x.id))
+        ));
+        out.push_str(&format!("{}\tlabel={};\n", pad, quote(&x.title)));
+        if let Some(entry) = x.entry {
+            out.push_str(&format!(
+                "{}\t{}={};\n",
+                pad,
+                START_ATTRIBUTE,
+                quote(&graph[entry].id)
+            ));
+        }
+        write_dot_nodes(out, quest, Some(i), depth + 1);
+        out.push_str(&format!("{}}}\n", pad));
+    }
+}
+
+fn push_annotations(
+    attributes: &mut Vec<(&'static str, String)>,
+    description: &Option<String>,
+    url: &Option<String>,
+    tags: &[String],
+) {
+    attributes.extend(description.clone().map(|x| ("tooltip", x)));
+    attributes.extend(url.clone().map(|x| ("URL", x)));
+    if !tags.is_empty() {
+        attributes.push(("class", tags.join(" ")));
+    }
+}
+
+fn attribute_list(attributes: &[(&str, String)]) -> String {
+    if attributes.is_empty() {
+        return String::new();
+    }
+    let fields: Vec<String> = attributes
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, quote(value)))
+        .collect();
+    format!(" [{}]", fields.join(", "))
+}
+
+/// Строка DOT в кавычках.
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for x in text.chars() {
+        match x {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(x),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Префикс имени подграфа, который Graphviz рисует рамкой, - главы квеста.
+const CLUSTER_PREFIX: &str = "cluster_";
+
+/// Атрибут графа и кластера с идентификатором стартовой сцены квеста или главы.
+const START_ATTRIBUTE: &str = "quest_start";
+
+/// Атрибут графа с объявлениями переменных квеста.
+const VARIABLES_ATTRIBUTE: &str = "variables";
+
+/// Атрибут ребра с действиями над переменными при выборе варианта.
+const EFFECTS_ATTRIBUTE: &str = "effects";
+
+/// Атрибуты ребра с условием доступности варианта выбора и причиной недоступности.
+const CONDITION_ATTRIBUTE: &str = "condition";
+const LOCKED_ATTRIBUTE: &str = "locked";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    /// Идентификатор, число, строка в кавычках или HTML строка.
+    Id(String),
+    /// `->` или `--`.
+    EdgeOp,
+    Symbol(char),
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: Kind,
+    line: usize,
+    column: usize,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ErrorDot> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+    // Начало строки: строки с `#` в начале - вывод препроцессора, они пропускаются
+    let mut line_start = true;
+
+    macro_rules! next {
+        () => {{
+            let x = chars.next();
+            if x == Some('\n') {
+                line += 1;
+                column = 1;
+                line_start = true;
+            } else if x.is_some() {
+                column += 1;
+            }
+            x
+        }};
+    }
+
+    while let Some(&x) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let at_line_start = line_start && x != '\n';
+        if !x.is_whitespace() {
+            line_start = false;
+        }
+
+        let kind = match x {
+            _ if x.is_whitespace() => {
+                next!();
+                continue;
+            }
+            '#' if at_line_start => {
+                while !matches!(next!(), Some('\n') | None) {}
+                continue;
+            }
+            '/' => {
+                next!();
+                match next!() {
+                    Some('/') => while !matches!(next!(), Some('\n') | None) {},
+                    Some('*') => {
+                        let mut star = false;
+                        loop {
+                            match next!() {
+                                Some('/') if star => break,
+                                Some(x) => star = x == '*',
+                                None => {
+                                    return Err(ErrorDot::Syntax("`*/`", line, column));
+                                }
+                            }
+                        }
+                    }
+                    _ => return Err(ErrorDot::Syntax("комментарий", start_line, start_column)),
+                }
+                continue;
+            }
+            '"' => {
+                next!();
+                let mut value = String::new();
+                loop {
+                    match next!() {
+                        Some('"') => break,
+                        Some('\\') => match next!() {
+                            Some('"') => value.push('"'),
+                            Some('\\') => value.push('\\'),
+                            Some('n') | Some('l') | Some('r') => value.push('\n'),
+                            // Перенос длинной строки
+                            Some('\n') => (),
+                            Some(x) => {
+                                value.push('\\');
+                                value.push(x);
+                            }
+                            None => break,
+                        },
+                        Some(x) => value.push(x),
+                        None => return Err(ErrorDot::Syntax("`\"`", line, column)),
+                    }
+                }
+                Kind::Id(value)
+            }
+            '<' => {
+                next!();
+                let mut value = String::new();
+                let mut depth = 1;
+                loop {
+                    let x = next!().ok_or(ErrorDot::Syntax("`>`", line, column))?;
+                    match x {
+                        '<' => depth += 1,
+                        '>' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => (),
+                    }
+                    value.push(x);
+                }
+                Kind::Id(html_label(&value))
+            }
+            '-' => {
+                next!();
+                match chars.peek() {
+                    Some('>') | Some('-') => {
+                        next!();
+                        Kind::EdgeOp
+                    }
+                    _ => Kind::Id(format!("-{}", take_id(&mut chars, &mut column))),
+                }
+            }
+            _ if x.is_alphanumeric() || x == '_' || x == '.' || !x.is_ascii() => {
+                Kind::Id(take_id(&mut chars, &mut column))
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' | ':' | '+' => {
+                next!();
+                Kind::Symbol(x)
+            }
+            _ => return Err(ErrorDot::Syntax("идентификатор", start_line, start_column)),
+        };
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    Ok(tokens)
+}
+
+/// Идентификатор или число без кавычек.
+fn take_id(chars: &mut std::iter::Peekable<std::str::Chars<'_>>, column: &mut usize) -> String {
+    let mut value = String::new();
+    while let Some(&x) = chars.peek() {
+        if !(x.is_alphanumeric() || x == '_' || x == '.' || !x.is_ascii()) {
+            break;
+        }
+        value.push(x);
+        chars.next();
+        *column += 1;
+    }
+    value
+}
+
+/// Текст HTML строки Graphviz: `<BR>` в любой записи и с любыми атрибутами -
+/// перевод строки, теги оформления сохраняются, их разбирает
+/// [`RichText`](crate::rich_text::RichText). Остальные теги (`<TABLE>`, `<TD>`,
+/// `<U>`...) отбрасываются, их содержимое остается.
+fn html_label(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(x) = rest.find('<') {
+        out.push_str(&rest[..x]);
+        rest = &rest[x..];
+        match parse_tag(rest) {
+            Some((tag, len)) => {
+                if tag.name == "br" {
+                    if !tag.closing {
+                        out.push('\n');
+                    }
+                } else if STYLE_TAGS.contains(&tag.name.as_str()) {
+                    out.push_str(&rest[..len]);
+                }
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+type Attributes = Vec<(String, String)>;
+
+fn find<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .rev()
+        .find(|(x, _)| x == name)
+        .map(|(_, x)| x.as_str())
+}
+
+struct DotNode {
+    id: String,
+    attributes: Attributes,
+    chapter: Option<usize>,
+}
+
+struct DotEdge {
+    source: usize,
+    target: usize,
+    attributes: Attributes,
+}
+
+struct DotChapter {
+    id: String,
+    parent: Option<usize>,
+    attributes: Attributes,
+}
+
+/// Атрибуты по умолчанию (`node [...]`, `edge [...]`) и глава текущего подграфа.
+#[derive(Clone, Default)]
+struct Scope {
+    node: Attributes,
+    edge: Attributes,
+    chapter: Option<usize>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    nodes: Vec<DotNode>,
+    node_indexes: HashMap<String, usize>,
+    edges: Vec<DotEdge>,
+    chapters: Vec<DotChapter>,
+    /// Атрибуты графа верхнего уровня.
+    attributes: Attributes,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Kind> {
+        self.tokens.get(self.pos).map(|x| &x.kind)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Kind::Id(x)) if x.eq_ignore_ascii_case(keyword))
+    }
+
+    fn error(&self, expected: &'static str) -> ErrorDot {
+        match self.tokens.get(self.pos).or_else(|| self.tokens.last()) {
+            Some(x) => ErrorDot::Syntax(expected, x.line, x.column),
+            None => ErrorDot::Syntax(expected, 1, 1),
+        }
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Kind::Symbol(symbol)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: char, expected: &'static str) -> Result<(), ErrorDot> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    /// Идентификатор, в том числе строки, склеенные через `+`.
+    fn id(&mut self) -> Result<String, ErrorDot> {
+        let mut value = match self.peek() {
+            Some(Kind::Id(x)) => x.clone(),
+            _ => return Err(self.error("идентификатор")),
+        };
+        self.pos += 1;
+        while self.peek() == Some(&Kind::Symbol('+')) {
+            self.pos += 1;
+            match self.peek() {
+                Some(Kind::Id(x)) => value.push_str(x),
+                _ => return Err(self.error("строка после `+`")),
+            }
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn parse_graph(&mut self) -> Result<(), ErrorDot> {
+        if self.peek_keyword("strict") {
+            self.pos += 1;
+        }
+        if !(self.peek_keyword("digraph") || self.peek_keyword("graph")) {
+            return Err(self.error("`digraph` или `graph`"));
+        }
+        self.pos += 1;
+        if !self.eat('{') {
+            self.id()?;
+            self.expect('{', "`{`")?;
+        }
+        self.parse_statements(&mut Scope::default())?;
+        self.expect('}', "`}`")?;
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("конец файла")),
+        }
+    }
+
+    /// Разобрать операторы до `}`; возвращает упомянутые в них вершины.
+    fn parse_statements(&mut self, scope: &mut Scope) -> Result<Vec<usize>, ErrorDot> {
+        let mut nodes = Vec::new();
+        while !matches!(self.peek(), Some(Kind::Symbol('}')) | None) {
+            self.parse_statement(scope, &mut nodes)?;
+            self.eat(';');
+        }
+        Ok(nodes)
+    }
+
+    fn parse_statement(
+        &mut self,
+        scope: &mut Scope,
+        nodes: &mut Vec<usize>,
+    ) -> Result<(), ErrorDot> {
+        let is_subgraph = self.peek_keyword("subgraph") || self.peek() == Some(&Kind::Symbol('{'));
+        let is_default = ["graph", "node", "edge"]
+            .iter()
+            .any(|x| self.peek_keyword(x))
+            && matches!(
+                self.tokens.get(self.pos + 1).map(|x| &x.kind),
+                Some(Kind::Symbol('['))
+            );
+        let is_assignment = matches!(
+            self.tokens.get(self.pos + 1).map(|x| &x.kind),
+            Some(Kind::Symbol('='))
+        );
+
+        if is_default {
+            let keyword = self.id()?.to_lowercase();
+            let attributes = self.parse_attributes()?;
+            match keyword.as_str() {
+                "node" => scope.node.extend(attributes),
+                "edge" => scope.edge.extend(attributes),
+                _ => self.graph_attributes(scope).extend(attributes),
+            }
+            return Ok(());
+        }
+        if is_assignment && !is_subgraph {
+            let name = self.id()?;
+            self.expect('=', "`=`")?;
+            let value = self.id()?;
+            self.graph_attributes(scope).push((name, value));
+            return Ok(());
+        }
+
+        let mut operand = if is_subgraph {
+            self.parse_subgraph(scope)?
+        } else {
+            vec![self.parse_node_id(scope)?]
+        };
+        nodes.extend(&operand);
+
+        if self.peek() != Some(&Kind::EdgeOp) {
+            if !is_subgraph {
+                let attributes = self.parse_attributes()?;
+                self.nodes[operand[0]].attributes.extend(attributes);
+            }
+            return Ok(());
+        }
+
+        let mut chain = Vec::new();
+        while self.peek() == Some(&Kind::EdgeOp) {
+            self.pos += 1;
+            let next = if self.peek_keyword("subgraph") || self.peek() == Some(&Kind::Symbol('{')) {
+                self.parse_subgraph(scope)?
+            } else {
+                vec![self.parse_node_id(scope)?]
+            };
+            nodes.extend(&next);
+            chain.push((std::mem::replace(&mut operand, next.clone()), next));
+        }
+
+        let mut attributes = scope.edge.clone();
+        attributes.extend(self.parse_attributes()?);
+        for (sources, targets) in chain {
+            for source in &sources {
+                for target in &targets {
+                    self.edges.push(DotEdge {
+                        source: *source,
+                        target: *target,
+                        attributes: attributes.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn graph_attributes(&mut self, scope: &Scope) -> &mut Attributes {
+        match scope.chapter {
+            Some(i) => &mut self.chapters[i].attributes,
+            None => &mut self.attributes,
+        }
+    }
+
+    fn parse_subgraph(&mut self, scope: &Scope) -> Result<Vec<usize>, ErrorDot> {
+        let mut inner = scope.clone();
+        if self.peek_keyword("subgraph") {
+            self.pos += 1;
+            if self.peek() != Some(&Kind::Symbol('{')) {
+                let name = self.id()?;
+                if let Some(id) = name.strip_prefix("cluster") {
+                    inner.chapter = Some(self.chapters.len());
+                    self.chapters.push(DotChapter {
+                        id: id.strip_prefix('_').unwrap_or(id).to_string(),
+                        parent: scope.chapter,
+                        attributes: Vec::new(),
+                    });
+                }
+            }
+        }
+        self.expect('{', "`{`")?;
+        let nodes = self.parse_statements(&mut inner)?;
+        self.expect('}', "`}`")?;
+        Ok(nodes)
+    }
+
+    fn parse_node_id(&mut self, scope: &Scope) -> Result<usize, ErrorDot> {
+        let id = self.id()?;
+        // Порт вершины (`a:n`, `a:port:sw`) на квест не влияет
+        while self.eat(':') {
+            self.id()?;
+        }
+
+        if let Some(&i) = self.node_indexes.get(&id) {
+            // Как в Graphviz, вершина входит в самый вложенный кластер, где она упомянута
+            if let Some(chapter) = scope.chapter {
+                let current = self.nodes[i].chapter;
+                if current.is_none_or(|x| self.is_ancestor(x, chapter)) {
+                    self.nodes[i].chapter = Some(chapter);
+                }
+            }
+            return Ok(i);
+        }
+        let i = self.nodes.len();
+        self.node_indexes.insert(id.clone(), i);
+        self.nodes.push(DotNode {
+            id,
+            attributes: scope.node.clone(),
+            chapter: scope.chapter,
+        });
+        Ok(i)
+    }
+
+    /// Вложена ли глава `chapter` в главу `ancestor`.
+    fn is_ancestor(&self, ancestor: usize, chapter: usize) -> bool {
+        let mut current = self.chapters[chapter].parent;
+        while let Some(x) = current {
+            if x == ancestor {
+                return true;
+            }
+            current = self.chapters[x].parent;
+        }
+        false
+    }
+
+    /// Списки атрибутов `[a=1, b=2][c=3]`, если они есть.
+    fn parse_attributes(&mut self) -> Result<Attributes, ErrorDot> {
+        let mut attributes = Vec::new();
+        while self.eat('[') {
+            while !self.eat(']') {
+                let name = self.id()?;
+                self.expect('=', "`=`")?;
+                let value = self.id()?;
+                attributes.push((name, value));
+                if !self.eat(',') {
+                    self.eat(';');
+                }
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn into_quest(self) -> Result<Quest, ErrorDot> {
+        let mut graph = Graph::<Vertex, Edge>::new();
+
+        for node in &self.nodes {
+            let label = find(&node.attributes, "label").unwrap_or("\\N");
+            let label = label.replace("\\N", &node.id);
+            let mut blocks = label
+                .split("\n\n")
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty());
+            let tags = find_tags(&node.attributes);
+            graph.add_node(Vertex {
+                id: node.id.clone(),
+                text: blocks.next().unwrap_or_default(),
+                paragraphs: blocks.collect(),
+                description: find(&node.attributes, "tooltip").map(String::from),
+                url: find_url(&node.attributes),
+                ending: Ending::find(find(&node.attributes, ENDING_TAG), &tags),
+                tags,
+                chapter: node.chapter,
+                ..Vertex::default()
+            });
+        }
+
+        let variables = match find(&self.attributes, VARIABLES_ATTRIBUTE) {
+            Some(text) => parse_variables(text)
+                .map_err(|e| ErrorDot::InvalidScript(VARIABLES_ATTRIBUTE.to_string(), e))?,
+            None => Vec::new(),
+        };
+
+        for (i, edge) in self.edges.iter().enumerate() {
+            let id = find(&edge.attributes, "id").map_or_else(|| format!("e{}", i), String::from);
+            let effects = match find(&edge.attributes, EFFECTS_ATTRIBUTE) {
+                Some(text) => parse_effects(text, &variables)
+                    .map_err(|e| ErrorDot::InvalidScript(id.clone(), e))?,
+                None => Vec::new(),
+            };
+            let condition = match find(&edge.attributes, CONDITION_ATTRIBUTE) {
+                Some(text) => Some(Condition {
+                    expr: parse_condition(text, &variables)
+                        .map_err(|e| ErrorDot::InvalidScript(id.clone(), e))?,
+                    locked: find(&edge.attributes, LOCKED_ATTRIBUTE).map(String::from),
+                }),
+                None => None,
+            };
+            graph.add_edge(
+                NodeIndex::new(edge.source),
+                NodeIndex::new(edge.target),
+                Edge {
+                    id,
+                    text: find(&edge.attributes, "label")
+                        .unwrap_or_default()
+                        .to_string(),
+                    description: find(&edge.attributes, "tooltip").map(String::from),
+                    url: find_url(&edge.attributes),
+                    tags: find_tags(&edge.attributes),
+                    effects,
+                    condition,
+                    ..Edge::default()
+                },
+            );
+        }
+
+        let find_scene = |id: &str| {
+            self.node_indexes
+                .get(id)
+                .map(|x| NodeIndex::new(*x))
+                .ok_or_else(|| ErrorDot::NotFoundScene(id.to_string()))
+        };
+
+        let mut start_ids: Vec<(String, ())> = self
+            .nodes
+            .iter()
+            .filter(|x| find(&x.attributes, "start").is_some_and(is_true_value))
+            .map(|x| (x.id.clone(), ()))
+            .collect();
+        start_ids.extend(find(&self.attributes, START_ATTRIBUTE).map(|x| (x.to_string(), ())));
+        let start = find_start_scene(&graph, start_ids).map_err(|e| match e {
+            ErrorStartScene::NotFound => ErrorDot::NotFoundStartScene,
+            ErrorStartScene::Ambiguous(ids, _) => ErrorDot::AmbiguousStartScene(ids),
+            ErrorStartScene::NotFoundScene(id, _) => ErrorDot::NotFoundScene(id),
+        })?;
+
+        let parents: Vec<Option<usize>> = self.chapters.iter().map(|x| x.parent).collect();
+        let edge_ids = self.edges.iter().map(|x| {
+            (
+                self.nodes[x.source].id.as_str(),
+                self.nodes[x.target].id.as_str(),
+            )
+        });
+        let mut chapters = Vec::new();
+        for (i, x) in self.chapters.iter().enumerate() {
+            let entry = match find(&x.attributes, START_ATTRIBUTE) {
+                Some(id) => Some(find_scene(id)?),
+                None => find_chapter_entry(&graph, i, &parents, edge_ids.clone()),
+            };
+            chapters.push(Chapter {
+                id: x.id.clone(),
+                title: find(&x.attributes, "label").unwrap_or(&x.id).to_string(),
+                parent: x.parent,
+                entry,
+                position: None,
+            });
+        }
+
+        Ok(Quest {
+            graph,
+            start,
+            chapters,
+            locale: None,
+            variables,
+            source: None,
+        })
+    }
+}
+
+fn find_url(attributes: &[(String, String)]) -> Option<String> {
+    find(attributes, "URL")
+        .or_else(|| find(attributes, "href"))
+        .map(String::from)
+}
+
+fn find_tags(attributes: &[(String, String)]) -> Vec<String> {
+    find(attributes, "class")
+        .map(|x| x.split_whitespace().map(String::from).collect())
+        .unwrap_or_default()
+}
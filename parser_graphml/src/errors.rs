@@ -1,8 +1,94 @@
+use crate::script::{ErrorScript, Variable};
+use crate::source::GraphMLSource;
 use crate::types::*;
-use petgraph::graph::Graph;
+use petgraph::graph::{Graph, NodeIndex};
+use roxmltree::Node;
+use std::fmt;
 
-/// Результат парсинга графа из GraphML формата.
-pub type ResultGraphML<'a> = Result<Graph<Vertex, Edge>, Error>;
+/// Результат парсинга квеста из GraphML формата.
+pub type ResultGraphML<'a> = Result<Quest, Error>;
+
+/// Результат парсинга квеста из GraphML формата в режиме диагностики.
+#[derive(Debug)]
+pub struct Diagnostics {
+    /// Граф, который удалось построить, пропустив ошибочные элементы.
+    pub graph: Graph<Vertex, Edge>,
+    /// Стартовая сцена, если ее удалось определить.
+    pub start: Option<NodeIndex>,
+    /// Главы квеста.
+    pub chapters: Vec<Chapter>,
+    /// Язык основного текста.
+    pub locale: Option<String>,
+    /// Переменные квеста.
+    pub variables: Vec<Variable>,
+    /// Все найденные ошибки в порядке обнаружения.
+    pub errors: Vec<Error>,
+    /// Исходный документ, если удалось разобрать XML и найти ключи подписей.
+    pub source: Option<GraphMLSource>,
+}
+
+impl Diagnostics {
+    /// Получить квест, если ошибок нет, иначе первую найденную ошибку.
+    pub fn into_result<'a>(self) -> ResultGraphML<'a> {
+        match (self.errors.into_iter().next(), self.start) {
+            (Some(error), _) => Err(error),
+            (None, Some(start)) => Ok(Quest {
+                graph: self.graph,
+                start,
+                chapters: self.chapters,
+                locale: self.locale,
+                variables: self.variables,
+                source: self.source,
+            }),
+            (None, None) => Err(Error::FormatGraph(ErrorFormatGraph::NotFoundStartScene(
+                Location::default(),
+            ))),
+        }
+    }
+}
+
+/// Положение элемента в GraphML документе.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// Идентификатор элемента GraphML (атрибут `id`), если он задан.
+    pub element_id: Option<String>,
+    /// Номер строки, начиная с 1.
+    pub line: u32,
+    /// Номер столбца, начиная с 1.
+    pub column: u32,
+}
+
+impl Location {
+    /// Положение начала элемента XML в документе.
+    pub(crate) fn of(node: &Node<'_, '_>) -> Location {
+        let pos = node.document().text_pos_at(node.range().start);
+
+        Location {
+            element_id: node.attribute("id").map(String::from),
+            line: pos.row,
+            column: pos.col,
+        }
+    }
+}
+
+impl Default for Location {
+    fn default() -> Location {
+        Location {
+            element_id: None,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(id) = &self.element_id {
+            write!(f, "`{}`, ", id)?;
+        }
+        write!(f, "строка {}, столбец {}", self.line, self.column)
+    }
+}
 
 /// Ошибки парсинга графа из GraphML формата.
 #[derive(Debug)]
@@ -17,16 +103,142 @@ pub enum Error {
     FormatGraph(ErrorFormatGraph),
 }
 
+impl Error {
+    /// Положение элемента, на котором произошла ошибка.
+    pub fn location(&self) -> Location {
+        match self {
+            Error::ParseXMLDocument(e) => Location {
+                element_id: None,
+                line: e.pos().row,
+                column: e.pos().col,
+            },
+            Error::PrepareGraphml(e) => e.location().clone(),
+            Error::FormatGraph(e) => e.location().clone(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParseXMLDocument(e) => write!(f, "некорректный XML: {}", e),
+            Error::PrepareGraphml(e) => write!(f, "{} ({})", e, e.location()),
+            Error::FormatGraph(e) => write!(f, "{} ({})", e, e.location()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ParseXMLDocument(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 /// Ошибки этапа подготовления графа из GraphML формата.
 #[derive(Debug)]
 pub enum ErrorPrepareGraphML {
     /// Не найдена атрибут по ключу.
-    NotFoundAttrByKey(String),
+    NotFoundAttrByKey(String, Location),
+    /// Не найдено объявление ключа `<key>` с указанным семантическим именем.
+    NotFoundKeyByName(String, Location),
+    /// Не найдена подпись у вершины.
+    NotFoundNodeLabel(Location),
+    /// Ошибка в объявлениях переменных графа или в изменениях переменных варианта выбора.
+    InvalidScript(ErrorScript, Location),
+}
+
+impl ErrorPrepareGraphML {
+    /// Положение элемента, на котором произошла ошибка.
+    pub fn location(&self) -> &Location {
+        match self {
+            ErrorPrepareGraphML::NotFoundAttrByKey(_, location)
+            | ErrorPrepareGraphML::NotFoundKeyByName(_, location)
+            | ErrorPrepareGraphML::NotFoundNodeLabel(location)
+            | ErrorPrepareGraphML::InvalidScript(_, location) => location,
+        }
+    }
 }
 
+impl fmt::Display for ErrorPrepareGraphML {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorPrepareGraphML::NotFoundAttrByKey(key, _) => {
+                write!(f, "не найден атрибут `{}`", key)
+            }
+            ErrorPrepareGraphML::NotFoundKeyByName(name, _) => {
+                write!(f, "не найдено объявление ключа `{}`", name)
+            }
+            ErrorPrepareGraphML::NotFoundNodeLabel(_) => write!(f, "у сцены нет подписи"),
+            ErrorPrepareGraphML::InvalidScript(e, _) => write!(f, "ошибка в скрипте: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ErrorPrepareGraphML {}
+
 /// Ошибки этапа форматирования GraphML представления графа в Petgraph граф.
 #[derive(Debug)]
 pub enum ErrorFormatGraph {
     /// Не найдена нода графа по переданному идентификатору.
-    NotFoundNodeById(String),
+    NotFoundNodeById(String, Location),
+    /// Вершина с таким идентификатором уже встречалась в графе.
+    DuplicateNodeId(Location),
+    /// Не удалось определить стартовую сцену: нет ни отмеченной сцены,
+    ///     ни сцены без входящих ребер.
+    NotFoundStartScene(Location),
+    /// Стартовая сцена неоднозначна: подходят сцены с перечисленными идентификаторами.
+    AmbiguousStartScene(Vec<String>, Location),
+    /// Ребро начинается у главы (group node) с указанным идентификатором, а не у сцены.
+    ChoiceFromChapter(String, Location),
+    /// Ребро ведет в главу с указанным идентификатором, в которой нет ни одной сцены.
+    EmptyChapter(String, Location),
+}
+
+impl ErrorFormatGraph {
+    /// Положение элемента, на котором произошла ошибка.
+    pub fn location(&self) -> &Location {
+        match self {
+            ErrorFormatGraph::NotFoundNodeById(_, location)
+            | ErrorFormatGraph::DuplicateNodeId(location)
+            | ErrorFormatGraph::NotFoundStartScene(location)
+            | ErrorFormatGraph::AmbiguousStartScene(_, location)
+            | ErrorFormatGraph::ChoiceFromChapter(_, location)
+            | ErrorFormatGraph::EmptyChapter(_, location) => location,
+        }
+    }
 }
+
+impl fmt::Display for ErrorFormatGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorFormatGraph::NotFoundNodeById(id, _) => {
+                write!(f, "не найдена сцена с идентификатором `{}`", id)
+            }
+            ErrorFormatGraph::DuplicateNodeId(_) => {
+                write!(f, "сцена с таким идентификатором уже есть")
+            }
+            ErrorFormatGraph::NotFoundStartScene(_) => write!(
+                f,
+                "не удалось определить стартовую сцену, отметьте ее флагом `start`"
+            ),
+            ErrorFormatGraph::AmbiguousStartScene(ids, _) => write!(
+                f,
+                "стартовая сцена неоднозначна, подходят: {}",
+                ids.join(", ")
+            ),
+            ErrorFormatGraph::ChoiceFromChapter(id, _) => write!(
+                f,
+                "вариант выбора начинается у главы `{}`, а должен у сцены",
+                id
+            ),
+            ErrorFormatGraph::EmptyChapter(id, _) => {
+                write!(f, "в главе `{}` нет сцен, в нее нельзя перейти", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorFormatGraph {}
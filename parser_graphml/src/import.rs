@@ -0,0 +1,32 @@
+use std::fmt;
+
+use crate::types::Quest;
+
+/// Квест, импортированный из формата другого редактора, и предупреждения импорта.
+#[derive(Debug, Clone)]
+pub struct Imported {
+    pub quest: Quest,
+    /// Что из исходного файла не удалось перенести в граф сцен и вариантов выбора.
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Предупреждение импорта: конструкция исходного формата, у которой нет
+/// аналога в [`Vertex`](crate::types::Vertex) и [`Edge`](crate::types::Edge).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportWarning {
+    /// Где найдена конструкция: имя пассажа Twine, путь до узла Ink.
+    pub element: String,
+    /// Номер строки в исходном файле, начиная с 1, если он известен.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "предупреждение: {} (`{}`", self.message, self.element)?;
+        if let Some(line) = self.line {
+            write!(f, ", строка {}", line)?;
+        }
+        write!(f, ")")
+    }
+}
@@ -0,0 +1,609 @@
+use petgraph::graph::{Graph, NodeIndex};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::import::{ImportWarning, Imported};
+use crate::types::*;
+
+/// Версии формата скомпилированного Ink (`inkVersion`), которые читает импорт.
+pub const INK_VERSIONS: RangeInclusive<u64> = 19..=21;
+
+/// Текст варианта выбора, добавляемого там, где Ink продолжает историю сам
+/// (переход `-> knot` после текста): в квесте сцена сменяется только выбором.
+pub const INK_CONTINUE_TEXT: &str = "Далее";
+
+/// Ошибки импорта скомпилированной истории Ink.
+#[derive(Debug)]
+pub enum ErrorInk {
+    Parse(serde_json::Error),
+    /// Версия формата не входит в [`INK_VERSIONS`].
+    UnsupportedVersion(u64),
+    /// Нет обязательного поля `inkVersion` или `root`.
+    MissingField(&'static str),
+    /// Переход или вариант выбора ведет по несуществующему пути.
+    NotFoundPath(String),
+}
+
+impl fmt::Display for ErrorInk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorInk::Parse(e) => write!(f, "некорректный JSON: {}", e),
+            ErrorInk::UnsupportedVersion(version) => write!(
+                f,
+                "неподдерживаемая версия Ink {}, поддерживаются {}-{}",
+                version,
+                INK_VERSIONS.start(),
+                INK_VERSIONS.end()
+            ),
+            ErrorInk::MissingField(name) => write!(f, "нет поля `{}`", name),
+            ErrorInk::NotFoundPath(path) => write!(f, "не найден путь `{}`", path),
+        }
+    }
+}
+
+impl std::error::Error for ErrorInk {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ErrorInk::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Импортировать историю Ink, скомпилированную в JSON (`inklecate`, Inky).
+///
+/// Узлы (knot), подузлы (stitch) и сборки (gather) становятся сценами
+/// с идентификатором-путем Ink (`guard.talk`), строки текста - абзацами,
+/// теги `#` - тегами сцены, а варианты выбора `*` и `+` - ребрами.
+/// Если после выбора Ink выводит свой текст, для него создается
+/// промежуточная сцена. Переход `-> knot` после текста становится вариантом
+/// выбора [`INK_CONTINUE_TEXT`], а сцены, где история заканчивается,
+/// получают тег `ending`.
+///
+/// Переменные, условия, туннели, потоки, функции и счетчики посещений
+/// не представимы сценами и вариантами выбора: они пропускаются и попадают
+/// в [`Imported::warnings`] с путем до узла Ink.
+///
+/// # Errors
+///
+/// Некорректный JSON, неподдерживаемая версия формата или переход
+/// по несуществующему пути.
+pub fn read_ink_json(json: &str) -> Result<Imported, ErrorInk> {
+    let story: Value = serde_json::from_str(json).map_err(ErrorInk::Parse)?;
+    let version = story
+        .get("inkVersion")
+        .and_then(Value::as_u64)
+        .ok_or(ErrorInk::MissingField("inkVersion"))?;
+    if !INK_VERSIONS.contains(&version) {
+        return Err(ErrorInk::UnsupportedVersion(version));
+    }
+    let root = story
+        .get("root")
+        .filter(|x| x.is_array())
+        .ok_or(ErrorInk::MissingField("root"))?;
+
+    let mut importer = Importer {
+        root,
+        graph: Graph::new(),
+        scenes: HashMap::new(),
+        aliasing: HashSet::new(),
+        queue: Vec::new(),
+        warnings: Vec::new(),
+        reported: HashSet::new(),
+    };
+
+    const GLOBAL_DECL: &str = "global decl";
+    if child(root, GLOBAL_DECL).is_some() {
+        let location = Location {
+            container: vec![GLOBAL_DECL.to_string()],
+            index: 0,
+        };
+        importer.walk_block(&location)?;
+    }
+
+    let start = importer.scene(Location {
+        container: Vec::new(),
+        index: 0,
+    })?;
+    while let Some((ix, block)) = importer.queue.pop() {
+        importer.add_choices(ix, block)?;
+    }
+
+    Ok(Imported {
+        quest: Quest {
+            graph: importer.graph,
+            start,
+            chapters: Vec::new(),
+            locale: None,
+            variables: Vec::new(),
+            source: None,
+        },
+        warnings: importer.warnings,
+    })
+}
+
+/// Место в истории Ink: путь до контейнера и номер элемента в нем.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Location {
+    container: Vec<String>,
+    index: usize,
+}
+
+impl Location {
+    /// Путь Ink до места, идентификатор сцены.
+    fn id(&self) -> String {
+        let mut path = self.container.join(".");
+        if self.index > 0 {
+            path = format!("{}.{}", path, self.index);
+        }
+        if path.is_empty() {
+            path = "root".to_string();
+        }
+        path
+    }
+
+    /// Начинается ли с этого места сцена: узел, подузел или сборка,
+    /// а не вариант выбора (`c-0`) или служебный контейнер (`s`, `$r1`).
+    fn is_scene(&self) -> bool {
+        self.index == 0
+            && self.container.last().is_some_and(|name| {
+                name.parse::<usize>().is_err()
+                    && name != "s"
+                    && !name.starts_with('$')
+                    && !is_choice_name(name)
+            })
+    }
+}
+
+fn is_choice_name(name: &str) -> bool {
+    name.strip_prefix("c-")
+        .is_some_and(|x| x.parse::<usize>().is_ok())
+}
+
+/// Что происходит после текста и вариантов выбора.
+#[derive(Debug)]
+enum Next {
+    /// История ждет выбора игрока или заканчивается (`done`).
+    Done,
+    /// История заканчивается (`end`).
+    End,
+    /// История продолжается в другой сцене.
+    Goto(Location),
+}
+
+#[derive(Debug)]
+struct Choice {
+    text: String,
+    content: Location,
+}
+
+/// Вывод истории от начала сцены до выбора игрока.
+#[derive(Debug)]
+struct Block {
+    lines: Vec<String>,
+    line: String,
+    /// После `<>` перевод строки не начинает новую строку.
+    glue: bool,
+    tags: Vec<String>,
+    choices: Vec<Choice>,
+    next: Next,
+}
+
+impl Block {
+    fn new() -> Self {
+        Block {
+            lines: Vec::new(),
+            line: String::new(),
+            glue: false,
+            tags: Vec::new(),
+            choices: Vec::new(),
+            next: Next::Done,
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        self.line.push_str(text);
+        self.glue = false;
+    }
+
+    fn newline(&mut self) {
+        if self.glue {
+            return;
+        }
+        let line = self.line.trim();
+        if !line.is_empty() {
+            self.lines.push(line.to_string());
+        }
+        self.line.clear();
+    }
+
+    /// Сцена без своего содержимого, которая сразу переходит в другую.
+    fn is_alias(&self) -> bool {
+        self.lines.is_empty()
+            && self.tags.is_empty()
+            && self.choices.is_empty()
+            && matches!(self.next, Next::Goto(_))
+    }
+}
+
+/// Состояние вычислений Ink при обходе содержимого сцены.
+struct Walk {
+    element: String,
+    /// Режим вычисления выражений (`ev` ... `/ev`).
+    eval: bool,
+    /// Строка, собираемая между `str` и `/str`.
+    string: Option<String>,
+    /// Собранные строки - текст следующего варианта выбора.
+    strings: Vec<String>,
+    /// Тег, собираемый между `#` и `/#`.
+    tag: Option<String>,
+    /// Следующий переход запускает поток, а не продолжает историю.
+    thread: bool,
+    depth: usize,
+}
+
+enum Flow {
+    /// Контейнер закончился, обход продолжается после него.
+    Next,
+    /// Вывод сцены закончился.
+    Stop,
+}
+
+/// Глубина вложенных контейнеров и переходов внутри сцены, после которой
+/// обход останавливается: защита от циклов без выбора игрока.
+const MAX_DEPTH: usize = 64;
+
+struct Importer<'a> {
+    root: &'a Value,
+    graph: Graph<Vertex, Edge>,
+    scenes: HashMap<Location, NodeIndex>,
+    /// Сцены-переходы, которые уже разыменовываются: защита от циклов.
+    aliasing: HashSet<Location>,
+    /// Сцены, варианты выбора которых еще не добавлены.
+    queue: Vec<(NodeIndex, Block)>,
+    warnings: Vec<ImportWarning>,
+    reported: HashSet<(String, String)>,
+}
+
+impl<'a> Importer<'a> {
+    fn scene(&mut self, location: Location) -> Result<NodeIndex, ErrorInk> {
+        if let Some(ix) = self.scenes.get(&location) {
+            return Ok(*ix);
+        }
+        let block = self.walk_block(&location)?;
+        self.add_scene(location, block)
+    }
+
+    fn add_scene(&mut self, location: Location, mut block: Block) -> Result<NodeIndex, ErrorInk> {
+        if block.is_alias() && self.aliasing.insert(location.clone()) {
+            if let Next::Goto(target) = &block.next {
+                let ix = self.scene(target.clone())?;
+                self.scenes.insert(location, ix);
+                return Ok(ix);
+            }
+        }
+
+        if block.choices.is_empty()
+            && !matches!(block.next, Next::Goto(_))
+            && Ending::from_tags(&block.tags).is_none()
+        {
+            block.tags.push(ENDING_TAG.to_string());
+        }
+        let mut lines = std::mem::take(&mut block.lines).into_iter();
+        let ix = self.graph.add_node(Vertex {
+            id: location.id(),
+            text: lines.next().unwrap_or_default(),
+            paragraphs: lines.collect(),
+            ending: Ending::from_tags(&block.tags),
+            tags: std::mem::take(&mut block.tags),
+            ..Vertex::default()
+        });
+        self.scenes.insert(location, ix);
+        self.queue.push((ix, block));
+        Ok(ix)
+    }
+
+    fn add_choices(&mut self, ix: NodeIndex, block: Block) -> Result<(), ErrorInk> {
+        let mut edges = Vec::new();
+        for choice in block.choices {
+            let target = match self.scenes.get(&choice.content) {
+                Some(x) => *x,
+                None => {
+                    let mut content = self.walk_block(&choice.content)?;
+                    // `* Открыть дверь` повторяет текст варианта после выбора
+                    if content.lines.first() == Some(&choice.text) {
+                        content.lines.remove(0);
+                    }
+                    self.add_scene(choice.content, content)?
+                }
+            };
+            edges.push((target, choice.text));
+        }
+        if let Next::Goto(location) = block.next {
+            edges.push((self.scene(location)?, INK_CONTINUE_TEXT.to_string()));
+        }
+
+        for (i, (target, text)) in edges.into_iter().enumerate() {
+            let id = format!("{}#{}", self.graph[ix].id, i + 1);
+            self.graph.add_edge(
+                ix,
+                target,
+                Edge {
+                    id,
+                    text,
+                    ..Edge::default()
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn walk_block(&mut self, location: &Location) -> Result<Block, ErrorInk> {
+        let mut block = Block::new();
+        let mut walk = Walk {
+            element: location.id(),
+            eval: false,
+            string: None,
+            strings: Vec::new(),
+            tag: None,
+            thread: false,
+            depth: 0,
+        };
+        self.walk(location, &mut block, &mut walk)?;
+        block.glue = false;
+        block.newline();
+        Ok(block)
+    }
+
+    fn walk(
+        &mut self,
+        location: &Location,
+        block: &mut Block,
+        walk: &mut Walk,
+    ) -> Result<Flow, ErrorInk> {
+        let container = self
+            .find(&location.container)
+            .ok_or_else(|| ErrorInk::NotFoundPath(location.id()))?;
+        if walk.depth > MAX_DEPTH {
+            return Ok(Flow::Stop);
+        }
+        walk.depth += 1;
+
+        for (i, item) in content(container).iter().enumerate().skip(location.index) {
+            let path = || {
+                let mut path = location.container.clone();
+                path.push(i.to_string());
+                path
+            };
+            match item {
+                Value::String(command) => match command.as_str() {
+                    text if text.starts_with('^') => {
+                        let text = &text[1..];
+                        if let Some(tag) = &mut walk.tag {
+                            tag.push_str(text);
+                        } else if let Some(string) = &mut walk.string {
+                            string.push_str(text);
+                        } else if !walk.eval {
+                            block.text(text);
+                        }
+                    }
+                    "\n" if walk.string.is_none() => block.newline(),
+                    "<>" => block.glue = true,
+                    "ev" => walk.eval = true,
+                    "/ev" => walk.eval = false,
+                    "str" => walk.string = Some(String::new()),
+                    "/str" => walk.strings.extend(walk.string.take()),
+                    "#" => walk.tag = Some(String::new()),
+                    "/#" => {
+                        let tag = walk.tag.take().unwrap_or_default();
+                        block.tags.push(tag.trim().to_string());
+                    }
+                    "done" => {
+                        block.next = Next::Done;
+                        return Ok(Flow::Stop);
+                    }
+                    "end" => {
+                        block.next = Next::End;
+                        return Ok(Flow::Stop);
+                    }
+                    "->->" => {
+                        self.warn(walk, "туннели (`->->`) не поддерживаются".to_string());
+                        return Ok(Flow::Stop);
+                    }
+                    "thread" => {
+                        self.warn(walk, "потоки (`<-`) не поддерживаются".to_string());
+                        walk.thread = true;
+                    }
+                    "visit" | "seq" | "turn" | "turns" | "readc" | "rnd" | "srnd" | "choiceCnt" => {
+                        self.warn(
+                            walk,
+                            "счетчики посещений, случайные числа и последовательности \
+                             не поддерживаются"
+                                .to_string(),
+                        );
+                    }
+                    _ => (),
+                },
+                Value::Array(_) => {
+                    let inner = Location {
+                        container: path(),
+                        index: 0,
+                    };
+                    if let Flow::Stop = self.walk(&inner, block, walk)? {
+                        return Ok(Flow::Stop);
+                    }
+                }
+                Value::Object(object) => {
+                    if let Some(target) = object.get("->").and_then(Value::as_str) {
+                        if object.contains_key("var") {
+                            // `$r` - адрес возврата из начального текста варианта выбора
+                            if target.starts_with("$r") {
+                                walk.depth -= 1;
+                                return Ok(Flow::Next);
+                            }
+                            self.warn(
+                                walk,
+                                format!("переход по переменной `{}` не поддерживается", target),
+                            );
+                            return Ok(Flow::Stop);
+                        }
+                        if walk.thread {
+                            walk.thread = false;
+                            continue;
+                        }
+                        if object.contains_key("c") {
+                            self.warn(
+                                walk,
+                                "условия не поддерживаются, условный переход пропущен".to_string(),
+                            );
+                            continue;
+                        }
+                        let target = self.locate(&path(), target)?;
+                        if target.is_scene() && walk.string.is_none() {
+                            block.next = Next::Goto(target);
+                            return Ok(Flow::Stop);
+                        }
+                        if let Flow::Stop = self.walk(&target, block, walk)? {
+                            return Ok(Flow::Stop);
+                        }
+                    } else if let Some(target) = object.get("*").and_then(Value::as_str) {
+                        let flags = object.get("flg").and_then(Value::as_u64).unwrap_or(0);
+                        let text = walk.strings.drain(..).collect::<String>();
+                        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                        if flags & 0x8 != 0 {
+                            self.warn(
+                                walk,
+                                "невидимый вариант выбора по умолчанию пропущен".to_string(),
+                            );
+                            continue;
+                        }
+                        if flags & 0x1 != 0 {
+                            self.warn(
+                                walk,
+                                format!(
+                                    "условия не поддерживаются, вариант `{}` показывается всегда",
+                                    text
+                                ),
+                            );
+                        }
+                        let content = self.locate(&path(), target)?;
+                        block.choices.push(Choice { text, content });
+                    } else if let Some(target) = object.get("->t->").and_then(Value::as_str) {
+                        self.warn(walk, format!("туннель в `{}` не поддерживается", target));
+                    } else if let Some(name) = object
+                        .get("f()")
+                        .or_else(|| object.get("x()"))
+                        .and_then(Value::as_str)
+                    {
+                        self.warn(walk, format!("функция `{}` не поддерживается", name));
+                    } else if let Some(name) = object
+                        .get("VAR=")
+                        .or_else(|| object.get("VAR?"))
+                        .or_else(|| object.get("temp="))
+                        .and_then(Value::as_str)
+                    {
+                        if !name.starts_with("$r") {
+                            self.warn(walk, format!("переменная `{}` не поддерживается", name));
+                        }
+                    } else if let Some(name) = object.get("CNT?").and_then(Value::as_str) {
+                        self.warn(
+                            walk,
+                            format!("счетчик посещений `{}` не поддерживается", name),
+                        );
+                    } else if let Some(tag) = object.get("#").and_then(Value::as_str) {
+                        block.tags.push(tag.trim().to_string());
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        walk.depth -= 1;
+        Ok(Flow::Next)
+    }
+
+    /// Место, куда ведет путь Ink из элемента `from`.
+    fn locate(&self, from: &[String], target: &str) -> Result<Location, ErrorInk> {
+        let path = match target.strip_prefix('.') {
+            // Относительный путь: первый `^` - контейнер элемента, следующие - его родители
+            Some(relative) => {
+                let mut path = from.to_vec();
+                for component in relative.split('.') {
+                    if component == "^" {
+                        path.pop();
+                    } else {
+                        path.push(component.to_string());
+                    }
+                }
+                path
+            }
+            None => target.split('.').map(String::from).collect(),
+        };
+
+        let not_found = || ErrorInk::NotFoundPath(target.to_string());
+        match self.find(&path).ok_or_else(not_found)? {
+            Value::Array(_) => Ok(Location {
+                container: path,
+                index: 0,
+            }),
+            _ => {
+                let (last, container) = path.split_last().ok_or_else(not_found)?;
+                Ok(Location {
+                    container: container.to_vec(),
+                    index: last.parse().map_err(|_| not_found())?,
+                })
+            }
+        }
+    }
+
+    fn find(&self, path: &[String]) -> Option<&'a Value> {
+        path.iter().try_fold(self.root, |container, component| {
+            child(container, component)
+        })
+    }
+
+    fn warn(&mut self, walk: &Walk, message: String) {
+        if self
+            .reported
+            .insert((walk.element.clone(), message.clone()))
+        {
+            self.warnings.push(ImportWarning {
+                element: walk.element.clone(),
+                line: None,
+                message,
+            });
+        }
+    }
+}
+
+/// Содержимое контейнера Ink без словаря именованных контейнеров в конце.
+fn content(container: &Value) -> &[Value] {
+    match container.as_array().map(Vec::as_slice) {
+        Some([content @ .., last]) if last.is_object() || last.is_null() => content,
+        Some(content) => content,
+        None => &[],
+    }
+}
+
+/// Элемент контейнера Ink по номеру или имени.
+fn child<'a>(container: &'a Value, component: &str) -> Option<&'a Value> {
+    if let Ok(i) = component.parse::<usize>() {
+        return content(container).get(i);
+    }
+    container
+        .as_array()
+        .and_then(|x| x.last())
+        .and_then(|x| x.get(component))
+        .filter(|x| x.is_array())
+        .or_else(|| {
+            content(container).iter().find(|x| {
+                x.as_array()
+                    .and_then(|x| x.last())
+                    .and_then(|x| x.get("#n"))
+                    .and_then(Value::as_str)
+                    == Some(component)
+            })
+        })
+}
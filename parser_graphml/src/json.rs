@@ -0,0 +1,532 @@
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::condition::{parse_condition, Condition};
+use crate::script::{self, parse_effects, ErrorScript, VarType, Variable};
+use crate::types::*;
+
+/// Записать поле, если значение есть.
+macro_rules! insert_some {
+    ($map:expr, $name:expr, $value:expr) => {
+        if let Some(value) = $value {
+            $map.insert($name.to_string(), json!(value));
+        }
+    };
+}
+
+/// Версия формата JSON квеста, которую пишет [`write_quest_json`].
+pub const QUEST_JSON_VERSION: u64 = 1;
+
+/// Ошибки чтения квеста из JSON формата.
+///
+/// `path` - путь до значения в документе, например `scenes[2].text`.
+#[derive(Debug)]
+pub enum ErrorJson {
+    /// Некорректный JSON.
+    Parse(serde_json::Error),
+    /// Версия формата не поддерживается.
+    UnsupportedVersion(u64),
+    /// Нет обязательного поля.
+    MissingField(String),
+    /// Значение поля другого типа: путь и ожидаемый тип.
+    InvalidField(String, &'static str),
+    /// Сцена или глава с таким идентификатором уже есть.
+    DuplicateId(String, String),
+    /// Не найдена сцена с указанным идентификатором.
+    NotFoundScene(String, String),
+    /// Не найдена глава с указанным идентификатором.
+    NotFoundChapter(String, String),
+    /// Глава через цепочку `parent` вложена сама в себя.
+    ChapterCycle(String, String),
+    /// Ошибка в объявлении переменной или в изменениях переменных: путь и ошибка.
+    InvalidScript(String, ErrorScript),
+}
+
+impl fmt::Display for ErrorJson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorJson::Parse(e) => write!(f, "некорректный JSON: {}", e),
+            ErrorJson::UnsupportedVersion(version) => write!(
+                f,
+                "версия формата {} не поддерживается, поддерживается {}",
+                version, QUEST_JSON_VERSION
+            ),
+            ErrorJson::MissingField(path) => write!(f, "нет обязательного поля `{}`", path),
+            ErrorJson::InvalidField(path, expected) => {
+                write!(f, "в поле `{}` ожидается {}", path, expected)
+            }
+            ErrorJson::DuplicateId(path, id) => {
+                write!(f, "идентификатор `{}` уже есть (`{}`)", id, path)
+            }
+            ErrorJson::NotFoundScene(path, id) => {
+                write!(f, "не найдена сцена `{}` (`{}`)", id, path)
+            }
+            ErrorJson::NotFoundChapter(path, id) => {
+                write!(f, "не найдена глава `{}` (`{}`)", id, path)
+            }
+            ErrorJson::ChapterCycle(path, id) => {
+                write!(f, "глава `{}` вложена сама в себя (`{}`)", id, path)
+            }
+            ErrorJson::InvalidScript(path, e) => {
+                write!(f, "ошибка в скрипте `{}`: {}", path, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorJson {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ErrorJson::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Прочитать квест из JSON формата.
+///
+/// Формат описан схемой `schema/quest.schema.json`: версия формата, язык
+/// основного текста, переменные, стартовая сцена, главы, сцены и варианты выбора.
+/// Сцены и варианты выбора попадают в граф в порядке документа, поэтому
+/// [`write_quest_json`] записывает прочитанный квест обратно без изменений.
+///
+/// # Errors
+///
+/// Некорректный JSON, неизвестная версия формата, поля не того типа,
+/// повторяющиеся идентификаторы, ссылки на несуществующие сцены и главы,
+/// главы, вложенные сами в себя,
+/// ошибки в переменных и их изменениях.
+pub fn read_quest_json(json: &str) -> Result<Quest, ErrorJson> {
+    let document: Value = serde_json::from_str(json).map_err(ErrorJson::Parse)?;
+    let root = Fields::of(&document, String::new())?;
+
+    let version = root.optional("version", Value::as_u64, "целое число")?;
+    match version {
+        Some(QUEST_JSON_VERSION) | None => (),
+        Some(version) => return Err(ErrorJson::UnsupportedVersion(version)),
+    }
+
+    let mut variables: Vec<Variable> = Vec::new();
+    for x in root.array("variables")? {
+        let variable = x.variable()?;
+        if variables.iter().any(|v| v.name == variable.name) {
+            return Err(ErrorJson::InvalidScript(
+                x.path("name"),
+                ErrorScript::DuplicateVariable(variable.name),
+            ));
+        }
+        variables.push(variable);
+    }
+
+    let chapter_values = root.array("chapters")?;
+    let mut chapter_indexes = HashMap::new();
+    for (i, x) in chapter_values.iter().enumerate() {
+        let id = x.string("id")?;
+        if chapter_indexes.insert(id.clone(), i).is_some() {
+            return Err(ErrorJson::DuplicateId(x.path("id"), id));
+        }
+    }
+    let find_chapter = |fields: &Fields<'_>, name: &str| -> Result<Option<usize>, ErrorJson> {
+        match fields.optional_string(name)? {
+            Some(id) => match chapter_indexes.get(&id) {
+                Some(x) => Ok(Some(*x)),
+                None => Err(ErrorJson::NotFoundChapter(fields.path(name), id)),
+            },
+            None => Ok(None),
+        }
+    };
+
+    let mut graph = Graph::new();
+    let mut scene_indexes = HashMap::new();
+    for x in root.array("scenes")? {
+        let tags = x.strings("tags")?;
+        let vertex = Vertex {
+            id: x.string("id")?,
+            text: x.string("text")?,
+            paragraphs: x.strings("paragraphs")?,
+            description: x.optional_string("description")?,
+            url: x.optional_string("url")?,
+            ending: match x.ending("ending")? {
+                Some(ending) => Some(ending),
+                None => Ending::from_tags(&tags),
+            },
+            tags,
+            chapter: find_chapter(&x, "chapter")?,
+            position: x.rect("position")?,
+            translations: x.translations(|x| x.strings_value())?,
+        };
+        if scene_indexes.contains_key(&vertex.id) {
+            return Err(ErrorJson::DuplicateId(x.path("id"), vertex.id));
+        }
+        let id = vertex.id.clone();
+        scene_indexes.insert(id, graph.add_node(vertex));
+    }
+    let find_scene = |fields: &Fields<'_>, name: &str| -> Result<NodeIndex, ErrorJson> {
+        let id = fields.string(name)?;
+        scene_indexes
+            .get(&id)
+            .copied()
+            .ok_or_else(|| ErrorJson::NotFoundScene(fields.path(name), id))
+    };
+
+    for x in root.array("choices")? {
+        let edge = Edge {
+            id: x.string("id")?,
+            text: x.optional_string("text")?.unwrap_or_default(),
+            description: x.optional_string("description")?,
+            url: x.optional_string("url")?,
+            tags: x.strings("tags")?,
+            effects: parse_effects(&x.strings("effects")?.join("\n"), &variables)
+                .map_err(|e| ErrorJson::InvalidScript(x.path("effects"), e))?,
+            condition: match x.optional_string("condition")? {
+                Some(text) => Some(Condition {
+                    expr: parse_condition(&text, &variables)
+                        .map_err(|e| ErrorJson::InvalidScript(x.path("condition"), e))?,
+                    locked: x.optional_string("locked")?,
+                }),
+                None => None,
+            },
+            translations: x.translations(|x| x.string_value())?,
+        };
+        graph.add_edge(find_scene(&x, "from")?, find_scene(&x, "to")?, edge);
+    }
+
+    let mut chapters = Vec::new();
+    for x in &chapter_values {
+        chapters.push(Chapter {
+            id: x.string("id")?,
+            title: x.optional_string("title")?.unwrap_or_default(),
+            parent: find_chapter(x, "parent")?,
+            entry: match x.optional_string("entry")? {
+                Some(_) => Some(find_scene(x, "entry")?),
+                None => None,
+            },
+            position: x.rect("position")?,
+        });
+    }
+    for (i, x) in chapter_values.iter().enumerate() {
+        let mut visited = HashSet::new();
+        let mut current = Some(i);
+        while let Some(chapter) = current {
+            if !visited.insert(chapter) {
+                return Err(ErrorJson::ChapterCycle(
+                    x.path("parent"),
+                    chapters[i].id.clone(),
+                ));
+            }
+            current = chapters[chapter].parent;
+        }
+    }
+
+    Ok(Quest {
+        start: find_scene(&root, "start")?,
+        graph,
+        chapters,
+        locale: root.optional_string("locale")?,
+        variables,
+        source: None,
+    })
+}
+
+/// Сериализовать квест в JSON формат.
+///
+/// Пустые необязательные поля не пишутся. Исходный GraphML документ
+/// ([`Quest::source`]) в JSON не попадает.
+pub fn write_quest_json(quest: &Quest) -> String {
+    let graph = &quest.graph;
+    let chapter_id = |x: Option<usize>| x.and_then(|x| quest.chapters.get(x)).map(|x| &x.id);
+
+    let mut root = Map::new();
+    root.insert("version".to_string(), json!(QUEST_JSON_VERSION));
+    insert_some!(root, "locale", quest.locale.as_ref());
+    root.insert("start".to_string(), json!(graph[quest.start].id));
+
+    if !quest.variables.is_empty() {
+        let variables = quest.variables.iter().map(variable_json).collect();
+        root.insert("variables".to_string(), Value::Array(variables));
+    }
+
+    if !quest.chapters.is_empty() {
+        let chapters = quest
+            .chapters
+            .iter()
+            .map(|x| {
+                let mut chapter = Map::new();
+                chapter.insert("id".to_string(), json!(x.id));
+                insert_some!(chapter, "title", Some(&x.title).filter(|x| !x.is_empty()));
+                insert_some!(chapter, "parent", chapter_id(x.parent));
+                insert_some!(chapter, "entry", x.entry.map(|x| &graph[x].id));
+                insert_some!(chapter, "position", x.position.as_ref().map(rect_json));
+                Value::Object(chapter)
+            })
+            .collect();
+        root.insert("chapters".to_string(), Value::Array(chapters));
+    }
+
+    let scenes = graph
+        .node_indices()
+        .map(|ix| {
+            let x = &graph[ix];
+            let mut scene = Map::new();
+            scene.insert("id".to_string(), json!(x.id));
+            scene.insert("text".to_string(), json!(x.text));
+            insert_some!(scene, "paragraphs", non_empty(&x.paragraphs));
+            insert_some!(scene, "description", x.description.as_ref());
+            insert_some!(scene, "url", x.url.as_ref());
+            insert_some!(scene, "tags", non_empty(&x.tags));
+            insert_some!(scene, "ending", x.ending_to_write().map(ending_json));
+            insert_some!(scene, "chapter", chapter_id(x.chapter));
+            insert_some!(scene, "position", x.position.as_ref().map(rect_json));
+            insert_some!(scene, "translations", non_empty_map(&x.translations));
+            Value::Object(scene)
+        })
+        .collect();
+    root.insert("scenes".to_string(), Value::Array(scenes));
+
+    let choices = graph
+        .edge_references()
+        .map(|edge| {
+            let x = edge.weight();
+            let mut choice = Map::new();
+            choice.insert("id".to_string(), json!(x.id));
+            choice.insert("from".to_string(), json!(graph[edge.source()].id));
+            choice.insert("to".to_string(), json!(graph[edge.target()].id));
+            insert_some!(choice, "text", Some(&x.text).filter(|x| !x.is_empty()));
+            insert_some!(choice, "description", x.description.as_ref());
+            insert_some!(choice, "url", x.url.as_ref());
+            insert_some!(choice, "tags", non_empty(&x.tags));
+            if !x.effects.is_empty() {
+                let effects = x.effects.iter().map(|x| json!(x.to_string())).collect();
+                choice.insert("effects".to_string(), Value::Array(effects));
+            }
+            if let Some(condition) = &x.condition {
+                choice.insert("condition".to_string(), json!(condition.expr.to_string()));
+                insert_some!(choice, "locked", condition.locked.as_ref());
+            }
+            insert_some!(choice, "translations", non_empty_map(&x.translations));
+            Value::Object(choice)
+        })
+        .collect();
+    root.insert("choices".to_string(), Value::Array(choices));
+
+    let mut out = serde_json::to_string_pretty(&Value::Object(root)).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+fn non_empty<T>(values: &[T]) -> Option<&[T]> {
+    Some(values).filter(|x| !x.is_empty())
+}
+
+fn non_empty_map<T>(values: &BTreeMap<String, T>) -> Option<&BTreeMap<String, T>> {
+    Some(values).filter(|x| !x.is_empty())
+}
+
+fn rect_json(rect: &Rect) -> Value {
+    json!({
+        "x": rect.x,
+        "y": rect.y,
+        "width": rect.width,
+        "height": rect.height,
+    })
+}
+
+fn ending_json(ending: &Ending) -> Value {
+    let mut value = Map::new();
+    value.insert("type".to_string(), json!(ending.kind.name()));
+    insert_some!(value, "title", ending.title.as_ref());
+    Value::Object(value)
+}
+
+fn variable_json(variable: &Variable) -> Value {
+    let value = match &variable.initial {
+        script::Value::Int(x) => json!(x),
+        script::Value::Bool(x) => json!(x),
+        script::Value::String(x) => json!(x),
+    };
+    json!({
+        "name": variable.name,
+        "type": variable.var_type.name(),
+        "value": value,
+    })
+}
+
+/// Поля объекта JSON вместе с путем до него для сообщений об ошибках.
+struct Fields<'a> {
+    object: &'a Map<String, Value>,
+    path: String,
+}
+
+impl<'a> Fields<'a> {
+    fn of(value: &'a Value, path: String) -> Result<Fields<'a>, ErrorJson> {
+        match value.as_object() {
+            Some(object) => Ok(Fields { object, path }),
+            None => Err(ErrorJson::InvalidField(
+                if path.is_empty() {
+                    "$".to_string()
+                } else {
+                    path
+                },
+                "объект",
+            )),
+        }
+    }
+
+    fn path(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", self.path, name)
+        }
+    }
+
+    fn optional<T>(
+        &self,
+        name: &str,
+        convert: impl Fn(&'a Value) -> Option<T>,
+        expected: &'static str,
+    ) -> Result<Option<T>, ErrorJson> {
+        match self.object.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => convert(value)
+                .map(Some)
+                .ok_or_else(|| ErrorJson::InvalidField(self.path(name), expected)),
+        }
+    }
+
+    fn optional_string(&self, name: &str) -> Result<Option<String>, ErrorJson> {
+        self.optional(name, |x| x.as_str().map(String::from), "строка")
+    }
+
+    fn string(&self, name: &str) -> Result<String, ErrorJson> {
+        self.optional_string(name)?
+            .ok_or_else(|| ErrorJson::MissingField(self.path(name)))
+    }
+
+    fn strings(&self, name: &str) -> Result<Vec<String>, ErrorJson> {
+        Ok(self
+            .optional(name, |x| JsonValue(x).strings_value(), "массив строк")?
+            .unwrap_or_default())
+    }
+
+    /// Массив объектов, отсутствующий массив - пустой.
+    fn array(&self, name: &str) -> Result<Vec<Fields<'a>>, ErrorJson> {
+        let values = self
+            .optional(name, Value::as_array, "массив")?
+            .map_or(&[][..], Vec::as_slice);
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, x)| Fields::of(x, format!("{}[{}]", self.path(name), i)))
+            .collect()
+    }
+
+    fn rect(&self, name: &str) -> Result<Option<Rect>, ErrorJson> {
+        let value = match self.object.get(name) {
+            None | Some(Value::Null) => return Ok(None),
+            Some(x) => Fields::of(x, self.path(name))?,
+        };
+        let number = |name: &str| {
+            value
+                .optional(name, Value::as_f64, "число")
+                .map(Option::unwrap_or_default)
+        };
+        Ok(Some(Rect {
+            x: number("x")?,
+            y: number("y")?,
+            width: number("width")?,
+            height: number("height")?,
+        }))
+    }
+
+    /// Концовка: объект с типом `type` и необязательным названием `title`.
+    fn ending(&self, name: &str) -> Result<Option<Ending>, ErrorJson> {
+        let value = match self.object.get(name) {
+            None | Some(Value::Null) => return Ok(None),
+            Some(x) => Fields::of(x, self.path(name))?,
+        };
+        let kind = match value.optional_string("type")? {
+            Some(kind) => EndingKind::parse(&kind).ok_or_else(|| {
+                ErrorJson::InvalidField(value.path("type"), "good, bad, neutral или secret")
+            })?,
+            None => EndingKind::default(),
+        };
+        Ok(Some(Ending {
+            kind,
+            title: value.optional_string("title")?,
+        }))
+    }
+
+    /// Объявление переменной: имя `name`, тип `type` и необязательное
+    /// начальное значение `value` того же типа.
+    fn variable(&self) -> Result<Variable, ErrorJson> {
+        let name = self.string("name")?;
+        let type_name = self.string("type")?;
+        let var_type = VarType::parse(&type_name)
+            .ok_or_else(|| ErrorJson::InvalidField(self.path("type"), "int, bool или string"))?;
+        let initial = match var_type {
+            VarType::Int => self
+                .optional("value", Value::as_i64, "целое число")?
+                .map(script::Value::Int),
+            VarType::Bool => self
+                .optional("value", Value::as_bool, "логическое значение")?
+                .map(script::Value::Bool),
+            VarType::String => self.optional_string("value")?.map(script::Value::String),
+        };
+        Ok(Variable {
+            name,
+            var_type,
+            initial: initial.unwrap_or_else(|| var_type.default_value()),
+        })
+    }
+
+    /// Переводы по языкам: объект, значения которого читает `convert`.
+    fn translations<T>(
+        &self,
+        convert: impl Fn(JsonValue<'_>) -> Option<T>,
+    ) -> Result<BTreeMap<String, T>, ErrorJson> {
+        const NAME: &str = "translations";
+        let value = match self.object.get(NAME) {
+            None | Some(Value::Null) => return Ok(BTreeMap::new()),
+            Some(x) => Fields::of(x, self.path(NAME))?,
+        };
+        value
+            .object
+            .iter()
+            .map(|(locale, x)| match convert(JsonValue(x)) {
+                Some(x) => Ok((locale.clone(), x)),
+                None => Err(ErrorJson::InvalidField(
+                    value.path(locale),
+                    "строка или массив строк",
+                )),
+            })
+            .collect()
+    }
+}
+
+/// Значение JSON перевода: строка или массив строк.
+struct JsonValue<'a>(&'a Value);
+
+impl JsonValue<'_> {
+    fn string_value(&self) -> Option<String> {
+        match self.0 {
+            Value::String(x) => Some(x.clone()),
+            Value::Array(_) => self.strings_value().map(|x| x.join("\n")),
+            _ => None,
+        }
+    }
+
+    fn strings_value(&self) -> Option<Vec<String>> {
+        match self.0 {
+            Value::String(x) => Some(vec![x.clone()]),
+            Value::Array(values) => values
+                .iter()
+                .map(|x| x.as_str().map(String::from))
+                .collect(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use roxmltree::{Document, Node};
+use serde_json::Value;
+
+/// Область применения ключа GraphML (атрибут `for` у `<key>`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyDomain {
+    Graph,
+    Node,
+    Edge,
+    Port,
+    All,
+    /// Неизвестное значение атрибута `for`.
+    Other,
+}
+
+impl KeyDomain {
+    fn parse(value: Option<&str>) -> KeyDomain {
+        match value.map(str::trim) {
+            Some("graph") => KeyDomain::Graph,
+            Some("node") => KeyDomain::Node,
+            Some("edge") => KeyDomain::Edge,
+            Some("port") => KeyDomain::Port,
+            Some("all") | None => KeyDomain::All,
+            Some(_) => KeyDomain::Other,
+        }
+    }
+
+    /// Применим ли ключ с этой областью к элементам из области `domain`.
+    pub fn applies_to(self, domain: KeyDomain) -> bool {
+        self == domain || self == KeyDomain::All
+    }
+}
+
+/// Объявление ключа GraphML (`<key>`).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphMLKey {
+    /// Идентификатор ключа, на который ссылаются `<data key="...">`.
+    pub id: String,
+    /// Область применения ключа.
+    pub domain: KeyDomain,
+    /// Семантическое имя ключа (`attr.name`).
+    pub name: Option<String>,
+    /// URI ключа yFiles (`y:attr.uri`).
+    pub uri: Option<String>,
+    /// Тип значения (`attr.type`).
+    pub attr_type: Option<String>,
+    /// Тип данных yEd desktop (`yfiles.type`).
+    pub yfiles_type: Option<String>,
+    /// Текстовое значение по умолчанию (`<default>`).
+    pub default: Option<String>,
+}
+
+impl GraphMLKey {
+    fn from_xml_node(node: &Node<'_, '_>) -> Option<GraphMLKey> {
+        const ATTR_ID: &str = "id";
+        const ATTR_FOR: &str = "for";
+        const ATTR_NAME: &str = "attr.name";
+        const ATTR_URI: &str = "attr.uri";
+        const ATTR_TYPE: &str = "attr.type";
+        const ATTR_YFILES_TYPE: &str = "yfiles.type";
+        const TAG_DEFAULT: &str = "default";
+
+        let find_attr = |name: &str| {
+            node.attributes()
+                .iter()
+                .find(|a| a.name() == name)
+                .map(|a| a.value().to_string())
+        };
+
+        let default = node
+            .children()
+            .find(|x| x.tag_name().name() == TAG_DEFAULT)
+            .and_then(|x| x.text())
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty());
+
+        Some(GraphMLKey {
+            id: find_attr(ATTR_ID)?,
+            domain: KeyDomain::parse(node.attribute(ATTR_FOR)),
+            name: find_attr(ATTR_NAME),
+            uri: find_attr(ATTR_URI),
+            attr_type: find_attr(ATTR_TYPE),
+            yfiles_type: find_attr(ATTR_YFILES_TYPE),
+            default,
+        })
+    }
+
+    /// Соответствует ли ключ семантическому имени.
+    ///
+    /// Имя сравнивается с `attr.name`, а также с последним сегментом `y:attr.uri`.
+    pub fn has_name(&self, name: &str) -> bool {
+        let by_name = self.name.as_deref() == Some(name);
+        let by_uri = self.uri.as_ref().and_then(|x| x.rsplit('/').next()) == Some(name);
+
+        by_name || by_uri
+    }
+}
+
+/// Таблица ключей GraphML документа.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyTable {
+    keys: Vec<GraphMLKey>,
+}
+
+impl KeyTable {
+    /// Собрать таблицу ключей из объявлений `<key>` документа.
+    pub fn from_document(doc: &Document<'_>) -> KeyTable {
+        const TAG_KEY: &str = "key";
+
+        let keys = doc
+            .root_element()
+            .children()
+            .filter(|x| x.is_element() && x.tag_name().name() == TAG_KEY)
+            .filter_map(|x| GraphMLKey::from_xml_node(&x))
+            .collect();
+
+        KeyTable { keys }
+    }
+
+    /// Все объявленные ключи в порядке объявления.
+    pub fn keys(&self) -> &[GraphMLKey] {
+        &self.keys
+    }
+
+    /// Найти ключ по идентификатору.
+    pub fn get(&self, id: &str) -> Option<&GraphMLKey> {
+        self.keys.iter().find(|x| x.id == id)
+    }
+
+    /// Найти ключ по семантическому имени для элементов из области `domain`.
+    pub fn find_by_name(&self, domain: KeyDomain, name: &str) -> Option<&GraphMLKey> {
+        self.keys
+            .iter()
+            .find(|x| x.domain.applies_to(domain) && x.has_name(name))
+    }
+
+    /// Найти ключ yEd desktop по значению `yfiles.type` для элементов из области `domain`.
+    pub fn find_by_yfiles_type(&self, domain: KeyDomain, yfiles_type: &str) -> Option<&GraphMLKey> {
+        self.keys
+            .iter()
+            .find(|x| x.domain.applies_to(domain) && x.yfiles_type.as_deref() == Some(yfiles_type))
+    }
+}
+
+/// Найти дочерний элемент `<data>` с указанным ключом.
+pub(crate) fn find_data_by_key<'a>(node: &Node<'a, 'a>, key: &str) -> Option<Node<'a, 'a>> {
+    const TAG_DATA: &str = "data";
+    const ATTR_TAG_KEY: &str = "key";
+
+    node.children()
+        .find(|x| x.tag_name().name() == TAG_DATA && x.attribute(ATTR_TAG_KEY) == Some(key))
+}
+
+/// Найти текстовое значение `<data>` по ключу, а если его нет, то значение ключа по умолчанию.
+pub(crate) fn find_data_value(node: &Node<'_, '_>, key: &GraphMLKey) -> Option<String> {
+    match find_data_by_key(node, &key.id) {
+        Some(data) => Some(data.text().unwrap_or("").trim().to_string()),
+        None => key.default.clone(),
+    }
+}
+
+/// Найти теги в `<data>` по ключу.
+///
+/// Теги пишутся через запятую или пробел прямо в `<data>`, строками `x:String`
+/// или строками JSON в `y:Json` (`"ending"`, `["ending", "chapter-2"]`).
+/// Объекты JSON (yEd Live хранит в них настройки графа) тегами не считаются.
+pub(crate) fn find_data_tags(node: &Node<'_, '_>, key: &GraphMLKey) -> Vec<String> {
+    const TAG_JSON: &str = "Json";
+
+    let data = match find_data_by_key(node, &key.id) {
+        Some(data) => data,
+        None => return key.default.as_deref().map(split_tags).unwrap_or_default(),
+    };
+
+    let elements: Vec<_> = data
+        .descendants()
+        .filter(|x| x.is_element())
+        .skip(1)
+        .collect();
+    if elements.is_empty() {
+        return split_tags(data.text().unwrap_or(""));
+    }
+
+    elements
+        .iter()
+        .filter(|x| !x.children().any(|x| x.is_element()))
+        .filter_map(|x| {
+            let text = x.text()?.trim();
+            Some(if x.tag_name().name() == TAG_JSON {
+                if text.starts_with('{') {
+                    Vec::new()
+                } else {
+                    json_strings(text)
+                }
+            } else {
+                split_tags(text)
+            })
+        })
+        .flatten()
+        .collect()
+}
+
+fn split_tags(text: &str) -> Vec<String> {
+    text.split(|x: char| x == ',' || x.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Строковые значения JSON: строка или строки массива, в том числе вложенные.
+fn json_strings(json: &str) -> Vec<String> {
+    fn collect(value: &Value, strings: &mut Vec<String>) {
+        match value {
+            Value::String(x) if !x.is_empty() => strings.push(x.clone()),
+            Value::Array(values) => values.iter().for_each(|x| collect(x, strings)),
+            Value::Object(fields) => fields.values().for_each(|x| collect(x, strings)),
+            _ => (),
+        }
+    }
+
+    let mut strings = Vec::new();
+    if let Ok(value) = serde_json::from_str(json) {
+        collect(&value, &mut strings);
+    }
+    strings
+}
+
+/// Найти переводы в `<data>` по ключу: объект JSON, в котором у каждого языка
+/// строка или массив строк (`{"en": "Hello"}`, `{"en": ["Hello", "World"]}`).
+///
+/// Объект пишется прямо в `<data>` или в `y:Json`. Значения других типов
+/// пропускаются, а если это не объект JSON, то переводов нет.
+pub(crate) fn find_data_translations(
+    node: &Node<'_, '_>,
+    key: &GraphMLKey,
+) -> Vec<(String, Vec<String>)> {
+    let text = match find_data_by_key(node, &key.id) {
+        Some(data) => data
+            .descendants()
+            .filter(|x| x.is_text())
+            .filter_map(|x| x.text())
+            .collect::<String>(),
+        None => key.default.clone().unwrap_or_default(),
+    };
+
+    json_object_strings(text.trim()).unwrap_or_default()
+}
+
+fn json_object_strings(json: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let fields = match serde_json::from_str(json).ok()? {
+        Value::Object(fields) => fields,
+        _ => return None,
+    };
+
+    let strings = |value: Value| match value {
+        Value::String(x) => Some(vec![x]),
+        Value::Array(values) => Some(
+            values
+                .into_iter()
+                .filter_map(|x| match x {
+                    Value::String(x) => Some(x),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+    Some(
+        fields
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, strings(value)?)))
+            .collect(),
+    )
+}
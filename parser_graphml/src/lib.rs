@@ -1,20 +1,86 @@
 //! Модуль по парсингу графа из GraphML формата.
 
+#[path = "condition.rs"]
+pub mod condition;
+
+#[path = "dot.rs"]
+pub mod dot;
+
 #[path = "errors.rs"]
 pub mod errors;
 
+#[path = "dialect.rs"]
+pub mod dialect;
+
+#[path = "import.rs"]
+pub mod import;
+
+#[path = "ink.rs"]
+pub mod ink;
+
+#[path = "json.rs"]
+pub mod json;
+
+#[path = "keys.rs"]
+pub mod keys;
+
+#[path = "loader.rs"]
+pub mod loader;
+
+#[path = "report.rs"]
+pub mod report;
+
+#[path = "rich_text.rs"]
+pub mod rich_text;
+
+#[path = "script.rs"]
+pub mod script;
+
+#[path = "source.rs"]
+pub mod source;
+
 #[path = "types.rs"]
 pub mod types;
 
 #[path = "parser_impl.rs"]
 pub mod parser_impl;
 
+#[path = "twee.rs"]
+pub mod twee;
+
+#[path = "validate.rs"]
+pub mod validate;
+
+#[path = "writer.rs"]
+pub mod writer;
+
 #[macro_use]
 extern crate mdo;
 
-/// Парсер графа из GraphML формата.
+/// Парсер графа из GraphML формата и его сериализация обратно в GraphML.
 pub mod parser {
+    pub use crate::condition::{parse_condition, BinaryOp, Condition, Expr, UnaryOp};
+    pub use crate::dialect::Dialect;
+    pub use crate::dot::{read_dot, write_dot, ErrorDot};
     pub use crate::errors::*;
-    pub use crate::parser_impl::read_graphml;
+    pub use crate::import::{ImportWarning, Imported};
+    pub use crate::ink::{read_ink_json, ErrorInk, INK_CONTINUE_TEXT, INK_VERSIONS};
+    pub use crate::json::{read_quest_json, write_quest_json, ErrorJson, QUEST_JSON_VERSION};
+    pub use crate::keys::*;
+    pub use crate::loader::{
+        decode_text, DotLoader, ErrorGraphMLReport, ErrorLoad, GraphMLLoader, InkLoader,
+        QuestJsonLoader, QuestLoader, QuestLoaders, TweeLoader, TwineHtmlLoader,
+    };
+    pub use crate::parser_impl::{read_graphml, read_graphml_diagnostics};
+    pub use crate::report::{render_error, render_errors};
+    pub use crate::rich_text::{RichText, Span, Style};
+    pub use crate::script::{
+        parse_effects, parse_variables, split_description_effects, Effect, EffectOp, ErrorScript,
+        Value, VarType, Variable,
+    };
+    pub use crate::source::GraphMLSource;
+    pub use crate::twee::{read_twee, read_twine_html, ErrorTwee};
     pub use crate::types::*;
+    pub use crate::validate::{validate, Finding, Problem, Severity};
+    pub use crate::writer::write_graphml;
 }
@@ -0,0 +1,418 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::dot::read_dot;
+use crate::errors::Error;
+use crate::import::Imported;
+use crate::ink::read_ink_json;
+use crate::json::read_quest_json;
+use crate::parser_impl::read_graphml_diagnostics;
+use crate::report::render_errors;
+use crate::twee::{read_twee, read_twine_html};
+use crate::types::Quest;
+
+/// Загрузчик квеста из файла одного формата.
+///
+/// Реализации регистрируются в [`QuestLoaders`], который выбирает загрузчик
+/// по расширению файла или по содержимому. Так сторонний код добавляет
+/// свои форматы, не меняя этот крейт.
+pub trait QuestLoader {
+    /// Название формата для сообщений, например `GraphML`.
+    fn name(&self) -> &'static str;
+
+    /// Расширения файлов формата в нижнем регистре и без точки.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Похоже ли содержимое файла на этот формат: сигнатура, корневой элемент.
+    fn sniff(&self, bytes: &[u8]) -> bool;
+
+    /// Прочитать квест из содержимого файла.
+    ///
+    /// # Errors
+    ///
+    /// Файл не удалось прочитать в этом формате.
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad>;
+}
+
+/// Ошибки загрузки квеста из файла.
+#[derive(Debug)]
+pub enum ErrorLoad {
+    /// Текстовый файл не в кодировке UTF-8.
+    Encoding(std::str::Utf8Error),
+    /// Ни один загрузчик не узнал файл с таким именем и содержимым.
+    UnknownFormat(String),
+    /// Расширение подходит нескольким загрузчикам, но содержимое не узнал
+    /// ни один из них: имя файла и названия форматов.
+    AmbiguousFormat(String, Vec<&'static str>),
+    /// Файл не удалось прочитать в выбранном формате: название формата и ошибка.
+    Format(&'static str, Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for ErrorLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorLoad::Encoding(e) => write!(f, "файл не в кодировке UTF-8: {}", e),
+            ErrorLoad::UnknownFormat(name) => {
+                write!(f, "не удалось определить формат файла `{}`", name)
+            }
+            ErrorLoad::AmbiguousFormat(name, formats) => write!(
+                f,
+                "не удалось выбрать формат файла `{}` из: {}",
+                name,
+                formats.join(", ")
+            ),
+            ErrorLoad::Format(format, e) => write!(f, "ошибка чтения {}: {}", format, e),
+        }
+    }
+}
+
+impl std::error::Error for ErrorLoad {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ErrorLoad::Encoding(e) => Some(e),
+            ErrorLoad::Format(_, e) => Some(e.as_ref()),
+            ErrorLoad::UnknownFormat(_) | ErrorLoad::AmbiguousFormat(..) => None,
+        }
+    }
+}
+
+/// Все ошибки GraphML документа и отчет о них с фрагментами исходного текста.
+#[derive(Debug)]
+pub struct ErrorGraphMLReport {
+    pub errors: Vec<Error>,
+    /// Отчет [`render_errors`].
+    pub report: String,
+}
+
+impl fmt::Display for ErrorGraphMLReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\n{}", self.report)
+    }
+}
+
+impl std::error::Error for ErrorGraphMLReport {}
+
+/// Текст файла в UTF-8 без метки порядка байтов.
+///
+/// # Errors
+///
+/// Файл не в кодировке UTF-8.
+pub fn decode_text(bytes: &[u8]) -> Result<&str, ErrorLoad> {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    std::str::from_utf8(bytes).map_err(ErrorLoad::Encoding)
+}
+
+/// Начало файла для проверки содержимого.
+fn head(bytes: &[u8]) -> Cow<'_, str> {
+    const HEAD_LEN: usize = 1024;
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(HEAD_LEN)]);
+    match text {
+        Cow::Borrowed(x) => Cow::Borrowed(x.trim_start_matches('\u{feff}').trim_start()),
+        Cow::Owned(x) => Cow::Owned(x.trim_start_matches('\u{feff}').trim_start().to_string()),
+    }
+}
+
+/// Встречается ли `needle` в содержимом файла.
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|x| x == needle)
+}
+
+fn format_error<E>(format: &'static str) -> impl FnOnce(E) -> ErrorLoad
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    move |e| ErrorLoad::Format(format, Box::new(e))
+}
+
+fn without_warnings(quest: Quest) -> Imported {
+    Imported {
+        quest,
+        warnings: Vec::new(),
+    }
+}
+
+/// GraphML: yEd Live, yEd desktop и обычный GraphML.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GraphMLLoader;
+
+impl QuestLoader for GraphMLLoader {
+    fn name(&self) -> &'static str {
+        "GraphML"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["graphml", "xml"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        head(bytes).contains("<graphml")
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        let text = decode_text(bytes)?;
+        let diagnostics = read_graphml_diagnostics(text);
+        if diagnostics.errors.is_empty() {
+            return diagnostics
+                .into_result()
+                .map(without_warnings)
+                .map_err(format_error(self.name()));
+        }
+        let report = render_errors(text, &diagnostics.errors);
+        Err(ErrorLoad::Format(
+            self.name(),
+            Box::new(ErrorGraphMLReport {
+                errors: diagnostics.errors,
+                report,
+            }),
+        ))
+    }
+}
+
+/// JSON формат квеста ([`read_quest_json`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuestJsonLoader;
+
+impl QuestLoader for QuestJsonLoader {
+    fn name(&self) -> &'static str {
+        "JSON квеста"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // Поле `version` необязательно, поэтому квест узнается по сценам
+        // или по тому, что это не история Ink
+        let head = head(bytes);
+        head.starts_with('{')
+            && (head.contains("\"scenes\"") || !contains(bytes, b"\"inkVersion\""))
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        read_quest_json(decode_text(bytes)?)
+            .map(without_warnings)
+            .map_err(format_error(self.name()))
+    }
+}
+
+/// Скомпилированная история Ink ([`read_ink_json`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InkLoader;
+
+impl QuestLoader for InkLoader {
+    fn name(&self) -> &'static str {
+        "истории Ink"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        head(bytes).starts_with('{') && contains(bytes, b"\"inkVersion\"")
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        read_ink_json(decode_text(bytes)?).map_err(format_error(self.name()))
+    }
+}
+
+/// История Twine в формате Twee 3 ([`read_twee`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TweeLoader;
+
+impl QuestLoader for TweeLoader {
+    fn name(&self) -> &'static str {
+        "истории Twee"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["twee", "tw"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        head(bytes).starts_with("::")
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        read_twee(decode_text(bytes)?).map_err(format_error(self.name()))
+    }
+}
+
+/// Архив или опубликованная история Twine 2 ([`read_twine_html`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TwineHtmlLoader;
+
+impl QuestLoader for TwineHtmlLoader {
+    fn name(&self) -> &'static str {
+        "истории Twine"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html", "htm"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // В опубликованной истории данные идут после скриптов движка
+        contains(bytes, b"<tw-storydata")
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        read_twine_html(decode_text(bytes)?).map_err(format_error(self.name()))
+    }
+}
+
+/// Graphviz DOT ([`read_dot`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotLoader;
+
+impl QuestLoader for DotLoader {
+    fn name(&self) -> &'static str {
+        "Graphviz DOT"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["dot", "gv"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        let head = head(bytes);
+        let mut rest = head.as_ref();
+        // Пропустить комментарии перед заголовком графа
+        loop {
+            rest = rest.trim_start();
+            if let Some(x) = rest.strip_prefix("//") {
+                rest = x.find('\n').map_or("", |i| &x[i..]);
+            } else if let Some(x) = rest.strip_prefix("/*") {
+                rest = x.find("*/").map_or("", |i| &x[i + 2..]);
+            } else {
+                break;
+            }
+        }
+        let keyword = rest
+            .split(|x: char| x.is_whitespace() || x == '{')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        matches!(keyword.as_str(), "digraph" | "graph" | "strict")
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        read_dot(decode_text(bytes)?)
+            .map(without_warnings)
+            .map_err(format_error(self.name()))
+    }
+}
+
+/// Реестр загрузчиков квестов, который выбирает формат файла.
+///
+/// [`QuestLoaders::default`] знает все форматы этого крейта; сторонние
+/// загрузчики добавляются через [`QuestLoaders::register`].
+pub struct QuestLoaders {
+    /// Загрузчики в порядке регистрации.
+    loaders: Vec<Box<dyn QuestLoader>>,
+}
+
+impl QuestLoaders {
+    /// Пустой реестр.
+    pub fn new() -> Self {
+        QuestLoaders {
+            loaders: Vec::new(),
+        }
+    }
+
+    /// Добавить загрузчик. Он проверяется раньше добавленных до него,
+    /// поэтому может заменить встроенный загрузчик того же формата.
+    pub fn register(&mut self, loader: impl QuestLoader + 'static) -> &mut Self {
+        self.loaders.push(Box::new(loader));
+        self
+    }
+
+    /// Загрузчики в порядке проверки.
+    pub fn loaders(&self) -> impl Iterator<Item = &dyn QuestLoader> {
+        self.loaders.iter().rev().map(|x| x.as_ref())
+    }
+
+    /// Все известные расширения файлов без повторов, например для `accept` в HTML.
+    pub fn extensions(&self) -> Vec<&'static str> {
+        let mut extensions: Vec<&'static str> = Vec::new();
+        for x in self.loaders().flat_map(|x| x.extensions().iter()) {
+            if !extensions.contains(x) {
+                extensions.push(x);
+            }
+        }
+        extensions
+    }
+
+    /// Выбрать загрузчик для файла.
+    ///
+    /// Из загрузчиков с подходящим расширением берется тот, кто узнал
+    /// содержимое. Если расширение незнакомо, загрузчик выбирается только
+    /// по содержимому.
+    ///
+    /// # Errors
+    ///
+    /// Содержимое не узнал ни один загрузчик ([`ErrorLoad::UnknownFormat`])
+    /// или ни один из нескольких загрузчиков с подходящим расширением
+    /// ([`ErrorLoad::AmbiguousFormat`]).
+    pub fn detect(&self, file_name: &str, bytes: &[u8]) -> Result<&dyn QuestLoader, ErrorLoad> {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|(_, x)| x.to_lowercase())
+            .unwrap_or_default();
+        let by_extension: Vec<&dyn QuestLoader> = self
+            .loaders()
+            .filter(|x| x.extensions().contains(&extension.as_str()))
+            .collect();
+
+        match by_extension.as_slice() {
+            [] => self
+                .loaders()
+                .find(|x| x.sniff(bytes))
+                .ok_or_else(|| ErrorLoad::UnknownFormat(file_name.to_string())),
+            [single] => Ok(*single),
+            several => several
+                .iter()
+                .copied()
+                .find(|x| x.sniff(bytes))
+                .ok_or_else(|| {
+                    ErrorLoad::AmbiguousFormat(
+                        file_name.to_string(),
+                        several.iter().map(|x| x.name()).collect(),
+                    )
+                }),
+        }
+    }
+
+    /// Загрузить квест из файла любого известного формата.
+    ///
+    /// # Errors
+    ///
+    /// Формат не определен или файл не удалось прочитать в этом формате.
+    pub fn load(&self, file_name: &str, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        self.detect(file_name, bytes)?.load(bytes)
+    }
+}
+
+impl Default for QuestLoaders {
+    fn default() -> Self {
+        let mut loaders = QuestLoaders::new();
+        loaders
+            .register(GraphMLLoader)
+            .register(QuestJsonLoader)
+            .register(InkLoader)
+            .register(TweeLoader)
+            .register(TwineHtmlLoader)
+            .register(DotLoader);
+        loaders
+    }
+}
+
+impl fmt::Debug for QuestLoaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.loaders().map(|x| x.name()))
+            .finish()
+    }
+}
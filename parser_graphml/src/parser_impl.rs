@@ -1,153 +1,757 @@
-use mdo::option::bind;
 use petgraph::graph::{Graph, NodeIndex};
+use petgraph::Direction;
 use roxmltree::Node;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use crate::condition::{parse_condition, Condition};
+use crate::dialect::Dialect;
 use crate::errors::*;
+use crate::keys::{
+    find_data_tags, find_data_translations, find_data_value, GraphMLKey, KeyDomain, KeyTable,
+};
+use crate::script::{parse_effects, parse_variables, split_description_effects, Effect, Variable};
+use crate::source::{GraphMLSource, RawElement};
 use crate::types::*;
 
-/// Распарсить граф из GraphML формата.
+/// Распарсить квест из GraphML формата.
+///
+/// Стартовая сцена задается значением ключа `start` у графа (идентификатор сцены)
+/// или флагом `start` у одной из сцен. Если ни того, ни другого нет,
+/// то стартовой считается единственная сцена без входящих ребер.
+///
+/// Group node со вложенным графом становится главой ([`Chapter`]), а сцены
+/// вложенного графа входят в нее. Ребро, которое ведет в саму главу,
+/// ведет в ее начальную сцену.
 ///
 /// # Errors
 ///
-/// Если парсинг провалился, то возвращает ошибки парсинга [`errors::Error`].
-pub fn read_graphml(xml_doc: &str) -> ResultGraphML {
+/// Если парсинг провалился, то возвращает первую ошибку парсинга [`errors::Error`].
+/// Чтобы получить все ошибки сразу, используйте [`read_graphml_diagnostics`].
+pub fn read_graphml(xml_doc: &str) -> ResultGraphML<'_> {
+    read_graphml_diagnostics(xml_doc).into_result()
+}
+
+/// Распарсить квест из GraphML формата в режиме диагностики.
+///
+/// Не останавливается на первой ошибке: пропускает ошибочные элементы,
+/// собирает все найденные проблемы и возвращает их вместе с графом,
+/// который удалось построить.
+pub fn read_graphml_diagnostics(xml_doc: &str) -> Diagnostics {
+    let mut errors = Vec::new();
+
     let doc = match roxmltree::Document::parse(xml_doc) {
         Ok(v) => v,
-        Err(error) => return Err(Error::ParseXMLDocument(error)),
+        Err(error) => {
+            errors.push(Error::ParseXMLDocument(error));
+            return Diagnostics {
+                graph: Graph::new(),
+                start: None,
+                chapters: Vec::new(),
+                locale: None,
+                variables: Vec::new(),
+                errors,
+                source: None,
+            };
+        }
     };
 
-    let (vertexes, edges): (Vec<GraphMLNode>, Vec<GraphMLNode>) =
-        prepare_graphml(doc)?.into_iter().partition(|x| match x {
-            GraphMLNode::Node(_) => true,
-            _ => false,
-        });
+    let mut vertexes = Vec::new();
+    let mut edges = Vec::new();
+    let mut chapters = Vec::new();
+    let mut start_ids = Vec::new();
+    let mut locale = None;
+    let mut variables = Vec::new();
+    let (prepared, mut source) = prepare_graphml(xml_doc, &doc, &mut errors);
+    for x in prepared {
+        match x {
+            GraphMLNode::Node(..) => vertexes.push(x),
+            GraphMLNode::Weight(_) => edges.push(x),
+            GraphMLNode::Chapter(chapter) => chapters.push(chapter),
+            GraphMLNode::Start(id, location) => start_ids.push((id, location)),
+            GraphMLNode::Locale(x) => locale = Some(x),
+            GraphMLNode::Variables(x) => variables = x,
+        }
+    }
+
+    let start_marked = !start_ids.is_empty();
+    let graph_location = doc
+        .root_element()
+        .children()
+        .find(|x| x.tag_name().name() == "graph")
+        .map_or_else(|| Location::of(&doc.root_element()), |x| Location::of(&x));
+    let (graph, start, chapters) = format_graph(
+        vertexes,
+        edges,
+        chapters,
+        start_ids,
+        graph_location,
+        &mut errors,
+    );
+
+    if let Some(source) = &mut source {
+        let entries = chapters
+            .iter()
+            .filter_map(|x| Some((x.id.as_str(), graph[x.entry?].id.as_str())))
+            .collect();
+        source.resolve_chapter_targets(&entries);
+        source.remember_quest(&graph, start, &locale, &variables, start_marked);
+    }
 
-    format_graph(vertexes, edges)
+    Diagnostics {
+        graph,
+        start,
+        chapters,
+        locale,
+        variables,
+        errors,
+        source,
+    }
 }
 
 #[derive(Debug)]
 struct XmlEdge {
+    id: String,
     source_id: String,
     target_id: String,
     text: String,
+    annotations: Annotations,
+    effects: Vec<Effect>,
+    condition: Option<Condition>,
+    translations: BTreeMap<String, String>,
+    location: Location,
+}
+
+/// Описание, ссылка, теги, концовка, изменения переменных, условие и переводы
+/// из ключа `lang` сцены или варианта выбора.
+#[derive(Debug, Default)]
+struct Annotations {
+    description: Option<String>,
+    url: Option<String>,
+    tags: Vec<String>,
+    /// Значение ключа `ending`, если он есть у элемента.
+    ending: Option<String>,
+    /// Значение ключа `effects`.
+    effects: Option<String>,
+    /// Значения ключей `condition` и `locked`.
+    condition: Option<String>,
+    locked: Option<String>,
+    translations: Vec<(String, Vec<String>)>,
+}
+
+/// Ключи описания, ссылки, тегов, концовки, изменений переменных, условия
+/// и переводов для сцен или вариантов выбора.
+pub(crate) struct AnnotationKeys<'a> {
+    pub description: Option<&'a GraphMLKey>,
+    pub url: Option<&'a GraphMLKey>,
+    pub tags: Option<&'a GraphMLKey>,
+    pub ending: Option<&'a GraphMLKey>,
+    pub effects: Option<&'a GraphMLKey>,
+    pub condition: Option<&'a GraphMLKey>,
+    pub locked: Option<&'a GraphMLKey>,
+    pub lang: Option<&'a GraphMLKey>,
+}
+
+impl<'a> AnnotationKeys<'a> {
+    /// Ключ описания не используется, если по нему уже читается подпись.
+    pub(crate) fn find(
+        keys: &'a KeyTable,
+        domain: KeyDomain,
+        label_key: Option<&GraphMLKey>,
+    ) -> Self {
+        AnnotationKeys {
+            description: keys
+                .find_by_name(domain, "description")
+                .filter(|x| label_key.map(|key| &key.id) != Some(&x.id)),
+            url: keys.find_by_name(domain, "url"),
+            tags: keys
+                .find_by_name(domain, "UserTags")
+                .or_else(|| keys.find_by_name(domain, "tags")),
+            ending: keys.find_by_name(domain, ENDING_TAG),
+            effects: keys.find_by_name(domain, EFFECTS_KEY_NAME),
+            condition: keys.find_by_name(domain, CONDITION_KEY_NAME),
+            locked: keys.find_by_name(domain, LOCKED_KEY_NAME),
+            lang: keys.find_by_name(domain, LANG_KEY_NAME),
+        }
+    }
+
+    fn read(&self, node: &Node<'_, '_>) -> Annotations {
+        let find_value = |key: Option<&GraphMLKey>| {
+            key.and_then(|key| find_data_value(node, key))
+                .filter(|x| !x.is_empty())
+        };
+
+        Annotations {
+            description: find_value(self.description),
+            url: find_value(self.url),
+            tags: self
+                .tags
+                .map(|key| find_data_tags(node, key))
+                .unwrap_or_default(),
+            // Пустое значение ключа тоже отмечает концовку
+            ending: self.ending.and_then(|key| find_data_value(node, key)),
+            effects: find_value(self.effects),
+            condition: find_value(self.condition),
+            locked: find_value(self.locked),
+            translations: self
+                .lang
+                .map(|key| find_data_translations(node, key))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Имя ключа с идентификатором стартовой сцены у графа и флагом стартовой сцены у сцены.
+pub(crate) const START_KEY_NAME: &str = "start";
+/// Имя ключа с переводами у сцен и вариантов выбора и с языком основного текста у графа.
+pub(crate) const LANG_KEY_NAME: &str = "lang";
+/// Имя ключа с объявлениями переменных у графа.
+pub(crate) const VARIABLES_KEY_NAME: &str = "variables";
+/// Имя ключа с изменениями переменных у вариантов выбора.
+pub(crate) const EFFECTS_KEY_NAME: &str = "effects";
+/// Имя ключа с условием доступности варианта выбора.
+pub(crate) const CONDITION_KEY_NAME: &str = "condition";
+/// Имя ключа с причиной, которую игрок видит у недоступного варианта выбора.
+pub(crate) const LOCKED_KEY_NAME: &str = "locked";
+
+/// Собрать переводы сцены: подписи с языковой меткой идут блоками по порядку,
+/// а переводы из ключа `lang` добавляются для языков, которых нет среди подписей.
+fn merge_translations(
+    labels: Vec<(String, String)>,
+    data: Vec<(String, Vec<String>)>,
+) -> BTreeMap<String, Vec<String>> {
+    let mut translations: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (locale, text) in labels {
+        translations.entry(locale).or_default().push(text);
+    }
+    for (locale, blocks) in data {
+        translations.entry(locale).or_insert(blocks);
+    }
+    translations.retain(|_, x| !x.is_empty());
+    translations
+}
+
+/// Глава до того, как найдена ее начальная сцена.
+#[derive(Debug)]
+struct XmlChapter {
+    chapter: Chapter,
+    /// Идентификатор начальной сцены из ключа `start` вложенного графа.
+    entry_id: Option<(String, Location)>,
 }
 
 #[derive(Debug)]
 enum GraphMLNode {
     Weight(XmlEdge),
-    Node(Vertex),
+    Node(Vertex, Location),
+    Chapter(XmlChapter),
+    /// Отметка стартовой сцены с указанным идентификатором.
+    Start(String, Location),
+    /// Язык основного текста квеста.
+    Locale(String),
+    /// Переменные квеста.
+    Variables(Vec<Variable>),
 }
 
-fn prepare_graphml(doc: roxmltree::Document) -> Result<Vec<GraphMLNode>, Error> {
+fn prepare_graphml(
+    xml_doc: &str,
+    doc: &roxmltree::Document,
+    errors: &mut Vec<Error>,
+) -> (Vec<GraphMLNode>, Option<GraphMLSource>) {
+    const GRAPH: &str = "graph";
     const NODE: &str = "node";
     const EDGE: &str = "edge";
-    const NODE_TEXT_ATTR_KEY: &str = "d3";
-    const EDGE_TEXT_ATTR_KEY: &str = "d10";
     const VERTEX_SOURCE_ATTR_KEY: &str = "source";
     const VERTEX_TARGET_ATTR_KEY: &str = "target";
 
+    let mut acc: Vec<GraphMLNode> = Vec::new();
+
+    let dialect = Dialect::detect(doc);
+    let keys = KeyTable::from_document(doc);
+    let label_keys = match dialect.label_keys(&keys, Location::of(&doc.root_element())) {
+        Ok(label_keys) => label_keys,
+        Err(error) => {
+            errors.push(error);
+            return (acc, None);
+        }
+    };
+    let mut source = GraphMLSource::new(xml_doc, doc, dialect, &keys, label_keys);
+    let node_annotation_keys = AnnotationKeys::find(&keys, KeyDomain::Node, Some(label_keys.node));
+    let edge_annotation_keys = AnnotationKeys::find(&keys, KeyDomain::Edge, label_keys.edge);
+    let graph_start_key = keys.find_by_name(KeyDomain::Graph, START_KEY_NAME);
+    let graph_lang_key = keys.find_by_name(KeyDomain::Graph, LANG_KEY_NAME);
+    let graph_variables_key = keys.find_by_name(KeyDomain::Graph, VARIABLES_KEY_NAME);
+    let node_start_key = keys.find_by_name(KeyDomain::Node, START_KEY_NAME);
+
     let filtered_nodes = doc.root().descendants().filter(|node| node.is_element());
+    let mut edges_count = 0;
+    let mut chapters: Vec<XmlChapter> = Vec::new();
+    // Индексы глав по началу group node в документе.
+    let mut chapter_indexes: HashMap<usize, usize> = HashMap::new();
+    // Переменные объявляются у корневого графа, который идет раньше ребер.
+    let mut variables = Vec::new();
 
-    let mut acc: Vec<GraphMLNode> = Vec::new();
     for node in filtered_nodes {
         match node.tag_name().name().trim() {
+            GRAPH => {
+                let is_root = node.parent() == Some(doc.root_element());
+                if let Some(locale) = graph_lang_key
+                    .filter(|_| is_root)
+                    .and_then(|key| find_data_value(&node, key))
+                    .filter(|x| !x.is_empty())
+                {
+                    acc.push(GraphMLNode::Locale(locale));
+                }
+                if let Some(text) = graph_variables_key
+                    .filter(|_| is_root)
+                    .and_then(|key| find_data_value(&node, key))
+                {
+                    match parse_variables(&text) {
+                        Ok(x) => variables = x,
+                        Err(e) => errors.push(Error::PrepareGraphml(
+                            ErrorPrepareGraphML::InvalidScript(e, Location::of(&node)),
+                        )),
+                    }
+                    acc.push(GraphMLNode::Variables(variables.clone()));
+                }
+                if let Some(id) = graph_start_key
+                    .and_then(|key| find_data_value(&node, key))
+                    .filter(|id| !id.is_empty())
+                {
+                    let location = Location::of(&node);
+                    let chapter = node
+                        .parent()
+                        .and_then(|x| chapter_indexes.get(&x.range().start));
+                    match chapter {
+                        Some(chapter) => chapters[*chapter].entry_id = Some((id, location)),
+                        None => acc.push(GraphMLNode::Start(id, location)),
+                    }
+                }
+            }
             NODE => {
-                acc.push(GraphMLNode::Node(Vertex {
-                    id: find_node_attr_by_key(&node, "id")?,
-                    text: find_xml_node_text(&node, NODE_TEXT_ATTR_KEY)?.to_string(),
-                }));
+                let location = Location::of(&node);
+                let id = match find_node_attr_by_key(&node, "id") {
+                    Ok(id) => id,
+                    Err(error) => {
+                        errors.push(error);
+                        continue;
+                    }
+                };
+                let chapter = find_parent_chapter(&node, &chapter_indexes);
+                if node.children().any(|x| x.tag_name().name() == GRAPH) {
+                    chapter_indexes.insert(node.range().start, chapters.len());
+                    chapters.push(XmlChapter {
+                        chapter: Chapter {
+                            id,
+                            title: dialect
+                                .find_node_text(&node, label_keys.node)
+                                .unwrap_or_default(),
+                            parent: chapter,
+                            entry: None,
+                            position: dialect.find_node_position(&node, &keys),
+                        },
+                        entry_id: None,
+                    });
+                    continue;
+                }
+
+                let text = match dialect.find_node_text(&node, label_keys.node) {
+                    Some(text) => text,
+                    None => {
+                        errors.push(Error::PrepareGraphml(
+                            ErrorPrepareGraphML::NotFoundNodeLabel(location.clone()),
+                        ));
+                        String::new()
+                    }
+                };
+                if node_start_key
+                    .and_then(|key| find_data_value(&node, key))
+                    .is_some_and(|x| is_true_value(&x))
+                {
+                    acc.push(GraphMLNode::Start(id.clone(), location.clone()));
+                }
+                source.nodes.entry(id.clone()).or_insert_with(|| {
+                    let label =
+                        dialect.label_splice(xml_doc, &node, label_keys.node, KeyDomain::Node);
+                    RawElement::new(xml_doc, &node, Some(label))
+                });
+                let annotations = node_annotation_keys.read(&node);
+                let translations = merge_translations(
+                    dialect.find_translated_labels(&node, label_keys.node, KeyDomain::Node),
+                    annotations.translations,
+                );
+                acc.push(GraphMLNode::Node(
+                    Vertex {
+                        id,
+                        text,
+                        paragraphs: dialect.find_node_paragraphs(&node, label_keys.node),
+                        description: annotations.description,
+                        url: annotations.url,
+                        ending: Ending::find(annotations.ending.as_deref(), &annotations.tags),
+                        tags: annotations.tags,
+                        chapter,
+                        position: dialect.find_node_position(&node, &keys),
+                        translations,
+                    },
+                    location,
+                ));
             }
             EDGE => {
+                edges_count += 1;
+                let source_id = find_node_attr_by_key(&node, VERTEX_SOURCE_ATTR_KEY);
+                let target_id = find_node_attr_by_key(&node, VERTEX_TARGET_ATTR_KEY);
+                let (source_id, target_id) = match (source_id, target_id) {
+                    (Ok(source_id), Ok(target_id)) => (source_id, target_id),
+                    (source_id, target_id) => {
+                        errors.extend(source_id.err());
+                        errors.extend(target_id.err());
+                        continue;
+                    }
+                };
+
+                let id = node
+                    .attribute("id")
+                    .map_or_else(|| format!("e{}", edges_count - 1), String::from);
+                let text = label_keys
+                    .edge
+                    .and_then(|key| dialect.find_edge_text(&node, key))
+                    .unwrap_or_default();
+                source.edges.entry(id.clone()).or_insert_with(|| {
+                    let label = label_keys
+                        .edge
+                        .map(|key| dialect.label_splice(xml_doc, &node, key, KeyDomain::Edge));
+                    RawElement {
+                        endpoints: Some((source_id.clone(), target_id.clone())),
+                        ..RawElement::new(xml_doc, &node, label)
+                    }
+                });
+
+                let location = Location::of(&node);
+                let mut annotations = edge_annotation_keys.read(&node);
+                let (description, description_effects) = annotations
+                    .description
+                    .as_deref()
+                    .map_or((None, String::new()), split_description_effects);
+                annotations.description = description;
+                let effects_text = annotations
+                    .effects
+                    .iter()
+                    .chain(Some(&description_effects))
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let effects = match parse_effects(&effects_text, &variables) {
+                    Ok(effects) => effects,
+                    Err(e) => {
+                        errors.push(Error::PrepareGraphml(ErrorPrepareGraphML::InvalidScript(
+                            e,
+                            location.clone(),
+                        )));
+                        Vec::new()
+                    }
+                };
+                let condition = match annotations
+                    .condition
+                    .as_deref()
+                    .map(|x| parse_condition(x, &variables))
+                    .transpose()
+                {
+                    Ok(expr) => expr.map(|expr| Condition {
+                        expr,
+                        locked: annotations.locked.clone(),
+                    }),
+                    Err(e) => {
+                        errors.push(Error::PrepareGraphml(ErrorPrepareGraphML::InvalidScript(
+                            e,
+                            location.clone(),
+                        )));
+                        None
+                    }
+                };
+                let labels = label_keys.edge.map_or_else(Vec::new, |key| {
+                    dialect.find_translated_labels(&node, key, KeyDomain::Edge)
+                });
+                let translations = merge_translations(labels, annotations.translations.clone())
+                    .into_iter()
+                    .map(|(locale, blocks)| (locale, blocks.join("\n")))
+                    .collect();
                 acc.push(GraphMLNode::Weight(XmlEdge {
-                    source_id: find_node_attr_by_key(&node, VERTEX_SOURCE_ATTR_KEY)?,
-                    target_id: find_node_attr_by_key(&node, VERTEX_TARGET_ATTR_KEY)?,
-                    text: find_xml_node_text(&node, EDGE_TEXT_ATTR_KEY)
-                        .unwrap_or("")
-                        .to_string(),
+                    id,
+                    source_id,
+                    target_id,
+                    location,
+                    text,
+                    annotations,
+                    effects,
+                    condition,
+                    translations,
                 }));
             }
             _ => (),
         }
     }
 
-    Ok(acc)
+    acc.extend(chapters.into_iter().map(GraphMLNode::Chapter));
+    (acc, Some(source))
 }
 
-fn find_xml_node_text<'a>(node: &Node<'a, 'a>, attr_key: &str) -> Result<&'a str, Error> {
-    const TAG_DATA: &str = "data";
-    const ATTR_TAG_KEY: &str = "key";
-    const TAG_LIST: &str = "List";
-    const TAG_LABEL: &str = "Label";
-    const TAG_LABEL_TEXT: &str = "Label.Text";
-
-    let result = mdo! {
-        data =<< node.children()
-            .find(|x| {
-                let found_key = match find_node_attr_by_key(x, ATTR_TAG_KEY) {
-                    Ok(x) => x == attr_key,
-                    _ => false,
-                };
-
-                x.tag_name().name() == TAG_DATA && found_key
-            });
-        l =<< data.children()
-            .find(|x| x.tag_name().name() == TAG_LIST);
-        lbl =<< l.children()
-            .find(|x| x.tag_name().name() == TAG_LABEL);
-        lbl_txt =<< lbl.children()
-            .find(|x| x.tag_name().name() == TAG_LABEL_TEXT);
+/// Индекс главы, в граф которой вложен элемент.
+fn find_parent_chapter(
+    node: &Node<'_, '_>,
+    chapter_indexes: &HashMap<usize, usize>,
+) -> Option<usize> {
+    node.ancestors()
+        .skip(1)
+        .find(|x| x.tag_name().name() == "node")
+        .and_then(|x| chapter_indexes.get(&x.range().start).copied())
+}
 
-        ret lbl_txt.text()
-    };
+/// Входит ли сцена из главы `chapter` в главу `target` или в ее вложенные главы.
+fn is_in_chapter(chapter: Option<usize>, target: usize, parents: &[Option<usize>]) -> bool {
+    let mut chapter = chapter;
+    while let Some(x) = chapter {
+        if x == target {
+            return true;
+        }
+        chapter = parents[x];
+    }
+    false
+}
 
-    result.ok_or(Error::PrepareGraphml(
-        ErrorPrepareGraphML::NotFoundAttrByKey(attr_key.to_string()),
-    ))
+/// Значение флага: `true`, `1` или `yes` в любом регистре.
+pub(crate) fn is_true_value(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes")
 }
 
 fn find_node_attr_by_key(node: &Node<'_, '_>, attr_key: &str) -> Result<String, Error> {
     node.attributes()
         .iter()
         .find(|a| a.name().contains(attr_key))
-        .and_then(|a| Some(a.value().into()))
-        .ok_or(Error::PrepareGraphml(
-            ErrorPrepareGraphML::NotFoundAttrByKey(attr_key.to_string()),
-        ))
+        .map(|a| a.value().into())
+        .ok_or_else(|| {
+            Error::PrepareGraphml(ErrorPrepareGraphML::NotFoundAttrByKey(
+                attr_key.to_string(),
+                Location::of(node),
+            ))
+        })
 }
 
-fn format_graph<'a>(vertexes: Vec<GraphMLNode>, edges: Vec<GraphMLNode>) -> ResultGraphML<'a> {
-    use std::collections::HashMap;
-
+fn format_graph(
+    vertexes: Vec<GraphMLNode>,
+    edges: Vec<GraphMLNode>,
+    chapters: Vec<XmlChapter>,
+    start_ids: Vec<(String, Location)>,
+    graph_location: Location,
+    errors: &mut Vec<Error>,
+) -> (Graph<Vertex, Edge>, Option<NodeIndex>, Vec<Chapter>) {
     let mut graph = Graph::<Vertex, Edge>::new();
     let mut vertex_indexes: HashMap<&String, NodeIndex> = HashMap::new();
 
     for vertex in &vertexes {
-        if let GraphMLNode::Node(v) = vertex {
+        if let GraphMLNode::Node(v, location) = vertex {
+            if vertex_indexes.contains_key(&v.id) {
+                errors.push(Error::FormatGraph(ErrorFormatGraph::DuplicateNodeId(
+                    location.clone(),
+                )));
+                continue;
+            }
             vertex_indexes.insert(&v.id, graph.add_node(v.clone()));
         }
     }
 
+    let xml_edges: Vec<&XmlEdge> = edges
+        .iter()
+        .filter_map(|x| match x {
+            GraphMLNode::Weight(e) => Some(e),
+            _ => None,
+        })
+        .collect();
+    let chapters = find_chapter_entries(&graph, &vertex_indexes, &xml_edges, chapters, errors);
+    let chapter_indexes: HashMap<&String, usize> = chapters
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (&x.id, i))
+        .collect();
+
     for edge in &edges {
         if let GraphMLNode::Weight(e) = edge {
             let edge = Edge {
+                id: e.id.clone(),
                 text: e.text.clone(),
+                description: e.annotations.description.clone(),
+                url: e.annotations.url.clone(),
+                tags: e.annotations.tags.clone(),
+                effects: e.effects.clone(),
+                condition: e.condition.clone(),
+                translations: e.translations.clone(),
             };
 
-            let try_get_node_by_id = |id: &String| match vertex_indexes.get(id) {
-                Some(node_index) => Ok(*node_index),
-                None => {
-                    return Err(Error::FormatGraph(ErrorFormatGraph::NotFoundNodeById(
+            let try_get_node_by_id = |id: &String, is_target: bool| {
+                let error = |x| Err(Error::FormatGraph(x));
+                match (vertex_indexes.get(id), chapter_indexes.get(id)) {
+                    (Some(node_index), _) => Ok(*node_index),
+                    (None, Some(_)) if !is_target => error(ErrorFormatGraph::ChoiceFromChapter(
                         id.to_string(),
-                    )))
+                        e.location.clone(),
+                    )),
+                    (None, Some(chapter)) => match chapters[*chapter].entry {
+                        Some(entry) => Ok(entry),
+                        None => error(ErrorFormatGraph::EmptyChapter(
+                            id.to_string(),
+                            e.location.clone(),
+                        )),
+                    },
+                    (None, None) => error(ErrorFormatGraph::NotFoundNodeById(
+                        id.to_string(),
+                        e.location.clone(),
+                    )),
                 }
             };
-            graph.add_edge(
-                try_get_node_by_id(&e.source_id)?,
-                try_get_node_by_id(&e.target_id)?,
-                edge,
-            );
+            match (
+                try_get_node_by_id(&e.source_id, false),
+                try_get_node_by_id(&e.target_id, true),
+            ) {
+                (Ok(source), Ok(target)) => {
+                    graph.add_edge(source, target, edge);
+                }
+                (source, target) => {
+                    errors.extend(source.err());
+                    errors.extend(target.err());
+                }
+            }
         }
     }
 
-    Ok(graph)
+    let start = match find_start_scene(&graph, start_ids) {
+        Ok(start) => Some(start),
+        Err(error) => {
+            errors.push(Error::FormatGraph(match error {
+                ErrorStartScene::NotFound => ErrorFormatGraph::NotFoundStartScene(graph_location),
+                ErrorStartScene::Ambiguous(ids, location) => {
+                    ErrorFormatGraph::AmbiguousStartScene(ids, location.unwrap_or(graph_location))
+                }
+                ErrorStartScene::NotFoundScene(id, location) => {
+                    ErrorFormatGraph::NotFoundNodeById(id, location)
+                }
+            }));
+            None
+        }
+    };
+
+    (graph, start, chapters)
+}
+
+/// Найти начальные сцены глав.
+fn find_chapter_entries(
+    graph: &Graph<Vertex, Edge>,
+    vertex_indexes: &HashMap<&String, NodeIndex>,
+    edges: &[&XmlEdge],
+    chapters: Vec<XmlChapter>,
+    errors: &mut Vec<Error>,
+) -> Vec<Chapter> {
+    let parents: Vec<Option<usize>> = chapters.iter().map(|x| x.chapter.parent).collect();
+    let edge_ids = edges
+        .iter()
+        .map(|e| (e.source_id.as_str(), e.target_id.as_str()));
+
+    chapters
+        .into_iter()
+        .enumerate()
+        .map(|(i, xml_chapter)| {
+            let entry = match xml_chapter.entry_id {
+                Some((id, location)) => match vertex_indexes.get(&id) {
+                    Some(entry) => Some(*entry),
+                    None => {
+                        errors.push(Error::FormatGraph(ErrorFormatGraph::NotFoundNodeById(
+                            id, location,
+                        )));
+                        None
+                    }
+                },
+                None => find_chapter_entry(graph, i, &parents, edge_ids.clone()),
+            };
+
+            Chapter {
+                entry,
+                ..xml_chapter.chapter
+            }
+        })
+        .collect()
+}
+
+/// Начальная сцена главы `chapter` без явной отметки: первая сцена главы,
+/// в которую не ведут варианты выбора из других ее сцен, иначе просто первая.
+///
+/// `parents` - родительские главы, `edges` - идентификаторы сцен, которые
+/// соединяют варианты выбора.
+pub(crate) fn find_chapter_entry<'a>(
+    graph: &Graph<Vertex, Edge>,
+    chapter: usize,
+    parents: &[Option<usize>],
+    edges: impl Iterator<Item = (&'a str, &'a str)> + Clone,
+) -> Option<NodeIndex> {
+    let members: Vec<NodeIndex> = graph
+        .node_indices()
+        .filter(|ix| is_in_chapter(graph[*ix].chapter, chapter, parents))
+        .collect();
+    let member_ids: HashSet<&str> = members.iter().map(|ix| graph[*ix].id.as_str()).collect();
+
+    members
+        .iter()
+        .copied()
+        .find(|ix| {
+            !edges
+                .clone()
+                .any(|(source, target)| target == graph[*ix].id && member_ids.contains(source))
+        })
+        .or_else(|| members.first().copied())
+}
+
+/// Почему не удалось выбрать стартовую сцену. `L` - место отметки в файле.
+pub(crate) enum ErrorStartScene<L> {
+    /// Нет ни отмеченной сцены, ни единственной сцены без входящих вариантов выбора.
+    NotFound,
+    /// Подходят сцены с перечисленными идентификаторами и место второй отметки,
+    /// если сцены отмечены явно.
+    Ambiguous(Vec<String>, Option<L>),
+    /// Отмечена сцена, которой нет в графе.
+    NotFoundScene(String, L),
+}
+
+/// Выбрать стартовую сцену: единственную из отмеченных `start_ids`, а если
+/// отметок нет, то единственную сцену без входящих вариантов выбора.
+pub(crate) fn find_start_scene<L: Clone>(
+    graph: &Graph<Vertex, Edge>,
+    mut start_ids: Vec<(String, L)>,
+) -> Result<NodeIndex, ErrorStartScene<L>> {
+    // Сцену могут отметить стартовой и флагом, и атрибутом графа - не обязательно подряд
+    let mut seen = HashSet::new();
+    start_ids.retain(|(id, _)| seen.insert(id.clone()));
+
+    if start_ids.is_empty() {
+        let mut sources = graph.externals(Direction::Incoming);
+        return match (sources.next(), sources.next()) {
+            (Some(start), None) => Ok(start),
+            (None, _) => Err(ErrorStartScene::NotFound),
+            (Some(_), Some(_)) => Err(ErrorStartScene::Ambiguous(
+                graph
+                    .externals(Direction::Incoming)
+                    .map(|ix| graph[ix].id.clone())
+                    .collect(),
+                None,
+            )),
+        };
+    }
+
+    if start_ids.len() > 1 {
+        let location = start_ids[1].1.clone();
+        return Err(ErrorStartScene::Ambiguous(
+            start_ids.into_iter().map(|(id, _)| id).collect(),
+            Some(location),
+        ));
+    }
+
+    let (id, location) = start_ids.remove(0);
+    graph
+        .node_indices()
+        .find(|ix| graph[*ix].id == id)
+        .ok_or(ErrorStartScene::NotFoundScene(id, location))
 }
@@ -0,0 +1,62 @@
+use crate::errors::*;
+
+/// Отрисовать ошибку парсинга в стиле rustc: сообщение, положение
+/// и строку документа с подчеркнутым началом элемента.
+///
+/// `source` - текст GraphML документа, на котором получена ошибка.
+pub fn render_error(source: &str, error: &Error) -> String {
+    let location = error.location();
+    let message = match error {
+        Error::ParseXMLDocument(_) => error.to_string(),
+        Error::PrepareGraphml(e) => e.to_string(),
+        Error::FormatGraph(e) => e.to_string(),
+    };
+
+    let mut out = format!("ошибка: {}\n", message);
+
+    let line = match source.lines().nth(location.line.saturating_sub(1) as usize) {
+        Some(line) => line.trim_end(),
+        None => {
+            out.push_str(&format!("  --> {}\n", location));
+            return out;
+        }
+    };
+
+    let line_number = location.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+    let column = location.column.saturating_sub(1) as usize;
+
+    // Табуляции переносятся в отступ, чтобы подчеркивание совпало со строкой.
+    let indent: String = line
+        .chars()
+        .take(column)
+        .map(|x| if x == '\t' { '\t' } else { ' ' })
+        .collect();
+    let rest: Vec<char> = line.chars().skip(column).collect();
+    let underline_len = rest
+        .iter()
+        .position(|x| *x == '>')
+        .map_or(rest.len(), |x| x + 1)
+        .max(1);
+
+    out.push_str(&format!("{}--> {}\n", gutter, location));
+    out.push_str(&format!("{} |\n", gutter));
+    out.push_str(&format!("{} | {}\n", line_number, line));
+    out.push_str(&format!(
+        "{} | {}{}\n",
+        gutter,
+        indent,
+        "^".repeat(underline_len)
+    ));
+
+    out
+}
+
+/// Отрисовать все ошибки диагностики одну за другой.
+pub fn render_errors(source: &str, errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(|x| render_error(source, x))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,460 @@
+use std::fmt;
+
+/// Оформленный текст сцены или варианта выбора.
+///
+/// Получается из текста подписи, в который авторы вставляют разметку
+/// из yEd: `<b>`/`<strong>`, `<i>`/`<em>`, `<br>`, `<font color="...">`,
+/// `<span style="color: ...">`, а также обычные переводы строк.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RichText {
+    pub spans: Vec<Span>,
+}
+
+/// Участок оформленного текста.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Span {
+    /// Текст с одним оформлением.
+    Text(String, Style),
+    /// Перевод строки.
+    LineBreak,
+}
+
+/// Оформление участка текста.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Style {
+    /// Курсив: `<i>`, `<em>`.
+    pub emphasis: bool,
+    /// Полужирный: `<b>`, `<strong>`.
+    pub strong: bool,
+    /// Цвет в записи CSS, как его указал автор: `#c00000`, `red`.
+    pub color: Option<String>,
+}
+
+impl RichText {
+    /// Разобрать текст подписи.
+    ///
+    /// Текст, обернутый в `<html>` (так yEd desktop сохраняет оформленные
+    /// подписи), разбирается как HTML: пробелы и переводы строк схлопываются,
+    /// а строки разделяются `<br>` и абзацами. Иначе переводы строк остаются.
+    /// Незнакомые теги отбрасываются, их содержимое остается.
+    pub fn parse(text: &str) -> RichText {
+        let html = text.trim_start().to_lowercase().starts_with("<html");
+        let mut parser = Parser {
+            html,
+            out: RichText::default(),
+            styles: Vec::new(),
+        };
+        parser.run(if html { text.trim() } else { text });
+        parser.finish()
+    }
+
+    /// Текст без оформления, переводы строк - `\n`.
+    pub fn plain(&self) -> String {
+        self.spans
+            .iter()
+            .map(|x| match x {
+                Span::Text(text, _) => text.as_str(),
+                Span::LineBreak => "\n",
+            })
+            .collect()
+    }
+
+    /// Текст с оформлением escape-последовательностями ANSI для терминала.
+    ///
+    /// Цвет задается 24-битным, если он записан как `#rgb` или `#rrggbb`,
+    /// иначе берется один из базовых цветов терминала по имени.
+    /// Незнакомые цвета пропускаются.
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        for span in &self.spans {
+            match span {
+                Span::Text(text, style) => {
+                    let codes = ansi_codes(style);
+                    if codes.is_empty() {
+                        out.push_str(text);
+                    } else {
+                        out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text));
+                    }
+                }
+                Span::LineBreak => out.push('\n'),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for RichText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.plain())
+    }
+}
+
+struct Parser {
+    html: bool,
+    out: RichText,
+    /// Открытые теги и оформление внутри них.
+    styles: Vec<(String, Style)>,
+}
+
+impl Parser {
+    fn style(&self) -> Style {
+        self.styles
+            .last()
+            .map(|(_, x)| x.clone())
+            .unwrap_or_default()
+    }
+
+    fn run(&mut self, text: &str) {
+        let mut rest = text;
+        while let Some(x) = rest.find(['<', '&', '\n']) {
+            self.push_text(&rest[..x]);
+            let (first, tail) = rest[x..].split_at(1);
+
+            rest = match first {
+                "\n" => {
+                    if self.html {
+                        self.push_text(" ");
+                    } else {
+                        self.line_break();
+                    }
+                    tail
+                }
+                "&" => match parse_entity(&rest[x..]) {
+                    Some((entity, len)) => {
+                        self.push_text(&entity.to_string());
+                        &rest[x + len..]
+                    }
+                    None => {
+                        self.push_text(first);
+                        tail
+                    }
+                },
+                _ => match parse_tag(&rest[x..]) {
+                    Some((tag, len)) => {
+                        self.tag(tag);
+                        &rest[x + len..]
+                    }
+                    None => {
+                        self.push_text(first);
+                        tail
+                    }
+                },
+            };
+        }
+        self.push_text(rest);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let text = text.replace('\r', "");
+        let text = if self.html {
+            collapse_whitespace(&text, self.at_line_start())
+        } else {
+            text
+        };
+        if text.is_empty() {
+            return;
+        }
+
+        let style = self.style();
+        match self.out.spans.last_mut() {
+            Some(Span::Text(last, last_style)) if *last_style == style => last.push_str(&text),
+            _ => self.out.spans.push(Span::Text(text, style)),
+        }
+    }
+
+    /// Последний записанный символ - пробел или перевод строки, либо текста еще нет.
+    fn at_line_start(&self) -> bool {
+        match self.out.spans.last() {
+            Some(Span::Text(x, _)) => x.ends_with(' '),
+            _ => true,
+        }
+    }
+
+    fn line_break(&mut self) {
+        if self.html {
+            if let Some(Span::Text(x, _)) = self.out.spans.last_mut() {
+                let len = x.trim_end_matches(' ').len();
+                x.truncate(len);
+            }
+        }
+        self.out.spans.push(Span::LineBreak);
+    }
+
+    /// Перевод строки между блоками, если блок начинается не с новой строки.
+    fn block_break(&mut self) {
+        if let Some(Span::Text(..)) = self.out.spans.last() {
+            self.line_break();
+        }
+    }
+
+    fn tag(&mut self, tag: Tag) {
+        let name = tag.name.as_str();
+        if tag.closing {
+            if matches!(name, "p" | "div") {
+                self.block_break();
+            }
+            if let Some(x) = self.styles.iter().rposition(|(x, _)| x == name) {
+                self.styles.truncate(x);
+            }
+            return;
+        }
+
+        let mut style = self.style();
+        match name {
+            "br" => {
+                self.line_break();
+                return;
+            }
+            "p" | "div" => self.block_break(),
+            "b" | "strong" => style.strong = true,
+            "i" | "em" => style.emphasis = true,
+            "font" => {
+                if let Some(x) = tag.attribute("color") {
+                    style.color = Some(x.to_string());
+                }
+            }
+            "span" => {
+                if let Some(x) = tag.attribute("style").and_then(css_color) {
+                    style.color = Some(x);
+                }
+            }
+            "html" | "body" => {}
+            _ => return,
+        }
+        if !tag.self_closing {
+            self.styles.push((tag.name, style));
+        }
+    }
+
+    fn finish(mut self) -> RichText {
+        while let Some(Span::LineBreak) = self.out.spans.last() {
+            self.out.spans.pop();
+        }
+        if self.html {
+            if let Some(Span::Text(x, _)) = self.out.spans.last_mut() {
+                let len = x.trim_end().len();
+                x.truncate(len);
+            }
+        }
+        self.out
+    }
+}
+
+/// Теги оформления, которые понимает [`RichText::parse`].
+pub(crate) const STYLE_TAGS: &[&str] = &["b", "strong", "i", "em", "font", "span"];
+
+pub(crate) struct Tag {
+    /// Имя тега в нижнем регистре.
+    pub name: String,
+    pub closing: bool,
+    self_closing: bool,
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(x, _)| x == name)
+            .map(|(_, x)| x.as_str())
+    }
+}
+
+/// Разобрать тег в начале `text`. Возвращает тег и его длину.
+///
+/// `None`, если это не тег, например `<` в тексте `a < b`.
+pub(crate) fn parse_tag(text: &str) -> Option<(Tag, usize)> {
+    let end = text.find('>')?;
+    let inner = &text[1..end];
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(x) => (true, x),
+        None => (false, inner),
+    };
+    let (self_closing, inner) = match inner.strip_suffix('/') {
+        Some(x) => (true, x),
+        None => (false, inner),
+    };
+
+    let name_len = inner
+        .find(|x: char| !x.is_ascii_alphanumeric())
+        .unwrap_or(inner.len());
+    let name = &inner[..name_len];
+    if !name.starts_with(|x: char| x.is_ascii_alphabetic()) {
+        return None;
+    }
+    let attributes = &inner[name_len..];
+    if !attributes.is_empty() && !attributes.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let tag = Tag {
+        name: name.to_lowercase(),
+        closing,
+        self_closing,
+        attributes: parse_attributes(attributes),
+    };
+    Some((tag, end + 1))
+}
+
+/// Разобрать атрибуты тега `name="value"`, `name='value'` или `name=value`.
+pub(crate) fn parse_attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let name_len = rest
+            .find(|x: char| x == '=' || x.is_whitespace())
+            .unwrap_or(rest.len());
+        let name = rest[..name_len].to_lowercase();
+        rest = rest[name_len..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(x) => {
+                let x = x.trim_start();
+                let (value, len) = match x.chars().next() {
+                    Some(quote) if quote == '"' || quote == '\'' => match x[1..].find(quote) {
+                        Some(len) => (x[1..=len].to_string(), len + 2),
+                        None => (x[1..].to_string(), x.len()),
+                    },
+                    _ => {
+                        let len = x.find(char::is_whitespace).unwrap_or(x.len());
+                        (x[..len].to_string(), len)
+                    }
+                };
+                rest = &x[len..];
+                value
+            }
+            None => String::new(),
+        };
+        attributes.push((name, decode_entities(&value)));
+        rest = rest.trim_start();
+    }
+    attributes
+}
+
+/// Цвет из CSS свойства `color` в атрибуте `style`.
+fn css_color(style: &str) -> Option<String> {
+    style.split(';').find_map(|x| {
+        let mut parts = x.splitn(2, ':');
+        let name = parts.next()?.trim();
+        let value = parts.next()?.trim();
+        if name.eq_ignore_ascii_case("color") && !value.is_empty() {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Разобрать ссылку на символ в начале `text`. Возвращает символ и длину ссылки.
+fn parse_entity(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';').filter(|x| *x <= 10)?;
+    let name = &text[1..end];
+    let x = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let code = match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                Some(x) => u32::from_str_radix(x, 16).ok()?,
+                None => name.strip_prefix('#')?.parse().ok()?,
+            };
+            std::char::from_u32(code)?
+        }
+    };
+    Some((x, end + 1))
+}
+
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(x) = rest.find('&') {
+        out.push_str(&rest[..x]);
+        rest = &rest[x..];
+        match parse_entity(rest) {
+            Some((x, len)) => {
+                out.push(x);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Схлопнуть пробельные символы в один пробел, как это делает HTML.
+fn collapse_whitespace(text: &str, at_line_start: bool) -> String {
+    let mut out = String::new();
+    let mut space = at_line_start;
+    for x in text.chars() {
+        if x.is_whitespace() && x != '\u{a0}' {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+        } else {
+            out.push(x);
+            space = false;
+        }
+    }
+    out
+}
+
+fn ansi_codes(style: &Style) -> Vec<String> {
+    let mut codes = Vec::new();
+    if style.strong {
+        codes.push("1".to_string());
+    }
+    if style.emphasis {
+        codes.push("3".to_string());
+    }
+    if let Some(x) = style.color.as_deref().and_then(ansi_color) {
+        codes.push(x);
+    }
+    codes
+}
+
+/// Код цвета текста ANSI для цвета CSS.
+fn ansi_color(color: &str) -> Option<String> {
+    let color = color.trim().to_lowercase();
+    if let Some(hex) = color.strip_prefix('#') {
+        let channel = |x: &str| u8::from_str_radix(x, 16).ok();
+        let (r, g, b) = match hex.len() {
+            3 => (
+                channel(&hex[0..1].repeat(2))?,
+                channel(&hex[1..2].repeat(2))?,
+                channel(&hex[2..3].repeat(2))?,
+            ),
+            6 => (
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            ),
+            _ => return None,
+        };
+        return Some(format!("38;2;{};{};{}", r, g, b));
+    }
+
+    let code = match color.as_str() {
+        "black" => 30,
+        "red" | "maroon" => 31,
+        "green" | "lime" => 32,
+        "yellow" | "olive" => 33,
+        "blue" | "navy" => 34,
+        "magenta" | "fuchsia" | "purple" => 35,
+        "cyan" | "aqua" | "teal" => 36,
+        "white" | "silver" => 37,
+        "gray" | "grey" => 90,
+        _ => return None,
+    };
+    Some(code.to_string())
+}
@@ -0,0 +1,534 @@
+use std::fmt;
+
+/// Тип переменной квеста.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VarType {
+    Int,
+    Bool,
+    String,
+}
+
+/// Значение переменной квеста.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    String(String),
+}
+
+/// Переменная, объявленная в квесте: имя, тип и начальное значение.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Variable {
+    pub name: String,
+    pub var_type: VarType,
+    pub initial: Value,
+}
+
+/// Как вариант выбора меняет переменную.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EffectOp {
+    /// `=`
+    Set,
+    /// `+=`, только для `int`
+    Add,
+    /// `-=`, только для `int`
+    Sub,
+}
+
+/// Изменение переменной при выборе варианта, например `gold += 10`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Effect {
+    pub variable: String,
+    pub op: EffectOp,
+    pub value: Value,
+}
+
+/// Ошибки разбора объявлений переменных и изменений переменных.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorScript {
+    /// Ожидалось другое: что ожидалось, строка и столбец, начиная с 1.
+    Syntax(&'static str, usize, usize),
+    /// Переменная не объявлена в квесте.
+    UnknownVariable(String),
+    /// Переменная с таким именем уже объявлена.
+    DuplicateVariable(String),
+    /// Значение другого типа: переменная, ее тип и тип значения.
+    TypeMismatch(String, VarType, VarType),
+    /// Операция не применима к переменной этого типа.
+    InvalidOperator(String, EffectOp, VarType),
+    /// Операция условия не применима к значению этого типа: операция,
+    /// ожидаемый тип и тип значения.
+    OperandType(&'static str, VarType, VarType),
+    /// Сравниваются значения разных типов: операция и типы операндов.
+    CompareMismatch(&'static str, VarType, VarType),
+    /// Условие не логическое: тип выражения.
+    ConditionType(VarType),
+}
+
+impl VarType {
+    /// Имя типа в файле квеста: `int`, `bool`, `string`.
+    pub fn name(self) -> &'static str {
+        match self {
+            VarType::Int => "int",
+            VarType::Bool => "bool",
+            VarType::String => "string",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<VarType> {
+        match name {
+            "int" => Some(VarType::Int),
+            "bool" => Some(VarType::Bool),
+            "string" => Some(VarType::String),
+            _ => None,
+        }
+    }
+
+    /// Значение по умолчанию: `0`, `false`, `""`.
+    pub fn default_value(self) -> Value {
+        match self {
+            VarType::Int => Value::Int(0),
+            VarType::Bool => Value::Bool(false),
+            VarType::String => Value::String(String::new()),
+        }
+    }
+}
+
+impl Value {
+    pub fn var_type(&self) -> VarType {
+        match self {
+            Value::Int(_) => VarType::Int,
+            Value::Bool(_) => VarType::Bool,
+            Value::String(_) => VarType::String,
+        }
+    }
+}
+
+impl EffectOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            EffectOp::Set => "=",
+            EffectOp::Add => "+=",
+            EffectOp::Sub => "-=",
+        }
+    }
+}
+
+impl Effect {
+    /// Новое значение переменной после изменения.
+    ///
+    /// Типы проверяются при разборе, поэтому для `+=` и `-=` значения - числа.
+    /// Переполнение ограничивается границами `i64`.
+    pub fn apply(&self, current: &Value) -> Value {
+        match (self.op, current, &self.value) {
+            (EffectOp::Add, Value::Int(a), Value::Int(b)) => Value::Int(a.saturating_add(*b)),
+            (EffectOp::Sub, Value::Int(a), Value::Int(b)) => Value::Int(a.saturating_sub(*b)),
+            _ => self.value.clone(),
+        }
+    }
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Display for Value {
+    /// Значение в синтаксисе квеста: `10`, `true`, `"Петр"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::String(x) => write!(f, "\"{}\"", x.replace('\\', "\\\\").replace('"', "\\\"")),
+        }
+    }
+}
+
+impl fmt::Display for Variable {
+    /// Объявление, которое [`parse_variables`] читает обратно: `gold: int = 10`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} = {}", self.name, self.var_type, self.initial)
+    }
+}
+
+impl fmt::Display for Effect {
+    /// Изменение, которое [`parse_effects`] читает обратно: `gold += 10`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.variable, self.op.symbol(), self.value)
+    }
+}
+
+impl fmt::Display for ErrorScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorScript::Syntax(expected, line, column) => write!(
+                f,
+                "ожидается {} (строка {}, столбец {})",
+                expected, line, column
+            ),
+            ErrorScript::UnknownVariable(name) => {
+                write!(f, "переменная `{}` не объявлена", name)
+            }
+            ErrorScript::DuplicateVariable(name) => {
+                write!(f, "переменная `{}` уже объявлена", name)
+            }
+            ErrorScript::TypeMismatch(name, expected, found) => write!(
+                f,
+                "переменной `{}` типа {} нельзя присвоить значение типа {}",
+                name, expected, found
+            ),
+            ErrorScript::InvalidOperator(name, op, var_type) => write!(
+                f,
+                "операция `{}` не применима к переменной `{}` типа {}",
+                op.symbol(),
+                name,
+                var_type
+            ),
+            ErrorScript::OperandType(op, expected, found) => write!(
+                f,
+                "операция `{}` применима к значениям типа {}, а не {}",
+                op, expected, found
+            ),
+            ErrorScript::CompareMismatch(op, left, right) => write!(
+                f,
+                "нельзя сравнить значения типов {} и {} операцией `{}`",
+                left, right, op
+            ),
+            ErrorScript::ConditionType(found) => {
+                write!(f, "условие должно быть типа bool, а не {}", found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorScript {}
+
+/// Разобрать объявления переменных квеста.
+///
+/// Объявления разделяются `;` или переводом строки: `gold: int = 10`,
+/// `met_guard: bool` (значение по умолчанию) или `name = "Петр"` (тип по значению).
+pub fn parse_variables(text: &str) -> Result<Vec<Variable>, ErrorScript> {
+    let mut lexer = Lexer::new(text)?;
+    let mut variables: Vec<Variable> = Vec::new();
+
+    while !lexer.at_end() {
+        if lexer.eat_separator() {
+            continue;
+        }
+        let name = lexer.ident()?;
+        let declared = if lexer.eat(":") {
+            let (line, column) = lexer.position();
+            let type_name = lexer.ident()?;
+            Some(VarType::parse(&type_name).ok_or(ErrorScript::Syntax(
+                "тип int, bool или string",
+                line,
+                column,
+            ))?)
+        } else {
+            None
+        };
+        let initial = match declared {
+            Some(var_type) if !lexer.eat("=") => var_type.default_value(),
+            _ => {
+                if declared.is_none() {
+                    lexer.expect("=", "`:` или `=`")?;
+                }
+                lexer.value()?
+            }
+        };
+        let var_type = declared.unwrap_or_else(|| initial.var_type());
+        if initial.var_type() != var_type {
+            return Err(ErrorScript::TypeMismatch(
+                name,
+                var_type,
+                initial.var_type(),
+            ));
+        }
+        if variables.iter().any(|x| x.name == name) {
+            return Err(ErrorScript::DuplicateVariable(name));
+        }
+        variables.push(Variable {
+            name,
+            var_type,
+            initial,
+        });
+        lexer.end_statement()?;
+    }
+
+    Ok(variables)
+}
+
+/// Разобрать изменения переменных варианта выбора и проверить их типы
+/// по объявлениям `variables`.
+///
+/// Изменения разделяются `;` или переводом строки: `gold += 10`, `met_guard = true`.
+pub fn parse_effects(text: &str, variables: &[Variable]) -> Result<Vec<Effect>, ErrorScript> {
+    let mut lexer = Lexer::new(text)?;
+    let mut effects = Vec::new();
+
+    while !lexer.at_end() {
+        if lexer.eat_separator() {
+            continue;
+        }
+        let name = lexer.ident()?;
+        let op = if lexer.eat("+=") {
+            EffectOp::Add
+        } else if lexer.eat("-=") {
+            EffectOp::Sub
+        } else {
+            lexer.expect("=", "`=`, `+=` или `-=`")?;
+            EffectOp::Set
+        };
+        let value = lexer.value()?;
+
+        let var_type = variables
+            .iter()
+            .find(|x| x.name == name)
+            .map(|x| x.var_type)
+            .ok_or_else(|| ErrorScript::UnknownVariable(name.clone()))?;
+        if op != EffectOp::Set && var_type != VarType::Int {
+            return Err(ErrorScript::InvalidOperator(name, op, var_type));
+        }
+        if value.var_type() != var_type {
+            return Err(ErrorScript::TypeMismatch(name, var_type, value.var_type()));
+        }
+        effects.push(Effect {
+            variable: name,
+            op,
+            value,
+        });
+        lexer.end_statement()?;
+    }
+
+    Ok(effects)
+}
+
+/// Разделить описание варианта выбора на заметки автора и строки изменений
+/// переменных, которые начинаются с `~` (`~ gold += 10`).
+///
+/// Возвращает описание без строк изменений (`None`, если ничего не осталось)
+/// и текст изменений для [`parse_effects`].
+pub fn split_description_effects(description: &str) -> (Option<String>, String) {
+    let mut notes = Vec::new();
+    let mut effects = Vec::new();
+    for line in description.lines() {
+        match line.trim_start().strip_prefix('~') {
+            Some(effect) => effects.push(effect.trim()),
+            None => notes.push(line),
+        }
+    }
+
+    let notes = notes.join("\n").trim().to_string();
+    (Some(notes).filter(|x| !x.is_empty()), effects.join("\n"))
+}
+
+/// Лексема скрипта квеста.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    /// Оператор или знак препинания.
+    Punct(&'static str),
+    /// Конец оператора: `;`.
+    Separator,
+    /// Перевод строки, тоже заканчивает оператор.
+    LineBreak,
+}
+
+/// Операторы в порядке проверки: сначала длинные.
+const PUNCTS: &[&str] = &["+=", "-=", "=", ":"];
+
+/// Разбор текста скрипта на лексемы с их положением (строка и столбец, начиная с 1).
+pub(crate) struct Lexer {
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+    end: (usize, usize),
+}
+
+impl Lexer {
+    pub(crate) fn new(text: &str) -> Result<Lexer, ErrorScript> {
+        Lexer::with_puncts(text, PUNCTS)
+    }
+
+    pub(crate) fn with_puncts(text: &str, puncts: &[&'static str]) -> Result<Lexer, ErrorScript> {
+        let mut tokens: Vec<(Token, usize, usize)> = Vec::new();
+        let chars: Vec<char> = text.chars().collect();
+        let (mut line, mut line_start) = (1, 0);
+        let mut i = 0;
+
+        while i < chars.len() {
+            let x = chars[i];
+            let column = i - line_start + 1;
+            let start = i;
+            let token = if x == '\n' {
+                i += 1;
+                Token::LineBreak
+            } else if x == ';' {
+                i += 1;
+                Token::Separator
+            } else if x.is_whitespace() {
+                i += 1;
+                continue;
+            } else if x.is_alphabetic() || x == '_' {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                Token::Ident(chars[start..i].iter().collect())
+            } else if x.is_ascii_digit()
+                || (x == '-' && chars.get(i + 1).is_some_and(|x| x.is_ascii_digit()))
+                    && !matches!(
+                        tokens.last(),
+                        Some((Token::Ident(_), ..))
+                            | Some((Token::Int(_), ..))
+                            | Some((Token::Punct(")"), ..))
+                    )
+            {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let digits: String = chars[start..i].iter().collect();
+                Token::Int(
+                    digits
+                        .parse()
+                        .map_err(|_| ErrorScript::Syntax("число", line, column))?,
+                )
+            } else if x == '"' {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(x) => {
+                            value.push(*x);
+                            i += 1;
+                        }
+                        None => return Err(ErrorScript::Syntax("`\"`", line, i - line_start + 1)),
+                    }
+                }
+                i += 1;
+                Token::Str(value)
+            } else {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let punct = puncts
+                    .iter()
+                    .find(|x| rest.starts_with(**x))
+                    .ok_or(ErrorScript::Syntax("оператор", line, column))?;
+                i += punct.chars().count();
+                Token::Punct(punct)
+            };
+            tokens.push((token, line, column));
+            if x == '\n' {
+                line += 1;
+                line_start = i;
+            }
+        }
+
+        Ok(Lexer {
+            tokens,
+            pos: 0,
+            end: (line, chars.len() - line_start + 1),
+        })
+    }
+
+    /// Выражение занимает весь текст, поэтому переводы строк в нем - просто пробелы.
+    pub(crate) fn without_line_breaks(mut self) -> Lexer {
+        self.tokens.retain(|(token, ..)| *token != Token::LineBreak);
+        self
+    }
+
+    pub(crate) fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    pub(crate) fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|x| &x.0)
+    }
+
+    /// Положение текущей лексемы, а в конце текста - положение конца.
+    pub(crate) fn position(&self) -> (usize, usize) {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end, |(_, line, column)| (*line, *column))
+    }
+
+    pub(crate) fn error(&self, expected: &'static str) -> ErrorScript {
+        let (line, column) = self.position();
+        ErrorScript::Syntax(expected, line, column)
+    }
+
+    pub(crate) fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Punct(x)) if *x == punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    pub(crate) fn expect(
+        &mut self,
+        punct: &str,
+        expected: &'static str,
+    ) -> Result<(), ErrorScript> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    fn eat_separator(&mut self) -> bool {
+        let found = matches!(self.peek(), Some(Token::Separator) | Some(Token::LineBreak));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn end_statement(&mut self) -> Result<(), ErrorScript> {
+        if self.at_end() || self.eat_separator() {
+            Ok(())
+        } else {
+            Err(self.error("`;` или перевод строки"))
+        }
+    }
+
+    pub(crate) fn ident(&mut self) -> Result<String, ErrorScript> {
+        match self.peek() {
+            Some(Token::Ident(name)) if !is_keyword(name) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("имя переменной")),
+        }
+    }
+
+    /// Значение: число, `true`, `false` или строка в кавычках.
+    pub(crate) fn value(&mut self) -> Result<Value, ErrorScript> {
+        let value = match self.peek() {
+            Some(Token::Int(x)) => Value::Int(*x),
+            Some(Token::Str(x)) => Value::String(x.clone()),
+            Some(Token::Ident(x)) if x == "true" => Value::Bool(true),
+            Some(Token::Ident(x)) if x == "false" => Value::Bool(false),
+            _ => return Err(self.error("значение")),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+}
+
+fn is_keyword(name: &str) -> bool {
+    matches!(name, "true" | "false")
+}
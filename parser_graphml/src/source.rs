@@ -0,0 +1,322 @@
+use petgraph::graph::{Graph, NodeIndex};
+use roxmltree::{Document, Node};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::dialect::{Dialect, LabelKeys};
+use crate::keys::KeyTable;
+use crate::script::Variable;
+use crate::types::{Edge, Vertex};
+use crate::writer::escape;
+
+/// Исходный GraphML документ, из которого прочитан квест.
+///
+/// Хранит текст документа, его ключи и положения сцен, вариантов выбора
+/// и их `<data>` в нем, чтобы [`write_graphml`] мог записать квест без потерь:
+/// геометрия, стили, порты и неизвестные `<data>` остаются байт в байт,
+/// меняются только данные, которые изменились в квесте.
+///
+/// [`write_graphml`]: crate::writer::write_graphml
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphMLSource {
+    pub(crate) text: String,
+    pub(crate) dialect: Dialect,
+    pub(crate) node_label_key: String,
+    pub(crate) edge_label_key: Option<String>,
+    /// Ключи документа.
+    pub(crate) keys: KeyTable,
+    /// Позиция для объявлений новых ключей: сразу после последнего `<key>`.
+    pub(crate) keys_end: usize,
+    /// Отступ перед объявлениями ключей.
+    pub(crate) keys_indent: String,
+    /// Элементы `<data>` корневого графа по ключу.
+    pub(crate) graph_data: HashMap<String, Range<usize>>,
+    /// Позиция для новых `<data>` графа: перед его первым элементом.
+    pub(crate) graph_data_start: usize,
+    /// Стартовая сцена при чтении и отмечена ли она явно.
+    pub(crate) start: Option<String>,
+    pub(crate) start_marked: bool,
+    /// Язык и переменные квеста при чтении.
+    pub(crate) locale: Option<String>,
+    pub(crate) variables: Vec<Variable>,
+    /// Элементы `<node>` по идентификатору сцены.
+    pub(crate) nodes: HashMap<String, RawElement<Vertex>>,
+    /// Элементы `<edge>` по идентификатору ребра.
+    pub(crate) edges: HashMap<String, RawElement<Edge>>,
+    /// Позиция для новых сцен: сразу после последней сцены графа.
+    pub(crate) nodes_end: usize,
+    /// Позиция для новых ребер: сразу после последнего ребра графа.
+    pub(crate) edges_end: usize,
+    /// Отступ перед элементами графа.
+    pub(crate) indent: String,
+}
+
+impl GraphMLSource {
+    pub(crate) fn new(
+        text: &str,
+        doc: &Document<'_>,
+        dialect: Dialect,
+        keys: &KeyTable,
+        label_keys: LabelKeys<'_>,
+    ) -> Self {
+        let graph = doc
+            .root_element()
+            .children()
+            .find(|x| x.tag_name().name() == "graph");
+        let children = || {
+            graph
+                .iter()
+                .flat_map(|x| x.children())
+                .filter(|x| x.is_element())
+        };
+        let last_end = |tag: &str| {
+            children()
+                .filter(|x| x.tag_name().name() == tag)
+                .map(|x| x.range().end)
+                .max()
+        };
+
+        let graph_end = graph.map_or(text.len(), |x| Splice::content_end(text, &x));
+        let nodes_end = last_end("node").unwrap_or(graph_end);
+        let edges_end = last_end("edge").unwrap_or(nodes_end);
+        let last_key = doc
+            .root_element()
+            .children()
+            .rfind(|x| x.is_element() && x.tag_name().name() == "key");
+        let indent = children()
+            .find(|x| matches!(x.tag_name().name(), "node" | "edge"))
+            .and_then(|x| x.prev_sibling())
+            .filter(|x| x.is_text())
+            .and_then(|x| x.text())
+            .filter(|x| x.trim().is_empty())
+            .unwrap_or("\n")
+            .to_string();
+
+        GraphMLSource {
+            text: text.to_string(),
+            dialect,
+            node_label_key: label_keys.node.id.clone(),
+            edge_label_key: label_keys.edge.map(|x| x.id.clone()),
+            keys: keys.clone(),
+            keys_end: last_key.map_or(graph.map_or(0, |x| x.range().start), |x| x.range().end),
+            keys_indent: last_key.map(|x| indent_before(&x)).unwrap_or_default(),
+            graph_data: graph.map(|x| data_ranges(&x)).unwrap_or_default(),
+            graph_data_start: children().next().map_or(graph_end, |x| x.range().start),
+            start: None,
+            start_marked: false,
+            locale: None,
+            variables: Vec::new(),
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+            nodes_end,
+            edges_end,
+            indent,
+        }
+    }
+
+    /// Текст исходного документа.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Диалект исходного документа.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Заменить в концах ребер главы на их начальные сцены, как это сделано в графе.
+    ///
+    /// `entries` - идентификаторы начальных сцен по идентификаторам глав.
+    pub(crate) fn resolve_chapter_targets(&mut self, entries: &HashMap<&str, &str>) {
+        for (_, target) in self.edges.values_mut().filter_map(|x| x.endpoints.as_mut()) {
+            if let Some(entry) = entries.get(target.as_str()) {
+                *target = entry.to_string();
+            }
+        }
+    }
+
+    /// Запомнить прочитанный квест, чтобы при записи найти изменения в нем.
+    pub(crate) fn remember_quest(
+        &mut self,
+        graph: &Graph<Vertex, Edge>,
+        start: Option<NodeIndex>,
+        locale: &Option<String>,
+        variables: &[Variable],
+        start_marked: bool,
+    ) {
+        for x in graph.raw_nodes() {
+            if let Some(raw) = self.nodes.get_mut(&x.weight.id) {
+                raw.original = x.weight.clone();
+            }
+        }
+        for x in graph.raw_edges() {
+            if let Some(raw) = self.edges.get_mut(&x.weight.id) {
+                raw.original = x.weight.clone();
+            }
+        }
+        self.start = start.map(|x| graph[x].id.clone());
+        self.start_marked = start_marked;
+        self.locale = locale.clone();
+        self.variables = variables.to_vec();
+    }
+
+    /// Диапазон элемента вместе с отступом перед ним, чтобы удалить его без следа.
+    pub(crate) fn range_with_indent(&self, range: &Range<usize>) -> Range<usize> {
+        let indent = self.text[..range.start].len()
+            - self.text[..range.start]
+                .trim_end_matches(|x: char| x.is_whitespace())
+                .len();
+        range.start - indent..range.end
+    }
+}
+
+/// Элемент `<node>` или `<edge>` исходного документа.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct RawElement<T> {
+    /// Положение элемента в документе.
+    pub range: Range<usize>,
+    /// Сцена или вариант выбора при чтении.
+    pub original: T,
+    /// Куда записать новый текст подписи.
+    pub label: Option<Splice>,
+    /// Элементы `<data>` по ключу.
+    pub data: HashMap<String, Range<usize>>,
+    /// Куда дописать новые `<data>` и отступ перед каждым из них.
+    pub append: Splice,
+    pub indent: String,
+    /// Идентификаторы начала и конца ребра при чтении.
+    pub endpoints: Option<(String, String)>,
+}
+
+impl<T: Default> RawElement<T> {
+    pub(crate) fn new(text: &str, node: &Node<'_, '_>, label: Option<Splice>) -> Self {
+        let last = node.children().rfind(|x| x.is_element());
+        let (append, indent) = match last {
+            Some(x) => (Splice::insert(x.range().end), indent_before(&x)),
+            None => (Splice::content_start(text, node), String::new()),
+        };
+        RawElement {
+            range: node.range(),
+            original: T::default(),
+            label,
+            data: data_ranges(node),
+            append,
+            indent,
+            endpoints: None,
+        }
+    }
+}
+
+/// Положения дочерних `<data>` элемента по ключу. Как и при чтении,
+/// из нескольких `<data>` с одним ключом берется первый.
+fn data_ranges(node: &Node<'_, '_>) -> HashMap<String, Range<usize>> {
+    let mut ranges = HashMap::new();
+    for x in node.children().filter(|x| x.tag_name().name() == "data") {
+        if let Some(key) = x.attribute("key") {
+            ranges.entry(key.to_string()).or_insert_with(|| x.range());
+        }
+    }
+    ranges
+}
+
+/// Пробелы перед элементом, если он стоит на отдельной строке.
+fn indent_before(node: &Node<'_, '_>) -> String {
+    node.prev_sibling()
+        .filter(|x| x.is_text())
+        .and_then(|x| x.text())
+        .filter(|x| x.trim().is_empty())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Замена участка документа: диапазон заменяется на `prefix`, текст и `suffix`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Splice {
+    pub range: Range<usize>,
+    pub prefix: String,
+    pub suffix: String,
+    /// Исходный текст был в `<![CDATA[...]]>`, новый пишется так же.
+    pub cdata: bool,
+}
+
+impl Splice {
+    /// Замена текста элемента, а если текста нет, то вставка в начало содержимого.
+    pub(crate) fn text_of(text: &str, node: &Node<'_, '_>) -> Splice {
+        match node.first_child().filter(|x| x.is_text()) {
+            Some(x) => Splice {
+                range: x.range(),
+                prefix: String::new(),
+                suffix: String::new(),
+                cdata: text[x.range()].starts_with("<![CDATA["),
+            },
+            None => Splice::content_start(text, node),
+        }
+    }
+
+    /// Вставка в начало содержимого элемента.
+    ///
+    /// Пустой элемент `<a/>` раскрывается в `<a>...</a>`.
+    pub(crate) fn content_start(text: &str, node: &Node<'_, '_>) -> Splice {
+        let range = node.range();
+        if let Some(child) = node.first_child() {
+            let start = child.range().start;
+            return Splice::insert(start);
+        }
+
+        let element = &text[range.clone()];
+        if element.ends_with("/>") {
+            let name = element[1..]
+                .split(|x: char| x.is_whitespace() || x == '/')
+                .next()
+                .unwrap_or_default();
+            Splice {
+                range: range.end - 2..range.end,
+                prefix: ">".to_string(),
+                suffix: format!("</{}>", name),
+                cdata: false,
+            }
+        } else {
+            Splice::insert(Splice::content_end(text, node))
+        }
+    }
+
+    /// Позиция закрывающего тега элемента.
+    fn content_end(text: &str, node: &Node<'_, '_>) -> usize {
+        let range = node.range();
+        range.start + text[range.clone()].rfind("</").unwrap_or(range.len())
+    }
+
+    pub(crate) fn insert(position: usize) -> Splice {
+        Splice {
+            range: position..position,
+            prefix: String::new(),
+            suffix: String::new(),
+            cdata: false,
+        }
+    }
+
+    /// Обернуть вставляемое содержимое разметкой.
+    pub(crate) fn wrap(mut self, open: &str, close: &str) -> Splice {
+        self.prefix.push_str(open);
+        self.suffix.insert_str(0, close);
+        self
+    }
+
+    /// Разметка, которой заменяется диапазон, чтобы записать готовую разметку `markup`.
+    pub(crate) fn apply_markup(&self, markup: &str) -> String {
+        format!("{}{}{}", self.prefix, markup, self.suffix)
+    }
+
+    /// Разметка, которой заменяется диапазон, чтобы записать текст `content`.
+    pub(crate) fn apply(&self, content: &str) -> String {
+        let content = if self.cdata {
+            format!("<![CDATA[{}]]>", content.replace("]]>", "]]]]><![CDATA[>"))
+        } else {
+            escape(content)
+        };
+        format!("{}{}{}", self.prefix, content, self.suffix)
+    }
+}
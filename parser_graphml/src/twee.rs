@@ -0,0 +1,531 @@
+use petgraph::graph::Graph;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::import::{ImportWarning, Imported};
+use crate::rich_text::{decode_entities, parse_attributes};
+use crate::types::*;
+
+/// Ошибки импорта истории Twine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorTwee {
+    /// В истории нет ни одного пассажа со сценой.
+    NoPassages,
+    /// Пассаж с таким именем уже есть: имя и номер строки повтора.
+    DuplicatePassage(String, usize),
+    /// Не найден стартовый пассаж, указанный в `StoryData` или `startnode`.
+    NotFoundStartPassage(String),
+    /// В HTML нет элемента `<tw-storydata>`: это не архив Twine 2.
+    NotFoundStoryData,
+}
+
+impl fmt::Display for ErrorTwee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorTwee::NoPassages => write!(f, "в истории нет пассажей"),
+            ErrorTwee::DuplicatePassage(name, line) => {
+                write!(f, "пассаж `{}` уже есть (строка {})", name, line)
+            }
+            ErrorTwee::NotFoundStartPassage(name) => {
+                write!(f, "не найден стартовый пассаж `{}`", name)
+            }
+            ErrorTwee::NotFoundStoryData => {
+                write!(f, "не найден элемент `<tw-storydata>` архива Twine 2")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorTwee {}
+
+/// Импортировать историю из Twee 3.
+///
+/// Пассажи (`:: Имя [теги] {"position":"100,200"}`) становятся сценами,
+/// а ссылки `[[текст->Пассаж]]`, `[[Пассаж<-текст]]`, `[[текст|Пассаж]]`
+/// и `[[Пассаж]]` - вариантами выбора. Текст пассажа без ссылок делится
+/// на абзацы по пустым строкам. Стартовый пассаж берется из `StoryData`,
+/// иначе это `Start` или первый пассаж.
+///
+/// Макросы Harlowe (`(set: ...)`) и SugarCube (`<<set ...>>`), переменные,
+/// скрипты и служебные пассажи в граф не переносятся: они вырезаются
+/// из текста и попадают в [`Imported::warnings`].
+///
+/// # Errors
+///
+/// Нет пассажей, повторяются имена пассажей или не найден стартовый пассаж.
+pub fn read_twee(text: &str) -> Result<Imported, ErrorTwee> {
+    let mut story = Story::default();
+    let mut current: Option<Passage> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        match line.strip_prefix("::") {
+            Some(header) => {
+                story.passages.extend(current.take());
+                current = Some(parse_twee_header(header, i + 1));
+            }
+            None => {
+                if let Some(passage) = &mut current {
+                    passage.text.push_str(line);
+                    passage.text.push('\n');
+                }
+            }
+        }
+    }
+    story.passages.extend(current);
+
+    if let Some(data) = story.passages.iter().find(|x| x.name == "StoryData") {
+        match serde_json::from_str::<serde_json::Value>(&data.text) {
+            Ok(value) => {
+                story.start = value
+                    .get("start")
+                    .and_then(|x| x.as_str())
+                    .map(String::from);
+            }
+            Err(e) => story.warnings.push(ImportWarning {
+                element: data.name.clone(),
+                line: Some(data.line),
+                message: format!("некорректный JSON в `StoryData`: {}", e),
+            }),
+        }
+    }
+
+    story.into_imported()
+}
+
+/// Импортировать историю из архива или опубликованной истории Twine 2 (HTML).
+///
+/// Читает `<tw-storydata>` и его `<tw-passagedata>` так же, как [`read_twee`]
+/// читает пассажи Twee. Стартовый пассаж задается атрибутом `startnode`.
+///
+/// # Errors
+///
+/// Кроме ошибок [`read_twee`] - нет элемента `<tw-storydata>`.
+pub fn read_twine_html(html: &str) -> Result<Imported, ErrorTwee> {
+    const STORY_DATA: &str = "<tw-storydata";
+    const PASSAGE_DATA: &str = "<tw-passagedata";
+    const PASSAGE_DATA_END: &str = "</tw-passagedata>";
+
+    let story_start = html.find(STORY_DATA).ok_or(ErrorTwee::NotFoundStoryData)?;
+    let (story_attributes, _) = parse_element_start(&html[story_start..]);
+    let start_pid = find_attribute(&story_attributes, "startnode");
+
+    let mut story = Story::default();
+    let mut rest_start = story_start;
+    while let Some(x) = html[rest_start..].find(PASSAGE_DATA) {
+        let start = rest_start + x;
+        let (attributes, len) = parse_element_start(&html[start..]);
+        let content_start = start + len;
+        let content_end = html[content_start..]
+            .find(PASSAGE_DATA_END)
+            .map_or(html.len(), |x| content_start + x);
+        rest_start = content_end;
+
+        let attribute = |name| find_attribute(&attributes, name).unwrap_or_default();
+        let name = attribute("name");
+        if start_pid.is_some() && find_attribute(&attributes, "pid") == start_pid {
+            story.start = Some(name.clone());
+        }
+        story.passages.push(Passage {
+            name,
+            tags: attribute("tags")
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            position: parse_rect(&attribute("position"), &attribute("size")),
+            text: decode_entities(&html[content_start..content_end]),
+            line: html[..start].matches('\n').count() + 1,
+        });
+    }
+
+    story.into_imported()
+}
+
+/// Пассаж истории.
+#[derive(Debug)]
+struct Passage {
+    name: String,
+    tags: Vec<String>,
+    position: Option<Rect>,
+    text: String,
+    /// Строка заголовка пассажа.
+    line: usize,
+}
+
+#[derive(Debug, Default)]
+struct Story {
+    passages: Vec<Passage>,
+    /// Имя стартового пассажа.
+    start: Option<String>,
+    warnings: Vec<ImportWarning>,
+}
+
+/// Служебные пассажи Twine и его форматов, которые не являются сценами.
+const SPECIAL_PASSAGES: [&str; 14] = [
+    "StoryTitle",
+    "StoryData",
+    "StoryInit",
+    "StoryMenu",
+    "StoryCaption",
+    "StoryBanner",
+    "StorySubtitle",
+    "StoryAuthor",
+    "StoryShare",
+    "StoryInterface",
+    "PassageReady",
+    "PassageDone",
+    "PassageHeader",
+    "PassageFooter",
+];
+
+/// Теги пассажей со скриптами и стилями.
+const SPECIAL_TAGS: [&str; 5] = ["script", "stylesheet", "widget", "header", "footer"];
+
+/// Вариант выбора до того, как найден пассаж, в который он ведет.
+struct Link {
+    text: String,
+    target: String,
+    line: usize,
+}
+
+impl Story {
+    fn into_imported(mut self) -> Result<Imported, ErrorTwee> {
+        let mut graph = Graph::new();
+        let mut indexes = HashMap::new();
+        let mut links = Vec::new();
+
+        for passage in &self.passages {
+            let special = SPECIAL_PASSAGES.contains(&passage.name.as_str())
+                || passage
+                    .tags
+                    .iter()
+                    .any(|x| SPECIAL_TAGS.contains(&x.as_str()));
+            if special {
+                if !matches!(passage.name.as_str(), "StoryTitle" | "StoryData")
+                    && !passage.text.trim().is_empty()
+                {
+                    self.warnings.push(ImportWarning {
+                        element: passage.name.clone(),
+                        line: Some(passage.line),
+                        message: "служебный пассаж пропущен: скрипты, стили и \
+                                  служебные пассажи не поддерживаются"
+                            .to_string(),
+                    });
+                }
+                continue;
+            }
+            if indexes.contains_key(&passage.name) {
+                return Err(ErrorTwee::DuplicatePassage(
+                    passage.name.clone(),
+                    passage.line,
+                ));
+            }
+
+            let body = parse_body(passage, &mut self.warnings);
+            let mut blocks = body.blocks.into_iter();
+            let ix = graph.add_node(Vertex {
+                id: passage.name.clone(),
+                text: blocks.next().unwrap_or_default(),
+                paragraphs: blocks.collect(),
+                ending: Ending::from_tags(&passage.tags),
+                tags: passage.tags.clone(),
+                position: passage.position,
+                ..Vertex::default()
+            });
+            indexes.insert(passage.name.clone(), ix);
+            links.push((ix, body.links));
+        }
+
+        for (source, links) in links {
+            for (i, link) in links.into_iter().enumerate() {
+                let target = match indexes.get(&link.target) {
+                    Some(x) => *x,
+                    None => {
+                        self.warnings.push(ImportWarning {
+                            element: graph[source].id.clone(),
+                            line: Some(link.line),
+                            message: format!(
+                                "пассаж `{}` не найден, переход `{}` пропущен",
+                                link.target, link.text
+                            ),
+                        });
+                        continue;
+                    }
+                };
+                let id = format!("{}#{}", graph[source].id, i + 1);
+                graph.add_edge(
+                    source,
+                    target,
+                    Edge {
+                        id,
+                        text: link.text,
+                        ..Edge::default()
+                    },
+                );
+            }
+        }
+
+        let start = match &self.start {
+            Some(name) => *indexes
+                .get(name)
+                .ok_or_else(|| ErrorTwee::NotFoundStartPassage(name.clone()))?,
+            None => match indexes.get("Start") {
+                Some(x) => *x,
+                None => graph.node_indices().next().ok_or(ErrorTwee::NoPassages)?,
+            },
+        };
+
+        Ok(Imported {
+            quest: Quest {
+                graph,
+                start,
+                chapters: Vec::new(),
+                locale: None,
+                variables: Vec::new(),
+                source: None,
+            },
+            warnings: self.warnings,
+        })
+    }
+}
+
+/// Разобрать заголовок пассажа Twee после `::`: `Имя [теги] {метаданные}`.
+fn parse_twee_header(header: &str, line: usize) -> Passage {
+    let header = header.trim();
+    let mut name = String::new();
+    let mut rest = "";
+    let mut chars = header.char_indices();
+    while let Some((i, x)) = chars.next() {
+        match x {
+            '\\' => name.extend(chars.next().map(|(_, x)| x)),
+            '[' | '{' => {
+                rest = &header[i..];
+                break;
+            }
+            _ => name.push(x),
+        }
+    }
+
+    let mut tags = Vec::new();
+    if let Some(x) = rest.strip_prefix('[') {
+        let end = x.find(']').unwrap_or(x.len());
+        tags = x[..end].split_whitespace().map(String::from).collect();
+        rest = x[end..].trim_start_matches(']').trim_start();
+    }
+
+    let metadata: Option<serde_json::Value> = serde_json::from_str(rest).ok();
+    let attribute = |name| {
+        metadata
+            .as_ref()
+            .and_then(|x| x.get(name))
+            .and_then(|x| x.as_str())
+            .unwrap_or_default()
+    };
+
+    Passage {
+        name: name.trim().to_string(),
+        tags,
+        position: parse_rect(attribute("position"), attribute("size")),
+        text: String::new(),
+        line,
+    }
+}
+
+/// Положение пассажа из `position` (`"x,y"`) и `size` (`"w,h"`, по умолчанию 100x100).
+fn parse_rect(position: &str, size: &str) -> Option<Rect> {
+    let pair = |text: &str| {
+        let mut parts = text.split(',').map(|x| x.trim().parse::<f64>());
+        match (parts.next(), parts.next()) {
+            (Some(Ok(a)), Some(Ok(b))) => Some((a, b)),
+            _ => None,
+        }
+    };
+    let (x, y) = pair(position)?;
+    let (width, height) = pair(size).unwrap_or((100.0, 100.0));
+    Some(Rect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Атрибуты открывающего тега в начале `text` и длина тега.
+fn parse_element_start(text: &str) -> (Vec<(String, String)>, usize) {
+    let end = text.find('>').map_or(text.len(), |x| x + 1);
+    let inner = text[1..end].trim_end_matches('>').trim_end_matches('/');
+    let attributes = inner
+        .find(char::is_whitespace)
+        .map_or_else(Vec::new, |x| parse_attributes(&inner[x..]));
+    (attributes, end)
+}
+
+fn find_attribute(attributes: &[(String, String)], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|(x, _)| x == name)
+        .map(|(_, x)| x.clone())
+}
+
+/// Текст пассажа без ссылок и макросов.
+struct Body {
+    blocks: Vec<String>,
+    links: Vec<Link>,
+}
+
+/// Разобрать текст пассажа: вырезать ссылки и макросы, разделить на абзацы.
+fn parse_body(passage: &Passage, warnings: &mut Vec<ImportWarning>) -> Body {
+    let text = &passage.text;
+    let line_of = |offset: usize| passage.line + 1 + text[..offset].matches('\n').count();
+    let mut warn = |offset: usize, message: String| {
+        warnings.push(ImportWarning {
+            element: passage.name.clone(),
+            line: Some(line_of(offset)),
+            message,
+        })
+    };
+
+    let mut out = String::new();
+    let mut links = Vec::new();
+    let mut reported = HashSet::new();
+    // Открытые хуки Harlowe `[...]` после вырезанных макросов.
+    let mut open_hooks = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+        if let Some(inner) = rest.strip_prefix("[[") {
+            let end = inner.find("]]").map_or(inner.len(), |x| x);
+            let mut link = &inner[..end];
+            if let Some(x) = link.find("][") {
+                warn(i, "сеттер в ссылке не поддерживается".to_string());
+                link = &link[..x];
+            }
+            let (text, target) = parse_link(link);
+            links.push(Link {
+                text,
+                target,
+                line: line_of(i),
+            });
+            i += 2 + (end + 2).min(inner.len());
+        } else if let Some(len) = sugarcube_macro(rest) {
+            let name = macro_name(&rest[2..]);
+            if !name.starts_with('/') && reported.insert(format!("<<{}>>", name)) {
+                warn(
+                    i,
+                    format!("макрос SugarCube `<<{}>>` не поддерживается", name),
+                );
+            }
+            i += len;
+        } else if let Some(len) = harlowe_macro(rest) {
+            let name = macro_name(&rest[1..]);
+            if reported.insert(format!("({}:)", name)) {
+                warn(i, format!("макрос Harlowe `({}:)` не поддерживается", name));
+            }
+            i += len;
+            if text[i..].starts_with('[') && !text[i..].starts_with("[[") {
+                open_hooks += 1;
+                i += 1;
+            }
+        } else if rest.starts_with(']') && !rest.starts_with("]]") && open_hooks > 0 {
+            open_hooks -= 1;
+            i += 1;
+        } else {
+            let x = rest.chars().next().unwrap_or_default();
+            if (x == '$' || x == '_') && is_variable(&rest[1..], x == '$') {
+                let name = macro_name(&rest[1..]);
+                if reported.insert(format!("{}{}", x, name)) {
+                    warn(i, format!("переменная `{}{}` не поддерживается", x, name));
+                }
+            }
+            out.push(x);
+            i += x.len_utf8();
+        }
+    }
+
+    let blocks = out
+        .split("\n\n")
+        .map(|x| {
+            x.lines()
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .filter(|x| !x.is_empty())
+        .collect();
+
+    Body { blocks, links }
+}
+
+/// Текст и пассаж ссылки Twine.
+fn parse_link(link: &str) -> (String, String) {
+    let (text, target) = if let Some(x) = link.rfind("->") {
+        (&link[..x], &link[x + 2..])
+    } else if let Some(x) = link.find("<-") {
+        (&link[x + 2..], &link[..x])
+    } else if let Some(x) = link.rfind('|') {
+        (&link[..x], &link[x + 1..])
+    } else {
+        (link, link)
+    };
+    (text.trim().to_string(), target.trim().to_string())
+}
+
+/// Длина макроса SugarCube `<<...>>` в начале `text`.
+fn sugarcube_macro(text: &str) -> Option<usize> {
+    let inner = text.strip_prefix("<<")?;
+    let name = inner.strip_prefix('/').unwrap_or(inner);
+    if !name.starts_with(|x: char| x.is_ascii_alphabetic()) {
+        return None;
+    }
+    inner.find(">>").map(|x| x + 4)
+}
+
+/// Длина вызова макроса Harlowe `(name: ...)` в начале `text` со скобками внутри.
+fn harlowe_macro(text: &str) -> Option<usize> {
+    let inner = text.strip_prefix('(')?;
+    let name_len = inner
+        .find(|x: char| !(x.is_alphanumeric() || x == '-'))
+        .filter(|x| *x > 0)?;
+    if !inner[name_len..].starts_with(':') {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, x) in text.char_indices() {
+        match (quote, x) {
+            (Some(q), x) if x == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(x),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Имя макроса или переменной в начале `text`.
+fn macro_name(text: &str) -> &str {
+    let text = text.trim_start();
+    let start = usize::from(text.starts_with('/'));
+    let end = text[start..]
+        .find(|x: char| !(x.is_alphanumeric() || x == '-' || x == '_'))
+        .map_or(text.len(), |x| start + x);
+    &text[..end]
+}
+
+/// Похоже ли начало `text` на имя переменной после `$` (или `_` для временных).
+fn is_variable(text: &str, global: bool) -> bool {
+    let first = text.chars().next();
+    let valid = first.is_some_and(|x| x.is_alphabetic());
+    // `_` в обычном тексте встречается чаще, чем временные переменные,
+    // поэтому они считаются только в начале слова.
+    valid && (global || text.len() > 1)
+}
@@ -1,12 +1,402 @@
+use petgraph::graph::{Graph, NodeIndex};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::condition::Condition;
+use crate::rich_text::RichText;
+use crate::script::{Effect, Variable};
+use crate::source::GraphMLSource;
+
 /// Вершина графа.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vertex {
     pub id: String,
+    /// Текст первой подписи сцены.
     pub text: String,
+    /// Тексты остальных подписей сцены по порядку: следующие абзацы
+    /// или реплики персонажей после `text`.
+    pub paragraphs: Vec<String>,
+    /// Описание из ключа `description`: заметки автора, которые игрок не видит.
+    pub description: Option<String>,
+    /// Ссылка из ключа `url`.
+    pub url: Option<String>,
+    /// Теги из ключа `UserTags` (или `tags`), например `ending`, `chapter-2`.
+    pub tags: Vec<String>,
+    /// Концовка квеста, если сцена ею отмечена: ключом `ending`
+    /// или тегом `ending` (`ending:good`, `ending:secret:Тайная комната`).
+    pub ending: Option<Ending>,
+    /// Индекс главы в [`Quest::chapters`], в которую входит сцена.
+    pub chapter: Option<usize>,
+    /// Положение сцены на схеме автора, если оно есть в файле.
+    pub position: Option<Rect>,
+    /// Переводы текстовых блоков сцены по языкам: подписи с языковой
+    /// меткой `[en] ...` и значения ключа `lang`.
+    pub translations: BTreeMap<String, Vec<String>>,
+}
+
+impl Vertex {
+    /// Все текстовые блоки сцены по порядку: `text`, затем `paragraphs`.
+    pub fn blocks(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.text.as_str()).chain(self.paragraphs.iter().map(String::as_str))
+    }
+
+    /// Текстовые блоки сцены с разобранным оформлением.
+    pub fn rich_blocks(&self) -> impl Iterator<Item = RichText> + '_ {
+        self.blocks().map(RichText::parse)
+    }
+
+    /// Текстовые блоки сцены на языке `locale`.
+    ///
+    /// Если перевода нет или `locale` - `None`, то основной текст.
+    pub fn blocks_in(&self, locale: Option<&str>) -> Vec<&str> {
+        match locale.and_then(|x| find_translation(&self.translations, x)) {
+            Some(blocks) => blocks.iter().map(String::as_str).collect(),
+            None => self.blocks().collect(),
+        }
+    }
+
+    /// Текстовые блоки сцены на языке `locale` с разобранным оформлением.
+    pub fn rich_blocks_in(&self, locale: Option<&str>) -> Vec<RichText> {
+        self.blocks_in(locale)
+            .into_iter()
+            .map(RichText::parse)
+            .collect()
+    }
+
+    /// Есть ли у сцены тег `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|x| x == tag)
+    }
+
+    /// Заканчивается ли квест на этой сцене.
+    pub fn is_ending(&self) -> bool {
+        self.ending.is_some()
+    }
+
+    /// Концовка, которую нужно записать отдельно от тегов: тегов для нее нет
+    /// или они задают другую концовку.
+    pub(crate) fn ending_to_write(&self) -> Option<&Ending> {
+        self.ending
+            .as_ref()
+            .filter(|x| Ending::from_tags(&self.tags).as_ref() != Some(*x))
+    }
+}
+
+/// Тег сцены, которой квест заканчивается: такой сцене не нужны варианты выбора.
+pub const ENDING_TAG: &str = "ending";
+
+/// Тип концовки квеста.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EndingKind {
+    Good,
+    Bad,
+    #[default]
+    Neutral,
+    Secret,
+}
+
+impl EndingKind {
+    /// Все типы концовок в порядке показа игроку.
+    pub const ALL: [EndingKind; 4] = [
+        EndingKind::Good,
+        EndingKind::Neutral,
+        EndingKind::Bad,
+        EndingKind::Secret,
+    ];
+
+    /// Имя типа в файле квеста: `good`, `bad`, `neutral`, `secret`.
+    pub fn name(self) -> &'static str {
+        match self {
+            EndingKind::Good => "good",
+            EndingKind::Bad => "bad",
+            EndingKind::Neutral => "neutral",
+            EndingKind::Secret => "secret",
+        }
+    }
+
+    /// Название типа для игрока.
+    pub fn title(self) -> &'static str {
+        match self {
+            EndingKind::Good => "Хорошая концовка",
+            EndingKind::Bad => "Плохая концовка",
+            EndingKind::Neutral => "Концовка",
+            EndingKind::Secret => "Секретная концовка",
+        }
+    }
+
+    /// Тип по имени без учета регистра.
+    pub fn parse(name: &str) -> Option<EndingKind> {
+        let name = name.trim();
+        EndingKind::ALL
+            .iter()
+            .copied()
+            .find(|x| x.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Концовка квеста: тип и название, которые игрок видит на экране концовки.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ending {
+    pub kind: EndingKind,
+    pub title: Option<String>,
+}
+
+impl Ending {
+    /// Разобрать значение ключа `ending`: `good`, `good: Спасение принцессы`
+    /// или только название нейтральной концовки. Пустое значение и `true` -
+    /// нейтральная концовка без названия.
+    pub fn parse(value: &str) -> Ending {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("true") {
+            return Ending::default();
+        }
+
+        let (kind, title) = match value.split_once(':') {
+            Some((kind, title)) => (kind, Some(title)),
+            None => (value, None),
+        };
+        match EndingKind::parse(kind) {
+            Some(kind) => Ending {
+                kind,
+                title: title
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty())
+                    .map(String::from),
+            },
+            None => Ending {
+                kind: EndingKind::Neutral,
+                title: Some(value.to_string()),
+            },
+        }
+    }
+
+    /// Концовка из тегов сцены: `ending`, `ending:<тип>` или `ending:<тип>:<название>`.
+    pub fn from_tags(tags: &[String]) -> Option<Ending> {
+        tags.iter().find_map(|x| match x.strip_prefix(ENDING_TAG)? {
+            "" => Some(Ending::default()),
+            rest => rest.strip_prefix(':').map(Ending::parse),
+        })
+    }
+
+    /// Концовка из значения ключа `ending`, а если его нет, то из тегов.
+    pub fn find(value: Option<&str>, tags: &[String]) -> Option<Ending> {
+        value.map(Ending::parse).or_else(|| Ending::from_tags(tags))
+    }
+
+    /// Название для экрана концовки: заданное автором или название типа.
+    pub fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or_else(|| self.kind.title())
+    }
+}
+
+impl fmt::Display for Ending {
+    /// Значение ключа `ending`, которое [`Ending::parse`] читает обратно.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind.name())?;
+        if let Some(title) = &self.title {
+            write!(f, ": {}", title)?;
+        }
+        Ok(())
+    }
 }
 
 /// Ребро графа.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Edge {
+    /// Идентификатор ребра в GraphML. Если в файле его нет, то генерируется `e<номер>`.
+    pub id: String,
     pub text: String,
+    /// Описание из ключа `description`: заметки автора, которые игрок не видит.
+    pub description: Option<String>,
+    /// Ссылка из ключа `url`.
+    pub url: Option<String>,
+    /// Теги из ключа `UserTags` (или `tags`).
+    pub tags: Vec<String>,
+    /// Изменения переменных квеста при выборе варианта: ключ `effects`
+    /// и строки описания, которые начинаются с `~`.
+    pub effects: Vec<Effect>,
+    /// Условие, при котором вариант доступен: ключ `condition` и причина
+    /// недоступности из ключа `locked`.
+    pub condition: Option<Condition>,
+    /// Переводы текста варианта выбора по языкам.
+    pub translations: BTreeMap<String, String>,
+}
+
+impl Edge {
+    /// Текст варианта выбора с разобранным оформлением.
+    pub fn rich_text(&self) -> RichText {
+        RichText::parse(&self.text)
+    }
+
+    /// Текст варианта выбора на языке `locale`, а если перевода нет, то основной.
+    pub fn text_in(&self, locale: Option<&str>) -> &str {
+        locale
+            .and_then(|x| find_translation(&self.translations, x))
+            .unwrap_or(&self.text)
+    }
+
+    /// Текст варианта выбора на языке `locale` с разобранным оформлением.
+    pub fn rich_text_in(&self, locale: Option<&str>) -> RichText {
+        RichText::parse(self.text_in(locale))
+    }
+
+    /// Есть ли у варианта выбора тег `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|x| x == tag)
+    }
+}
+
+/// Глава квеста: group node yEd со вложенным графом сцен.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chapter {
+    /// Идентификатор group node в GraphML.
+    pub id: String,
+    /// Название главы из подписи group node.
+    pub title: String,
+    /// Индекс родительской главы в [`Quest::chapters`], если глава вложенная.
+    pub parent: Option<usize>,
+    /// Сцена, с которой начинается глава: в нее ведут ребра к самой главе.
+    ///
+    /// Задается ключом `start` у вложенного графа, иначе это первая сцена главы,
+    /// в которую не ведут ребра из других сцен главы. `None`, если сцен нет.
+    pub entry: Option<NodeIndex>,
+    /// Положение group node на схеме автора, если оно есть в файле.
+    pub position: Option<Rect>,
+}
+
+/// Прямоугольник сцены на схеме: левый верхний угол и размеры.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    /// Центр прямоугольника.
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// Квест: граф сцен и вариантов выбора с выделенной стартовой сценой.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quest {
+    pub graph: Graph<Vertex, Edge>,
+    /// Сцена, с которой начинается игра.
+    pub start: NodeIndex,
+    /// Главы квеста в порядке объявления в файле.
+    pub chapters: Vec<Chapter>,
+    /// Язык основного текста из ключа `lang` у графа, например `ru`.
+    pub locale: Option<String>,
+    /// Переменные квеста из ключа `variables` у графа.
+    pub variables: Vec<Variable>,
+    /// Исходный GraphML документ для записи без потерь.
+    /// `None`, если квест создан не из GraphML.
+    pub source: Option<GraphMLSource>,
+}
+
+impl Quest {
+    /// Глава, в которую непосредственно входит сцена.
+    pub fn chapter_of(&self, scene: NodeIndex) -> Option<&Chapter> {
+        self.graph[scene].chapter.and_then(|x| self.chapters.get(x))
+    }
+
+    /// Индекс главы, в которую записывается сцена. Сцена с индексом
+    /// несуществующей главы записывается на верхнем уровне, чтобы не потерять ее.
+    pub(crate) fn chapter_to_write(&self, scene: NodeIndex) -> Option<usize> {
+        self.graph[scene]
+            .chapter
+            .filter(|x| *x < self.chapters.len())
+    }
+
+    /// Входит ли сцена в главу с индексом `chapter` или в одну из ее вложенных глав.
+    ///
+    /// Цепочка `parent` с циклом обрывается на главе, которая уже встречалась.
+    pub fn is_in_chapter(&self, scene: NodeIndex, chapter: usize) -> bool {
+        let mut visited = HashSet::new();
+        let mut current = self.graph[scene].chapter;
+        while let Some(x) = current {
+            if x == chapter {
+                return true;
+            }
+            if !visited.insert(x) {
+                return false;
+            }
+            current = self.chapters.get(x).and_then(|x| x.parent);
+        }
+        false
+    }
+
+    /// Языки переводов, которые есть хотя бы у одной сцены или варианта выбора,
+    /// кроме языка основного текста.
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self
+            .graph
+            .raw_nodes()
+            .iter()
+            .flat_map(|x| x.weight.translations.keys())
+            .chain(
+                self.graph
+                    .raw_edges()
+                    .iter()
+                    .flat_map(|x| x.weight.translations.keys()),
+            )
+            .filter(|x| self.locale.as_deref() != Some(x.as_str()))
+            .cloned()
+            .collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    /// Выбрать язык игры по предпочтительному языку игрока (`en`, `en-US`, `en_US.UTF-8`).
+    ///
+    /// Возвращает язык перевода, подходящий к `preferred`, или `None` -
+    /// основной текст, если такого перевода нет или это язык основного текста.
+    pub fn pick_locale(&self, preferred: &str) -> Option<String> {
+        let preferred = preferred.split('.').next().unwrap_or_default();
+        if self
+            .locale
+            .as_deref()
+            .is_some_and(|x| same_locale(x, preferred))
+        {
+            return None;
+        }
+
+        let locales = self.locales();
+        locales
+            .iter()
+            .find(|x| x.eq_ignore_ascii_case(preferred))
+            .or_else(|| locales.iter().find(|x| same_locale(x, preferred)))
+            .cloned()
+    }
+}
+
+/// Перевод на язык `locale`: точное совпадение, иначе перевод на тот же язык
+/// с другим регионом (`en-US` для `en` и наоборот).
+fn find_translation<'a, T>(translations: &'a BTreeMap<String, T>, locale: &str) -> Option<&'a T> {
+    translations.get(locale).or_else(|| {
+        translations
+            .iter()
+            .find(|(x, _)| same_locale(x, locale))
+            .map(|(_, x)| x)
+    })
+}
+
+/// Один ли язык у двух меток без учета региона: `en`, `en-US` и `en_GB`.
+fn same_locale(a: &str, b: &str) -> bool {
+    language(a).eq_ignore_ascii_case(language(b))
+}
+
+fn language(locale: &str) -> &str {
+    locale.split(['-', '_']).next().unwrap_or_default()
 }
@@ -0,0 +1,156 @@
+use petgraph::Direction;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::rich_text::RichText;
+use crate::types::{Quest, ENDING_TAG};
+
+/// Насколько серьезна находка проверки квеста.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// Квест можно пройти, но, скорее всего, автор ошибся.
+    Warning,
+    /// Игрок застрянет или увидит пустой экран.
+    Error,
+}
+
+/// Что не так со сценой или вариантом выбора.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Problem {
+    /// В сцену нельзя попасть из стартовой сцены.
+    UnreachableScene,
+    /// Из сцены, которая не отмечена концовкой, нет вариантов выбора.
+    DeadEnd,
+    /// У сцены нет текста.
+    EmptySceneText,
+    /// У варианта выбора нет текста.
+    EmptyChoiceText,
+    /// У сцены уже есть вариант выбора с таким же текстом.
+    DuplicateChoice(String),
+    /// Вариант выбора ведет в ту же сцену.
+    SelfLoop,
+    /// Вариант выбора ведет из концовки: игра в ней заканчивается, и игрок его не увидит.
+    ChoiceFromEnding,
+}
+
+/// Находка проверки квеста.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Finding {
+    pub severity: Severity,
+    /// Идентификатор сцены или варианта выбора в GraphML.
+    pub element: String,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "предупреждение"),
+            Severity::Error => write!(f, "ошибка"),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        let id = &self.element;
+        match &self.problem {
+            Problem::UnreachableScene => {
+                write!(f, "в сцену `{}` нельзя попасть из стартовой сцены", id)
+            }
+            Problem::DeadEnd => write!(
+                f,
+                "из сцены `{}` нет вариантов выбора, но она не отмечена концовкой (`{}`)",
+                id, ENDING_TAG
+            ),
+            Problem::EmptySceneText => write!(f, "у сцены `{}` нет текста", id),
+            Problem::EmptyChoiceText => write!(f, "у варианта выбора `{}` нет текста", id),
+            Problem::DuplicateChoice(text) => write!(
+                f,
+                "у сцены уже есть вариант выбора `{}`, повторяется в `{}`",
+                text, id
+            ),
+            Problem::SelfLoop => write!(f, "вариант выбора `{}` ведет в ту же сцену", id),
+            Problem::ChoiceFromEnding => write!(
+                f,
+                "вариант выбора `{}` ведет из концовки, игрок его не увидит",
+                id
+            ),
+        }
+    }
+}
+
+/// Проверить квест перед публикацией.
+///
+/// Находки идут в порядке сцен, затем вариантов выбора в порядке объявления в файле.
+pub fn validate(quest: &Quest) -> Vec<Finding> {
+    let graph = &quest.graph;
+    let mut findings = Vec::new();
+    let mut report = |severity, element: &str, problem| {
+        findings.push(Finding {
+            severity,
+            element: element.to_string(),
+            problem,
+        })
+    };
+
+    // Игра заканчивается в концовке, поэтому из концовок обход дальше не идет
+    let mut reachable = HashSet::new();
+    let mut stack = vec![quest.start];
+    while let Some(ix) = stack.pop() {
+        if reachable.insert(ix) && !graph[ix].is_ending() {
+            stack.extend(graph.neighbors_directed(ix, Direction::Outgoing));
+        }
+    }
+
+    for ix in graph.node_indices() {
+        let scene = &graph[ix];
+        if !reachable.contains(&ix) {
+            report(Severity::Warning, &scene.id, Problem::UnreachableScene);
+        }
+        if scene.blocks().all(is_blank) {
+            report(Severity::Error, &scene.id, Problem::EmptySceneText);
+        }
+        let has_choices = graph
+            .neighbors_directed(ix, Direction::Outgoing)
+            .next()
+            .is_some();
+        if !has_choices && !scene.is_ending() {
+            report(Severity::Error, &scene.id, Problem::DeadEnd);
+        }
+    }
+
+    let mut labels = HashSet::new();
+    for edge in graph.raw_edges() {
+        let choice = &edge.weight;
+        if is_blank(&choice.text) {
+            report(Severity::Error, &choice.id, Problem::EmptyChoiceText);
+        } else {
+            let text = RichText::parse(&choice.text).plain().trim().to_string();
+            if !labels.insert((edge.source(), text.clone())) {
+                report(
+                    Severity::Warning,
+                    &choice.id,
+                    Problem::DuplicateChoice(text),
+                );
+            }
+        }
+        if edge.source() == edge.target() {
+            report(Severity::Warning, &choice.id, Problem::SelfLoop);
+        }
+        if graph[edge.source()].is_ending() {
+            report(Severity::Warning, &choice.id, Problem::ChoiceFromEnding);
+        }
+    }
+
+    findings
+}
+
+/// Нет видимого игроку текста: пусто или только оформление.
+fn is_blank(text: &str) -> bool {
+    RichText::parse(text).plain().trim().is_empty()
+}
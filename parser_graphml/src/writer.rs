@@ -0,0 +1,921 @@
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+use serde_json::{json, Map, Value};
+
+use crate::dialect::Dialect;
+use crate::keys::{GraphMLKey, KeyDomain};
+use crate::parser_impl::{
+    read_graphml, AnnotationKeys, CONDITION_KEY_NAME, EFFECTS_KEY_NAME, LANG_KEY_NAME,
+    LOCKED_KEY_NAME, START_KEY_NAME, VARIABLES_KEY_NAME,
+};
+use crate::source::{GraphMLSource, RawElement, Splice};
+use crate::types::*;
+
+const NODE_WIDTH: f64 = 240.0;
+const NODE_HEIGHT: f64 = 60.0;
+const LAYER_GAP_X: f64 = 300.0;
+const LAYER_GAP_Y: f64 = 150.0;
+const GROUP_PADDING: f64 = 30.0;
+
+/// Сериализовать квест в GraphML формат.
+///
+/// Если квест прочитан из GraphML (есть [`Quest::source`]), то исходный документ
+/// записывается без потерь: меняются только подписи и `<data>` измененных
+/// сцен, вариантов выбора и графа, удаленные элементы вырезаются, а новые
+/// дописываются в конец графа в диалекте исходного файла вместе со всеми
+/// своими данными. Недостающие ключи объявляются после существующих.
+/// Геометрия, стили и неизвестные данные остаются как были. Если изменение
+/// так записать нельзя (абзацы, главы, положения сцен) и документ читается
+/// уже не в тот же квест, то квест пишется заново, как без исходного документа.
+///
+/// Иначе квест пишется в формате yFiles for HTML (yEd Live). Стартовая сцена
+/// отмечается флагом `start`, поэтому [`read_graphml`] прочитает квест обратно
+/// без изменений. Сцены остаются на своих местах на схеме автора, а сцены
+/// без положения раскладываются по слоям в порядке обхода в ширину
+/// от стартовой сцены, чтобы файл сразу можно было открыть в редакторе.
+pub fn write_graphml(quest: &Quest) -> String {
+    match &quest.source {
+        Some(source) => write_lossless(quest, source),
+        None => write_generated(quest),
+    }
+}
+
+/// Записать исходный документ, применив к нему изменения квеста,
+/// а если документ читается не в тот же квест, то записать квест заново.
+fn write_lossless(quest: &Quest, source: &GraphMLSource) -> String {
+    let written = LosslessWriter::new(source).write(quest);
+    if read_graphml(&written).is_ok_and(|x| is_same_quest(&x, quest)) {
+        written
+    } else {
+        write_generated(quest)
+    }
+}
+
+/// Совпадают ли квесты: сцены и варианты выбора сравниваются
+/// по идентификаторам, главы - по содержимому.
+fn is_same_quest(written: &Quest, quest: &Quest) -> bool {
+    fn scenes(quest: &Quest) -> HashMap<&String, &Vertex> {
+        quest
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|x| (&x.weight.id, &x.weight))
+            .collect()
+    }
+    fn choices(quest: &Quest) -> Vec<(&String, &String, &String, &Edge)> {
+        let graph = &quest.graph;
+        let mut choices: Vec<_> = graph
+            .edge_references()
+            .map(|e| {
+                let weight = e.weight();
+                (
+                    &weight.id,
+                    &graph[e.source()].id,
+                    &graph[e.target()].id,
+                    weight,
+                )
+            })
+            .collect();
+        choices.sort_by(|a, b| (a.0, a.1, a.2).cmp(&(b.0, b.1, b.2)));
+        choices
+    }
+    #[allow(clippy::type_complexity)]
+    fn chapters(
+        quest: &Quest,
+    ) -> Vec<(
+        &String,
+        &String,
+        Option<usize>,
+        Option<&String>,
+        Option<Rect>,
+    )> {
+        quest
+            .chapters
+            .iter()
+            .map(|x| {
+                let entry = x.entry.map(|ix| &quest.graph[ix].id);
+                (&x.id, &x.title, x.parent, entry, x.position)
+            })
+            .collect()
+    }
+
+    written.graph[written.start].id == quest.graph[quest.start].id
+        && written.locale == quest.locale
+        && written.variables == quest.variables
+        && scenes(written) == scenes(quest)
+        && choices(written) == choices(quest)
+        && chapters(written) == chapters(quest)
+}
+
+/// Новое значение `<data>` элемента: ключ (если он уже объявлен), имя ключа
+/// и разметка значения (`None` - удалить `<data>`).
+type DataChange<'a> = (Option<&'a GraphMLKey>, &'static str, Option<String>);
+
+/// Правки исходного документа без потерь.
+struct LosslessWriter<'a> {
+    source: &'a GraphMLSource,
+    node_keys: AnnotationKeys<'a>,
+    edge_keys: AnnotationKeys<'a>,
+    edits: Vec<(Range<usize>, String)>,
+    /// Ключи, которые нужно объявить: область, имя и идентификатор.
+    new_keys: Vec<(KeyDomain, &'static str, String)>,
+}
+
+impl<'a> LosslessWriter<'a> {
+    fn new(source: &'a GraphMLSource) -> Self {
+        let keys = &source.keys;
+        let edge_label_key = source.edge_label_key.as_ref().and_then(|x| keys.get(x));
+        LosslessWriter {
+            source,
+            node_keys: AnnotationKeys::find(
+                keys,
+                KeyDomain::Node,
+                keys.get(&source.node_label_key),
+            ),
+            edge_keys: AnnotationKeys::find(keys, KeyDomain::Edge, edge_label_key),
+            edits: Vec::new(),
+            new_keys: Vec::new(),
+        }
+    }
+
+    fn write(mut self, quest: &Quest) -> String {
+        let source = self.source;
+        let graph = &quest.graph;
+        let start_id = &graph[quest.start].id;
+
+        // Стартовую сцену нужно отметить заново, если она сменилась или была
+        // единственной сценой без входящих ребер, а теперь таких сцен несколько.
+        let mut sources = graph.externals(Direction::Incoming);
+        let single_source = sources.next() == Some(quest.start) && sources.next().is_none();
+        let mark_start =
+            source.start.as_ref() != Some(start_id) || (!source.start_marked && !single_source);
+        let graph_start_key = source
+            .keys
+            .find_by_name(KeyDomain::Graph, START_KEY_NAME)
+            .filter(|x| source.graph_data.contains_key(&x.id));
+        let node_start_key = source.keys.find_by_name(KeyDomain::Node, START_KEY_NAME);
+
+        let mut graph_data: Vec<DataChange<'a>> = Vec::new();
+        if mark_start && graph_start_key.is_some() {
+            graph_data.push((graph_start_key, START_KEY_NAME, Some(escape(start_id))));
+        }
+        if quest.locale != source.locale {
+            let key = source.keys.find_by_name(KeyDomain::Graph, LANG_KEY_NAME);
+            graph_data.push((key, LANG_KEY_NAME, quest.locale.as_deref().map(escape)));
+        }
+        if quest.variables != source.variables {
+            let key = source
+                .keys
+                .find_by_name(KeyDomain::Graph, VARIABLES_KEY_NAME);
+            let value = Some(&quest.variables)
+                .filter(|x| !x.is_empty())
+                .map(|x| escape(&join_lines(x)));
+            graph_data.push((key, VARIABLES_KEY_NAME, value));
+        }
+        let mut added = String::new();
+        for (key, markup) in self.resolve(KeyDomain::Graph, graph_data) {
+            match (source.graph_data.get(&key), markup) {
+                (Some(range), markup) => self.replace_data(range, markup),
+                (None, Some(markup)) => {
+                    added.push_str(&markup);
+                    added.push_str(&source.indent);
+                }
+                (None, None) => (),
+            }
+        }
+        self.edits
+            .push((source.graph_data_start..source.graph_data_start, added));
+
+        let mut new_nodes = String::new();
+        let mut node_ids = HashSet::new();
+        for ix in graph.node_indices() {
+            let vertex = &graph[ix];
+            node_ids.insert(vertex.id.as_str());
+            let raw = source.nodes.get(&vertex.id);
+
+            let mut data = self.vertex_data(raw.map(|x| &x.original), vertex);
+            let is_old_start = source.start.as_ref() == Some(&vertex.id);
+            if mark_start && graph_start_key.is_none() && &vertex.id == start_id {
+                data.push((node_start_key, START_KEY_NAME, Some("true".to_string())));
+            } else if mark_start && is_old_start && &vertex.id != start_id {
+                data.push((node_start_key, START_KEY_NAME, None));
+            }
+            let data = self.resolve(KeyDomain::Node, data);
+
+            match raw {
+                Some(raw) => {
+                    let label = raw
+                        .label
+                        .as_ref()
+                        .filter(|_| raw.original.text != vertex.text);
+                    let label = label.map(|x| (x, Some(&source.node_label_key)));
+                    self.splice_element(raw, label, KeyDomain::Node, &vertex.text, data);
+                }
+                None => {
+                    new_nodes.push_str(&source.indent);
+                    new_nodes.push_str(&format!("<node id=\"{}\">", escape(&vertex.id)));
+                    new_nodes.push_str(&lossless_label(
+                        source,
+                        Some(&source.node_label_key),
+                        KeyDomain::Node,
+                        &vertex.text,
+                    ));
+                    new_nodes.push_str(&new_data(data));
+                    new_nodes.push_str("</node>");
+                }
+            }
+        }
+
+        let mut new_edges = String::new();
+        let mut edge_ids = HashSet::new();
+        for edge in graph.edge_references() {
+            let weight = edge.weight();
+            let source_id = &graph[edge.source()].id;
+            let target_id = &graph[edge.target()].id;
+            edge_ids.insert(weight.id.as_str());
+
+            let raw = source.edges.get(&weight.id);
+            let endpoints = Some((source_id.clone(), target_id.clone()));
+            let moved = raw.is_some_and(|x| x.endpoints != endpoints);
+            let original = raw.filter(|_| !moved).map(|x| &x.original);
+            let data = self.edge_data(original, weight);
+            let data = self.resolve(KeyDomain::Edge, data);
+
+            let element = || {
+                format!(
+                    "<edge id=\"{}\" source=\"{}\" target=\"{}\">{}{}</edge>",
+                    escape(&weight.id),
+                    escape(source_id),
+                    escape(target_id),
+                    lossless_label(
+                        source,
+                        source.edge_label_key.as_ref(),
+                        KeyDomain::Edge,
+                        &weight.text
+                    ),
+                    new_data(data.clone()),
+                )
+            };
+            match raw {
+                Some(raw) if !moved => {
+                    let label = raw
+                        .label
+                        .as_ref()
+                        .filter(|_| raw.original.text != weight.text);
+                    let label = label.map(|x| (x, source.edge_label_key.as_ref()));
+                    self.splice_element(raw, label, KeyDomain::Edge, &weight.text, data);
+                }
+                // Ребро перенесено на другие сцены: старая геометрия ему уже не подходит.
+                Some(raw) => self.edits.push((raw.range.clone(), element())),
+                None => {
+                    new_edges.push_str(&source.indent);
+                    new_edges.push_str(&element());
+                }
+            }
+        }
+
+        for (id, raw) in &source.nodes {
+            if !node_ids.contains(id.as_str()) {
+                self.edits
+                    .push((source.range_with_indent(&raw.range), String::new()));
+            }
+        }
+        for (id, raw) in &source.edges {
+            if !edge_ids.contains(id.as_str()) {
+                self.edits
+                    .push((source.range_with_indent(&raw.range), String::new()));
+            }
+        }
+        self.edits
+            .push((source.nodes_end..source.nodes_end, new_nodes));
+        self.edits
+            .push((source.edges_end..source.edges_end, new_edges));
+
+        let declarations = self
+            .new_keys
+            .iter()
+            .map(|(domain, name, id)| {
+                let attr_type = if *name == START_KEY_NAME && *domain == KeyDomain::Node {
+                    "boolean"
+                } else {
+                    "string"
+                };
+                format!(
+                    "{}<key id=\"{}\" for=\"{}\" attr.type=\"{}\" attr.name=\"{}\"/>",
+                    source.keys_indent,
+                    id,
+                    domain_name(*domain),
+                    attr_type,
+                    name
+                )
+            })
+            .collect();
+        self.edits
+            .push((source.keys_end..source.keys_end, declarations));
+
+        apply_edits(source.text(), self.edits)
+    }
+
+    /// Изменения `<data>` сцены. У новой сцены (`old` - `None`) пишутся все данные.
+    fn vertex_data(&self, old: Option<&Vertex>, new: &Vertex) -> Vec<DataChange<'a>> {
+        let keys = &self.node_keys;
+        let changed = |f: &dyn Fn(&Vertex) -> bool| old.is_none_or(f);
+        let mut data = Vec::new();
+        if changed(&|x| x.description != new.description) {
+            let value = new.description.as_deref().map(escape);
+            data.push((keys.description, "description", value));
+        }
+        if changed(&|x| x.url != new.url) {
+            data.push((keys.url, "url", new.url.as_deref().map(escape)));
+        }
+        if changed(&|x| x.tags != new.tags) {
+            data.push((keys.tags, "tags", self.tags_markup(&new.tags)));
+        }
+        if changed(&|x| x.tags != new.tags || x.ending != new.ending) {
+            let value = new.ending_to_write().map(|x| escape(&x.to_string()));
+            data.push((keys.ending, ENDING_TAG, value));
+        }
+        if changed(&|x| x.translations != new.translations) {
+            let translations = new
+                .translations
+                .iter()
+                .map(|(locale, blocks)| (locale, json!(blocks)));
+            data.push((keys.lang, LANG_KEY_NAME, translations_markup(translations)));
+        }
+        data
+    }
+
+    /// Изменения `<data>` варианта выбора. У нового варианта (`old` - `None`)
+    /// пишутся все данные.
+    fn edge_data(&self, old: Option<&Edge>, new: &Edge) -> Vec<DataChange<'a>> {
+        let keys = &self.edge_keys;
+        let changed = |f: &dyn Fn(&Edge) -> bool| old.is_none_or(f);
+        let mut data = Vec::new();
+        // Изменения переменных могут быть записаны в описании строками `~`,
+        // поэтому описание и изменения переписываются вместе.
+        if changed(&|x| x.description != new.description || x.effects != new.effects) {
+            let value = new.description.as_deref().map(escape);
+            data.push((keys.description, "description", value));
+            let value = Some(&new.effects)
+                .filter(|x| !x.is_empty())
+                .map(|x| escape(&join_lines(x)));
+            data.push((keys.effects, EFFECTS_KEY_NAME, value));
+        }
+        if changed(&|x| x.url != new.url) {
+            data.push((keys.url, "url", new.url.as_deref().map(escape)));
+        }
+        if changed(&|x| x.tags != new.tags) {
+            data.push((keys.tags, "tags", self.tags_markup(&new.tags)));
+        }
+        if changed(&|x| x.condition != new.condition) {
+            let condition = new.condition.as_ref();
+            let value = condition.map(|x| escape(&x.expr.to_string()));
+            data.push((keys.condition, CONDITION_KEY_NAME, value));
+            let value = condition.and_then(|x| x.locked.as_deref()).map(escape);
+            data.push((keys.locked, LOCKED_KEY_NAME, value));
+        }
+        if changed(&|x| x.translations != new.translations) {
+            let translations = new
+                .translations
+                .iter()
+                .map(|(locale, text)| (locale, json!(text)));
+            data.push((keys.lang, LANG_KEY_NAME, translations_markup(translations)));
+        }
+        data
+    }
+
+    /// Теги: массив JSON в `y:Json`, как пишет yEd Live, или слова через
+    /// запятую, если в диалекте нет `y:Json`, а тегам не нужны кавычки.
+    fn tags_markup(&self, tags: &[String]) -> Option<String> {
+        if tags.is_empty() {
+            return None;
+        }
+        let plain = tags
+            .iter()
+            .all(|x| !x.contains(|x: char| x == ',' || x.is_whitespace()));
+        Some(match self.source.dialect {
+            Dialect::YFilesHtml => format!("<y:Json>{}</y:Json>", escape(&json!(tags).to_string())),
+            _ if plain => escape(&tags.join(", ")),
+            _ => format!(
+                "<y:Json xmlns:y=\"{}\">{}</y:Json>",
+                YFILES_COMMON_NAMESPACE,
+                escape(&json!(tags).to_string())
+            ),
+        })
+    }
+
+    /// Разметка `<data>` по идентификаторам ключей. Ключ объявляется,
+    /// только если по нему нужно что-то записать.
+    fn resolve(
+        &mut self,
+        domain: KeyDomain,
+        data: Vec<DataChange<'a>>,
+    ) -> Vec<(String, Option<String>)> {
+        data.into_iter()
+            .filter_map(|(key, name, value)| {
+                let id = match (key, &value) {
+                    (Some(key), _) => key.id.clone(),
+                    // Необъявленного ключа нет и в документе, удалять нечего
+                    (None, None) => return None,
+                    (None, Some(_)) => self.declare_key(domain, name),
+                };
+                let markup = value.map(|x| data_markup(&id, &x));
+                Some((id, markup))
+            })
+            .collect()
+    }
+
+    fn declare_key(&mut self, domain: KeyDomain, name: &'static str) -> String {
+        if let Some((_, _, id)) = self
+            .new_keys
+            .iter()
+            .find(|(d, n, _)| *d == domain && *n == name)
+        {
+            return id.clone();
+        }
+        let id = (self.source.keys.keys().len()..)
+            .map(|i| format!("d{}", i))
+            .find(|id| {
+                self.source.keys.get(id).is_none() && !self.new_keys.iter().any(|x| &x.2 == id)
+            })
+            .unwrap_or_default();
+        self.new_keys.push((domain, name, id.clone()));
+        id
+    }
+
+    /// Записать изменения подписи и `<data>` существующего элемента.
+    fn splice_element<T>(
+        &mut self,
+        raw: &RawElement<T>,
+        label: Option<(&Splice, Option<&String>)>,
+        domain: KeyDomain,
+        text: &str,
+        data: Vec<(String, Option<String>)>,
+    ) {
+        let mut added = String::new();
+        for (key, markup) in data {
+            match raw.data.get(&key) {
+                Some(range) => self.replace_data(range, markup),
+                None => {
+                    if let Some(markup) = markup {
+                        added.push_str(&raw.indent);
+                        added.push_str(&markup);
+                    }
+                }
+            }
+        }
+
+        if let Some((splice, key)) = label {
+            // Пустой элемент `<edge/>` раскрывается один раз: подпись
+            // пишется вместе с новыми данными.
+            if splice.range == raw.append.range && !added.is_empty() {
+                added.insert_str(0, &lossless_label(self.source, key, domain, text));
+            } else {
+                self.edits.push((splice.range.clone(), splice.apply(text)));
+            }
+        }
+        if !added.is_empty() {
+            self.edits
+                .push((raw.append.range.clone(), raw.append.apply_markup(&added)));
+        }
+    }
+
+    /// Заменить `<data>` новой разметкой или удалить его вместе с отступом.
+    fn replace_data(&mut self, range: &Range<usize>, markup: Option<String>) {
+        match markup {
+            Some(markup) => self.edits.push((range.clone(), markup)),
+            None => self
+                .edits
+                .push((self.source.range_with_indent(range), String::new())),
+        }
+    }
+}
+
+/// Применить правки к тексту документа.
+fn apply_edits(text: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    // Вставка идет раньше удаления, которое начинается в той же позиции,
+    // а вставки в одну позицию - в порядке добавления.
+    edits.sort_by_key(|(range, _)| (range.start, range.end));
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (range, replacement) in edits {
+        // Правки внутри уже удаленного элемента, например сцены группы.
+        if range.start < cursor {
+            continue;
+        }
+        out.push_str(&text[cursor..range.start]);
+        out.push_str(&replacement);
+        cursor = range.end;
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+/// `<data>` нового элемента: только непустые значения.
+fn new_data(data: Vec<(String, Option<String>)>) -> String {
+    data.into_iter().filter_map(|(_, markup)| markup).collect()
+}
+
+fn data_markup(key: &str, content: &str) -> String {
+    format!("<data key=\"{}\">{}</data>", escape(key), content)
+}
+
+/// Переводы объектом JSON для ключа `lang`, если они есть.
+fn translations_markup<'a>(
+    translations: impl Iterator<Item = (&'a String, Value)>,
+) -> Option<String> {
+    let fields: Map<String, Value> = translations
+        .map(|(locale, value)| (locale.clone(), value))
+        .collect();
+    Some(fields)
+        .filter(|x| !x.is_empty())
+        .map(|x| escape(&Value::Object(x).to_string()))
+}
+
+/// Значение атрибута `for` ключа.
+fn domain_name(domain: KeyDomain) -> &'static str {
+    match domain {
+        KeyDomain::Graph => "graph",
+        KeyDomain::Node => "node",
+        KeyDomain::Edge => "edge",
+        KeyDomain::Port => "port",
+        KeyDomain::All | KeyDomain::Other => "all",
+    }
+}
+
+/// Пространство имен `y:Json` в yFiles for HTML.
+const YFILES_COMMON_NAMESPACE: &str = "http://www.yworks.com/xml/yfiles-common/3.0";
+
+fn lossless_label(
+    source: &GraphMLSource,
+    key: Option<&String>,
+    domain: KeyDomain,
+    text: &str,
+) -> String {
+    match key {
+        Some(key) if !text.is_empty() || domain == KeyDomain::Node => {
+            let (open, close) = source.dialect.label_markup(key, domain);
+            format!("{}{}{}", open, escape(text), close)
+        }
+        _ => String::new(),
+    }
+}
+
+fn write_generated(quest: &Quest) -> String {
+    let graph = &quest.graph;
+    let positions = layout(quest);
+
+    let mut out = String::new();
+    out.push_str(r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#);
+    out.push('\n');
+    out.push_str(&format!(
+        "<!--Created by parser_graphml {}-->\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    out.push_str(concat!(
+        r#"<graphml xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://www.yworks.com/xml/schema/graphml.html/2.0/ygraphml.xsd " "#,
+        r#"xmlns="http://graphml.graphdrawing.org/xmlns" "#,
+        r#"xmlns:y="http://www.yworks.com/xml/yfiles-common/3.0" "#,
+        r#"xmlns:x="http://www.yworks.com/xml/yfiles-common/markup/3.0" "#,
+        r#"xmlns:yjs="http://www.yworks.com/xml/yfiles-for-html/2.0/xaml" "#,
+        r#"xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">"#,
+        "\n",
+        "\t<key id=\"d0\" for=\"node\" attr.type=\"boolean\" attr.name=\"start\">\n",
+        "\t\t<default>false</default>\n",
+        "\t</key>\n",
+        "\t<key id=\"d1\" for=\"node\" attr.name=\"NodeLabels\" y:attr.uri=\"http://www.yworks.com/xml/yfiles-common/2.0/NodeLabels\"/>\n",
+        "\t<key id=\"d2\" for=\"node\" attr.name=\"NodeGeometry\" y:attr.uri=\"http://www.yworks.com/xml/yfiles-common/2.0/NodeGeometry\"/>\n",
+        "\t<key id=\"d3\" for=\"node\" attr.name=\"NodeStyle\" y:attr.uri=\"http://www.yworks.com/xml/yfiles-common/2.0/NodeStyle\"/>\n",
+        "\t<key id=\"d4\" for=\"edge\" attr.name=\"EdgeLabels\" y:attr.uri=\"http://www.yworks.com/xml/yfiles-common/2.0/EdgeLabels\"/>\n",
+        "\t<key id=\"d5\" for=\"node\" attr.type=\"string\" attr.name=\"description\"/>\n",
+        "\t<key id=\"d6\" for=\"node\" attr.type=\"string\" attr.name=\"url\"/>\n",
+        "\t<key id=\"d7\" for=\"all\" attr.name=\"UserTags\" y:attr.uri=\"http://www.yworks.com/xml/yfiles-common/2.0/UserTags\"/>\n",
+        "\t<key id=\"d8\" for=\"edge\" attr.type=\"string\" attr.name=\"description\"/>\n",
+        "\t<key id=\"d9\" for=\"edge\" attr.type=\"string\" attr.name=\"url\"/>\n",
+        "\t<key id=\"d10\" for=\"graph\" attr.type=\"string\" attr.name=\"start\"/>\n",
+        "\t<key id=\"d11\" for=\"node\" attr.type=\"string\" attr.name=\"lang\"/>\n",
+        "\t<key id=\"d12\" for=\"edge\" attr.type=\"string\" attr.name=\"lang\"/>\n",
+        "\t<key id=\"d13\" for=\"graph\" attr.type=\"string\" attr.name=\"lang\"/>\n",
+        "\t<key id=\"d14\" for=\"node\" attr.type=\"string\" attr.name=\"ending\"/>\n",
+        "\t<key id=\"d15\" for=\"graph\" attr.type=\"string\" attr.name=\"variables\"/>\n",
+        "\t<key id=\"d16\" for=\"edge\" attr.type=\"string\" attr.name=\"effects\"/>\n",
+        "\t<key id=\"d17\" for=\"edge\" attr.type=\"string\" attr.name=\"condition\"/>\n",
+        "\t<key id=\"d18\" for=\"edge\" attr.type=\"string\" attr.name=\"locked\"/>\n",
+        "\t<graph id=\"G\" edgedefault=\"directed\">\n",
+    ));
+    if let Some(locale) = &quest.locale {
+        out.push_str(&format!(
+            "\t\t<data key=\"d13\">{}</data>\n",
+            escape(locale)
+        ));
+    }
+    if !quest.variables.is_empty() {
+        out.push_str(&format!(
+            "\t\t<data key=\"d15\">{}</data>\n",
+            escape(&join_lines(&quest.variables))
+        ));
+    }
+
+    write_graph_nodes(&mut out, quest, &positions, None, 0);
+
+    for edge in graph.edge_references() {
+        out.push_str(&format!(
+            "\t\t<edge id=\"{}\" source=\"{}\" target=\"{}\">\n",
+            escape(&edge.weight().id),
+            escape(&graph[edge.source()].id),
+            escape(&graph[edge.target()].id),
+        ));
+        let weight = edge.weight();
+        if !weight.text.is_empty() {
+            write_labels(&mut out, "d4", std::iter::once(weight.text.as_str()));
+        }
+        write_annotations(
+            &mut out,
+            ["d8", "d9", "d7"],
+            &weight.description,
+            &weight.url,
+            &weight.tags,
+        );
+        if !weight.effects.is_empty() {
+            out.push_str(&format!(
+                "\t\t\t<data key=\"d16\">{}</data>\n",
+                escape(&join_lines(&weight.effects))
+            ));
+        }
+        if let Some(condition) = &weight.condition {
+            out.push_str(&format!(
+                "\t\t\t<data key=\"d17\">{}</data>\n",
+                escape(&condition.expr.to_string())
+            ));
+            if let Some(locked) = &condition.locked {
+                out.push_str(&format!(
+                    "\t\t\t<data key=\"d18\">{}</data>\n",
+                    escape(locked)
+                ));
+            }
+        }
+        write_translations(
+            &mut out,
+            "d12",
+            weight
+                .translations
+                .iter()
+                .map(|(locale, text)| (locale, json!(text))),
+        );
+        out.push_str("\t\t</edge>\n");
+    }
+
+    out.push_str("\t</graph>\n</graphml>\n");
+    out
+}
+
+/// Объявления переменных или изменения переменных по одному на строке.
+fn join_lines<T: std::fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Записать сцены и главы, которые непосредственно входят в главу `chapter`
+/// (`None` - граф верхнего уровня). Вложенные графы сдвигаются на `depth` уровней.
+fn write_graph_nodes(
+    out: &mut String,
+    quest: &Quest,
+    positions: &HashMap<NodeIndex, Rect>,
+    chapter: Option<usize>,
+    depth: usize,
+) {
+    let graph = &quest.graph;
+    let pad = "\t\t".repeat(depth);
+
+    for ix in graph
+        .node_indices()
+        .filter(|ix| quest.chapter_to_write(*ix) == chapter)
+    {
+        push_indented(out, &write_scene(quest, ix, &positions[&ix]), &pad);
+    }
+
+    for (i, x) in quest.chapters.iter().enumerate() {
+        if x.parent != chapter {
+            continue;
+        }
+
+        let bounds = x.position.unwrap_or_else(|| {
+            let (x0, y0, x1, y1) = graph
+                .node_indices()
+                .filter(|ix| quest.is_in_chapter(*ix, i))
+                .map(|ix| positions[&ix])
+                .fold(None, |bounds, rect| {
+                    let (x0, y0, x1, y1) = bounds.unwrap_or((rect.x, rect.y, rect.x, rect.y));
+                    Some((
+                        x0.min(rect.x),
+                        y0.min(rect.y),
+                        x1.max(rect.x + rect.width),
+                        y1.max(rect.y + rect.height),
+                    ))
+                })
+                .unwrap_or((0.0, 0.0, NODE_WIDTH, NODE_HEIGHT));
+
+            Rect {
+                x: x0 - GROUP_PADDING,
+                y: y0 - GROUP_PADDING * 2.0,
+                width: x1 - x0 + GROUP_PADDING * 2.0,
+                height: y1 - y0 + GROUP_PADDING * 3.0,
+            }
+        });
+
+        let mut group = String::new();
+        group.push_str(&format!("\t\t<node id=\"{}\">\n", escape(&x.id)));
+        write_labels(&mut group, "d1", std::iter::once(x.title.as_str()));
+        write_geometry(&mut group, &bounds);
+        group.push_str("\t\t\t<data key=\"d3\">\n\t\t\t\t<yjs:ShapeNodeStyle fill=\"#FFEBEBEB\" stroke=\"#FF999999\"/>\n\t\t\t</data>\n");
+        group.push_str(&format!(
+            "\t\t\t<graph id=\"{}:\" edgedefault=\"directed\">\n",
+            escape(&x.id)
+        ));
+        if let Some(entry) = x.entry {
+            group.push_str(&format!(
+                "\t\t\t\t<data key=\"d10\">{}</data>\n",
+                escape(&graph[entry].id)
+            ));
+        }
+        push_indented(out, &group, &pad);
+
+        write_graph_nodes(out, quest, positions, Some(i), depth + 1);
+        push_indented(out, "\t\t\t</graph>\n\t\t</node>\n", &pad);
+    }
+}
+
+fn write_scene(quest: &Quest, ix: NodeIndex, rect: &Rect) -> String {
+    let vertex = &quest.graph[ix];
+    let mut out = String::new();
+
+    out.push_str(&format!("\t\t<node id=\"{}\">\n", escape(&vertex.id)));
+    if ix == quest.start {
+        out.push_str("\t\t\t<data key=\"d0\">true</data>\n");
+    }
+    write_labels(&mut out, "d1", vertex.blocks());
+    write_geometry(&mut out, rect);
+    out.push_str("\t\t\t<data key=\"d3\">\n\t\t\t\t<yjs:ShapeNodeStyle fill=\"#FFFF8C00\" stroke=\"#FF000000\"/>\n\t\t\t</data>\n");
+    write_annotations(
+        &mut out,
+        ["d5", "d6", "d7"],
+        &vertex.description,
+        &vertex.url,
+        &vertex.tags,
+    );
+    if let Some(ending) = vertex.ending_to_write() {
+        out.push_str(&format!(
+            "\t\t\t<data key=\"d14\">{}</data>\n",
+            escape(&ending.to_string())
+        ));
+    }
+    write_translations(
+        &mut out,
+        "d11",
+        vertex
+            .translations
+            .iter()
+            .map(|(locale, blocks)| (locale, json!(blocks))),
+    );
+    out.push_str("\t\t</node>\n");
+    out
+}
+
+fn write_geometry(out: &mut String, rect: &Rect) {
+    out.push_str(&format!(
+        "\t\t\t<data key=\"d2\">\n\t\t\t\t<y:RectD X=\"{}\" Y=\"{}\" Width=\"{}\" Height=\"{}\"/>\n\t\t\t</data>\n",
+        rect.x, rect.y, rect.width, rect.height
+    ));
+}
+
+/// Дописать строки `text`, сдвинув каждую на `pad`.
+fn push_indented(out: &mut String, text: &str, pad: &str) {
+    for line in text.lines() {
+        out.push_str(pad);
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+fn write_labels<'a>(out: &mut String, key: &str, texts: impl Iterator<Item = &'a str>) {
+    out.push_str(&format!("\t\t\t<data key=\"{}\">\n\t\t\t\t<x:List>\n", key));
+    for text in texts {
+        out.push_str(&format!(
+            "\t\t\t\t\t<y:Label>\n\t\t\t\t\t\t<y:Label.Text>{}</y:Label.Text>\n\t\t\t\t\t</y:Label>\n",
+            escape(text)
+        ));
+    }
+    out.push_str("\t\t\t\t</x:List>\n\t\t\t</data>\n");
+}
+
+/// Записать описание, ссылку и теги по ключам `keys` в том же порядке.
+fn write_annotations(
+    out: &mut String,
+    keys: [&str; 3],
+    description: &Option<String>,
+    url: &Option<String>,
+    tags: &[String],
+) {
+    let [description_key, url_key, tags_key] = keys;
+    for (key, value) in &[(description_key, description), (url_key, url)] {
+        if let Some(value) = value {
+            out.push_str(&format!(
+                "\t\t\t<data key=\"{}\">{}</data>\n",
+                key,
+                escape(value)
+            ));
+        }
+    }
+
+    if !tags.is_empty() {
+        out.push_str(&format!(
+            "\t\t\t<data key=\"{}\">\n\t\t\t\t<y:Json>{}</y:Json>\n\t\t\t</data>\n",
+            tags_key,
+            escape(&json!(tags).to_string())
+        ));
+    }
+}
+
+/// Записать переводы объектом JSON в ключ `lang`: язык и значение JSON перевода.
+fn write_translations<'a>(
+    out: &mut String,
+    key: &str,
+    translations: impl Iterator<Item = (&'a String, Value)>,
+) {
+    if let Some(markup) = translations_markup(translations) {
+        out.push_str(&format!("\t\t\t{}\n", data_markup(key, &markup)));
+    }
+}
+
+/// Положения сцен: заданные автором, а для остальных - раскладка по слоям.
+fn layout(quest: &Quest) -> HashMap<NodeIndex, Rect> {
+    layout_by_layers(quest)
+        .into_iter()
+        .map(|(ix, (x, y))| {
+            let rect = quest.graph[ix].position.unwrap_or(Rect {
+                x,
+                y,
+                width: NODE_WIDTH,
+                height: NODE_HEIGHT,
+            });
+            (ix, rect)
+        })
+        .collect()
+}
+
+/// Разложить сцены по слоям обхода в ширину от стартовой сцены.
+///
+/// Недостижимые сцены попадают в отдельный последний слой.
+fn layout_by_layers(quest: &Quest) -> HashMap<NodeIndex, (f64, f64)> {
+    let graph = &quest.graph;
+    let mut layers: HashMap<NodeIndex, usize> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    layers.insert(quest.start, 0);
+    queue.push_back(quest.start);
+    while let Some(ix) = queue.pop_front() {
+        let layer = layers[&ix];
+        for next in graph.neighbors_directed(ix, Direction::Outgoing) {
+            if let Entry::Vacant(entry) = layers.entry(next) {
+                entry.insert(layer + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let unreachable_layer = layers.values().max().map_or(0, |x| x + 1);
+    let mut layer_sizes: HashMap<usize, usize> = HashMap::new();
+    graph
+        .node_indices()
+        .map(|ix| {
+            let layer = *layers.get(&ix).unwrap_or(&unreachable_layer);
+            let position = layer_sizes.entry(layer).or_insert(0);
+            let x = *position as f64 * LAYER_GAP_X;
+            *position += 1;
+
+            (ix, (x, layer as f64 * LAYER_GAP_Y))
+        })
+        .collect()
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for x in text.chars() {
+        match x {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(x),
+        }
+    }
+    escaped
+}
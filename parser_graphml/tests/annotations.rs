@@ -0,0 +1,142 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::{find_edge, find_vertex};
+
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+fn yfiles_html_quest(node_data: &str, edge_data: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!--Created by yFiles for HTML 2.2.0.2-->
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:y="http://www.yworks.com/xml/yfiles-common/3.0" xmlns:x="http://www.yworks.com/xml/yfiles-common/markup/3.0">
+	<key id="d1" for="node" attr.type="string" attr.name="url"/>
+	<key id="d2" for="node" attr.type="string" attr.name="description"/>
+	<key id="d3" for="node" attr.name="NodeLabels" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/NodeLabels"/>
+	<key id="d5" for="all" attr.name="UserTags" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/UserTags"/>
+	<key id="d8" for="edge" attr.type="string" attr.name="url"/>
+	<key id="d9" for="edge" attr.type="string" attr.name="description"/>
+	<key id="d10" for="edge" attr.name="EdgeLabels" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/EdgeLabels"/>
+	<graph id="G" edgedefault="directed">
+		<data key="d5">
+			<y:Json>{{"version":"2.0.0","theme":{{"name":"light","version":"1.0.0"}}}}</y:Json>
+		</data>
+		<node id="n0">
+			<data key="d3"><x:List><y:Label><y:Label.Text>Начало</y:Label.Text></y:Label></x:List></data>
+			{}
+		</node>
+		<node id="n1">
+			<data key="d3"><x:List><y:Label><y:Label.Text>Конец</y:Label.Text></y:Label></x:List></data>
+		</node>
+		<edge id="e0" source="n0" target="n1">
+			{}
+		</edge>
+	</graph>
+</graphml>"#,
+        node_data, edge_data
+    )
+}
+
+#[test]
+fn reads_description_url_and_json_tags() {
+    let xml_doc = yfiles_html_quest(
+        r#"<data key="d1">https://example.com/scene</data>
+			<data key="d2">Здесь игрок должен заметить ключ</data>
+			<data key="d5"><y:Json>["chapter-2","combat"]</y:Json></data>"#,
+        r#"<data key="d9">Ведет к финалу</data>
+			<data key="d5"><y:Json>"ending"</y:Json></data>"#,
+    );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    let start = find_vertex(&quest, "n0");
+    assert_eq!(start.url.as_deref(), Some("https://example.com/scene"));
+    assert_eq!(
+        start.description.as_deref(),
+        Some("Здесь игрок должен заметить ключ")
+    );
+    assert_eq!(start.tags, vec!["chapter-2", "combat"]);
+    assert!(start.has_tag("combat"));
+
+    let end = find_vertex(&quest, "n1");
+    assert_eq!(end.description, None);
+    assert!(end.tags.is_empty());
+
+    let choice = find_edge(&quest, "e0");
+    assert_eq!(choice.description.as_deref(), Some("Ведет к финалу"));
+    assert_eq!(choice.url, None);
+    assert_eq!(choice.tags, vec!["ending"]);
+}
+
+#[test]
+fn reads_tags_as_strings_and_plain_text() {
+    let xml_doc = yfiles_html_quest(
+        r#"<data key="d5"><x:String>ending</x:String></data>"#,
+        r#"<data key="d5">chapter-2, combat</data>"#,
+    );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    assert_eq!(find_vertex(&quest, "n0").tags, vec!["ending"]);
+    assert_eq!(find_edge(&quest, "e0").tags, vec!["chapter-2", "combat"]);
+}
+
+#[test]
+fn reads_plain_description_and_tags() {
+    let xml_doc = PLAIN_NETWORKX
+        .replace(
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />"#,
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />
+  <key id="d2" for="node" attr.name="description" attr.type="string" />
+  <key id="d3" for="node" attr.name="tags" attr.type="string" />"#,
+        )
+        .replace(
+            r#"<data key="d0">Вы уходите ни с чем</data>"#,
+            r#"<data key="d0">Вы уходите ни с чем</data>
+      <data key="d2">Плохая концовка</data>
+      <data key="d3">ending bad</data>"#,
+        );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    let end = find_vertex(&quest, "n2");
+    assert_eq!(end.text, "Вы уходите ни с чем");
+    assert_eq!(end.description.as_deref(), Some("Плохая концовка"));
+    assert_eq!(end.tags, vec!["ending", "bad"]);
+}
+
+#[test]
+fn description_used_as_label_is_not_repeated() {
+    let xml_doc = PLAIN_NETWORKX.replace(r#"attr.name="label""#, r#"attr.name="description""#);
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    let start = find_vertex(&quest, "n0");
+    assert_eq!(start.text, "Вы стоите у ворот");
+    assert_eq!(start.description, None);
+}
+
+#[test]
+fn written_quest_keeps_annotations() {
+    let xml_doc = yfiles_html_quest(
+        r#"<data key="d2">Заметка &amp; "цитата"</data>
+			<data key="d5"><y:Json>["chapter-2","say \"hi\""]</y:Json></data>"#,
+        r#"<data key="d8">https://example.com/choice</data>"#,
+    );
+    let mut quest = read_graphml(&xml_doc).expect("Parsed quest");
+    quest.source = None;
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+
+    // Сцене без геометрии писатель назначает положение из раскладки
+    let written = find_vertex(&read_back, "n0");
+    assert!(written.position.is_some());
+    assert_eq!(
+        &Vertex {
+            position: None,
+            ..written.clone()
+        },
+        find_vertex(&quest, "n0")
+    );
+    assert_eq!(
+        find_vertex(&read_back, "n0").tags,
+        vec!["chapter-2", "say \"hi\""]
+    );
+    assert_eq!(find_edge(&read_back, "e0"), find_edge(&quest, "e0"));
+}
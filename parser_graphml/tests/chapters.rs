@@ -0,0 +1,149 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::{edge_texts, find_node, vertexes};
+
+const CHAPTERS: &str = include_str!("fixtures/chapters.graphml");
+
+fn chapter_titles(quest: &Quest) -> Vec<(String, Option<String>, Option<String>)> {
+    quest
+        .chapters
+        .iter()
+        .map(|x| {
+            (
+                x.title.clone(),
+                x.parent.map(|i| quest.chapters[i].id.clone()),
+                x.entry.map(|ix| quest.graph[ix].id.clone()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn group_nodes_become_chapters() {
+    let quest = read_graphml(CHAPTERS).expect("Parsed quest");
+
+    assert_eq!(
+        chapter_titles(&quest),
+        vec![
+            (
+                "Глава 1. Город".to_string(),
+                None,
+                Some("g1::n1".to_string())
+            ),
+            ("Глава 2. Лес".to_string(), None, Some("g2::n4".to_string())),
+            (
+                "Поляна".to_string(),
+                Some("g2".to_string()),
+                Some("g2::g3::n5".to_string())
+            ),
+        ]
+    );
+    assert_eq!(quest.graph.node_count(), 6);
+    assert_eq!(quest.graph[quest.start].id, "n0");
+}
+
+#[test]
+fn scenes_know_their_chapter() {
+    let quest = read_graphml(CHAPTERS).expect("Parsed quest");
+    let title = |id| {
+        quest
+            .chapter_of(find_node(&quest, id))
+            .map(|x| x.title.as_str())
+    };
+
+    assert_eq!(title("n0"), None);
+    assert_eq!(title("g1::n2"), Some("Глава 1. Город"));
+    assert_eq!(title("g2::n3"), Some("Глава 2. Лес"));
+    assert_eq!(title("g2::g3::n5"), Some("Поляна"));
+
+    let hut = find_node(&quest, "g2::g3::n5");
+    assert!(quest.is_in_chapter(hut, 1));
+    assert!(quest.is_in_chapter(hut, 2));
+    assert!(!quest.is_in_chapter(hut, 0));
+}
+
+#[test]
+fn choices_into_chapter_lead_to_its_entry() {
+    let quest = read_graphml(CHAPTERS).expect("Parsed quest");
+
+    assert_eq!(
+        edge_texts(&quest),
+        vec![
+            ("g1::n1".into(), "g1::n2".into(), "Пройтись".into()),
+            ("n0".into(), "g1::n1".into(), "В город".into()),
+            ("g1::n2".into(), "g2::n4".into(), "В лес".into()),
+            ("g2::n4".into(), "g2::g3::n5".into(), "К поляне".into()),
+            ("g2::g3::n5".into(), "n0".into(), "Вернуться".into()),
+        ]
+    );
+}
+
+#[test]
+fn choice_from_chapter_is_reported() {
+    let xml_doc = CHAPTERS.replace(
+        r#"<edge id="e3" source="g2::g3::n5" target="n0">"#,
+        r#"<edge id="e3" source="g2" target="n0">"#,
+    );
+    let diagnostics = read_graphml_diagnostics(&xml_doc);
+
+    assert_eq!(diagnostics.errors.len(), 1);
+    assert_eq!(
+        diagnostics.errors[0].to_string(),
+        "вариант выбора начинается у главы `g2`, а должен у сцены (`e3`, строка 65, столбец 3)"
+    );
+}
+
+#[test]
+fn choice_into_empty_chapter_is_reported() {
+    let start = CHAPTERS.find(r#"<node id="g2::g3::n5">"#).unwrap();
+    let end = CHAPTERS[start..].find("</node>").unwrap() + start + "</node>".len();
+    let xml_doc = format!("{}{}", &CHAPTERS[..start], &CHAPTERS[end..])
+        .replace(r#"source="g2::g3::n5""#, r#"source="n0""#);
+    let diagnostics = read_graphml_diagnostics(&xml_doc);
+
+    assert!(diagnostics.errors.iter().any(|x| matches!(
+        x,
+        Error::FormatGraph(ErrorFormatGraph::EmptyChapter(id, _)) if id == "g2::g3"
+    )));
+}
+
+#[test]
+fn chapters_survive_lossless_and_generated_writing() {
+    let mut quest = read_graphml(CHAPTERS).expect("Parsed quest");
+    assert_eq!(write_graphml(&quest), CHAPTERS);
+
+    let market = find_node(&quest, "g1::n2");
+    quest.graph[market].text = "Шумный рынок".to_string();
+    assert_eq!(
+        write_graphml(&quest),
+        CHAPTERS.replace(">Рынок<", ">Шумный рынок<")
+    );
+
+    quest.source = None;
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+    assert_eq!(chapter_titles(&read_back), chapter_titles(&quest));
+    assert_eq!(edge_texts(&read_back), edge_texts(&quest));
+    assert_eq!(vertexes(&read_back), vertexes(&quest));
+    for ix in quest.graph.node_indices() {
+        if let Some(position) = quest.graph[ix].position {
+            assert_eq!(read_back.graph[ix].position, Some(position));
+        }
+    }
+}
+
+#[test]
+fn scene_of_missing_chapter_is_written_at_top_level() {
+    let mut quest = read_graphml(CHAPTERS).expect("Parsed quest");
+    quest.source = None;
+    let market = find_node(&quest, "g1::n2");
+    quest.graph[market].chapter = Some(quest.chapters.len() + 10);
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+
+    assert_eq!(
+        read_back.graph[find_node(&read_back, "g1::n2")].chapter,
+        None
+    );
+    assert_eq!(edge_texts(&read_back), edge_texts(&quest));
+}
@@ -0,0 +1,77 @@
+//! Помощники, общие для интеграционных тестов.
+//!
+//! Каждый тест подключает модуль целиком и пользуется только частью помощников.
+#![allow(dead_code)]
+
+use parser_graphml::parser::*;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+
+pub fn find_node(quest: &Quest, id: &str) -> NodeIndex {
+    quest
+        .graph
+        .node_indices()
+        .find(|ix| quest.graph[*ix].id == id)
+        .expect("Scene exists")
+}
+
+pub fn find_vertex<'a>(quest: &'a Quest, id: &str) -> &'a Vertex {
+    &quest.graph[find_node(quest, id)]
+}
+
+pub fn find_edge<'a>(quest: &'a Quest, id: &str) -> &'a Edge {
+    quest
+        .graph
+        .edge_indices()
+        .map(|ix| &quest.graph[ix])
+        .find(|x| x.id == id)
+        .expect("Choice exists")
+}
+
+/// Сцены без положений: сценам без геометрии писатель назначает их сам.
+pub fn vertexes(quest: &Quest) -> Vec<Vertex> {
+    quest
+        .graph
+        .node_indices()
+        .map(|ix| Vertex {
+            position: None,
+            ..quest.graph[ix].clone()
+        })
+        .collect()
+}
+
+/// Варианты выбора с идентификаторами начала и конца в порядке графа.
+pub fn edges(quest: &Quest) -> Vec<(String, String, Edge)> {
+    let graph = &quest.graph;
+    graph
+        .edge_references()
+        .map(|e| {
+            (
+                graph[e.source()].id.clone(),
+                graph[e.target()].id.clone(),
+                e.weight().clone(),
+            )
+        })
+        .collect()
+}
+
+/// Тексты вариантов выбора с идентификаторами начала и конца в порядке графа.
+pub fn edge_texts(quest: &Quest) -> Vec<(String, String, String)> {
+    edges(quest)
+        .into_iter()
+        .map(|(source, target, edge)| (source, target, edge.text))
+        .collect()
+}
+
+/// Тексты и концы вариантов выбора из сцены `id`, отсортированные.
+pub fn choices(quest: &Quest, id: &str) -> Vec<(String, String)> {
+    let mut choices = quest
+        .graph
+        .raw_edges()
+        .iter()
+        .filter(|x| quest.graph[x.source()].id == id)
+        .map(|x| (x.weight.text.clone(), quest.graph[x.target()].id.clone()))
+        .collect::<Vec<_>>();
+    choices.sort();
+    choices
+}
@@ -0,0 +1,297 @@
+use parser_graphml::parser::*;
+use std::collections::BTreeMap;
+
+const GUARD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+	<key id="d0" for="node" attr.type="string" attr.name="label"/>
+	<key id="d1" for="edge" attr.type="string" attr.name="label"/>
+	<key id="d2" for="edge" attr.type="string" attr.name="condition"/>
+	<key id="d3" for="edge" attr.type="string" attr.name="locked"/>
+	<key id="d4" for="graph" attr.type="string" attr.name="variables"/>
+	<graph id="G" edgedefault="directed">
+		<data key="d4">gold: int = 5; met_guard: bool; name = "Петр"</data>
+		<node id="gate"><data key="d0">Вы стоите у ворот</data></node>
+		<node id="castle"><data key="d0">Вы в замке</data></node>
+		<edge id="bribe" source="gate" target="castle"><data key="d1">Подкупить стражу</data><data key="d2">gold >= 10 &amp;&amp; !met_guard</data><data key="d3">Нужно 10 золотых</data></edge>
+		<edge id="friend" source="gate" target="castle"><data key="d1">Пройти как знакомый</data><data key="d2">met_guard || name == "Страж"</data></edge>
+		<edge id="walk" source="gate" target="castle"><data key="d1">Просто войти</data></edge>
+	</graph>
+</graphml>"#;
+
+fn variables() -> Vec<Variable> {
+    parse_variables("gold: int = 5; met_guard: bool; name = \"Петр\"").expect("Parsed variables")
+}
+
+fn state(gold: i64, met_guard: bool) -> BTreeMap<String, Value> {
+    vec![
+        ("gold".to_string(), Value::Int(gold)),
+        ("met_guard".to_string(), Value::Bool(met_guard)),
+        ("name".to_string(), Value::String("Петр".to_string())),
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn conditions(quest: &Quest) -> Vec<(&str, Option<String>, Option<&str>)> {
+    quest
+        .graph
+        .raw_edges()
+        .iter()
+        .map(|x| {
+            let condition = x.weight.condition.as_ref();
+            (
+                x.weight.id.as_str(),
+                condition.map(ToString::to_string),
+                condition.and_then(|x| x.locked.as_deref()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn parses_expressions_by_precedence() {
+    let expr = parse_condition("gold >= 10 && !met_guard || gold - 2 < -3", &variables())
+        .expect("Parsed condition");
+
+    assert_eq!(
+        expr,
+        Expr::Binary(
+            BinaryOp::Or,
+            Box::new(Expr::Binary(
+                BinaryOp::And,
+                Box::new(Expr::Binary(
+                    BinaryOp::Ge,
+                    Box::new(Expr::Variable("gold".to_string())),
+                    Box::new(Expr::Value(Value::Int(10))),
+                )),
+                Box::new(Expr::Unary(
+                    UnaryOp::Not,
+                    Box::new(Expr::Variable("met_guard".to_string())),
+                )),
+            )),
+            Box::new(Expr::Binary(
+                BinaryOp::Lt,
+                Box::new(Expr::Binary(
+                    BinaryOp::Sub,
+                    Box::new(Expr::Variable("gold".to_string())),
+                    Box::new(Expr::Value(Value::Int(2))),
+                )),
+                Box::new(Expr::Value(Value::Int(-3))),
+            )),
+        )
+    );
+}
+
+#[test]
+fn writes_expressions_back() {
+    for text in &[
+        "gold >= 10 && !met_guard",
+        "(met_guard || gold > 3) && name != \"Страж\"",
+        "!(gold - (2 - 1) == -gold)",
+        "gold - -3 > 0",
+    ] {
+        let expr = parse_condition(text, &variables()).expect("Parsed condition");
+        assert_eq!(expr.to_string(), *text);
+    }
+}
+
+#[test]
+fn evaluates_conditions() {
+    let check = |text: &str, gold: i64, met_guard: bool| {
+        parse_condition(text, &variables())
+            .expect("Parsed condition")
+            .eval(&state(gold, met_guard))
+    };
+
+    assert_eq!(
+        check("gold >= 10 && !met_guard", 10, false),
+        Some(Value::Bool(true))
+    );
+    assert_eq!(
+        check("gold >= 10 && !met_guard", 10, true),
+        Some(Value::Bool(false))
+    );
+    assert_eq!(check("name == \"Петр\"", 0, false), Some(Value::Bool(true)));
+    assert_eq!(
+        check("(gold + 1) - 2 == 4", 5, false),
+        Some(Value::Bool(true))
+    );
+    assert_eq!(
+        Expr::Variable("silver".to_string()).eval(&state(0, false)),
+        None
+    );
+}
+
+#[test]
+fn reports_syntax_errors_with_position() {
+    let parse = |text: &str| parse_condition(text, &variables());
+
+    assert_eq!(
+        parse("gold >= "),
+        Err(ErrorScript::Syntax("значение, переменная или `(`", 1, 9))
+    );
+    assert_eq!(parse("(gold > 1"), Err(ErrorScript::Syntax("`)`", 1, 10)));
+    assert_eq!(parse("gold 10"), Err(ErrorScript::Syntax("оператор", 1, 6)));
+    assert_eq!(
+        parse("gold @ 10"),
+        Err(ErrorScript::Syntax("оператор", 1, 6))
+    );
+    assert_eq!(
+        parse("gold > 0;"),
+        Err(ErrorScript::Syntax("конец условия", 1, 9))
+    );
+    assert_eq!(
+        parse("gold > 0 ; gold < 0"),
+        Err(ErrorScript::Syntax("конец условия", 1, 10))
+    );
+    assert_eq!(
+        parse("gold > 1\n&& met_guard\n&&"),
+        Err(ErrorScript::Syntax("значение, переменная или `(`", 3, 3))
+    );
+}
+
+#[test]
+fn reports_type_errors() {
+    let parse = |text: &str| parse_condition(text, &variables());
+
+    assert_eq!(
+        parse("silver > 1"),
+        Err(ErrorScript::UnknownVariable("silver".to_string()))
+    );
+    assert_eq!(
+        parse("gold && met_guard"),
+        Err(ErrorScript::OperandType("&&", VarType::Bool, VarType::Int))
+    );
+    assert_eq!(
+        parse("name > 1"),
+        Err(ErrorScript::OperandType(">", VarType::Int, VarType::String))
+    );
+    assert_eq!(
+        parse("gold == true"),
+        Err(ErrorScript::CompareMismatch(
+            "==",
+            VarType::Int,
+            VarType::Bool
+        ))
+    );
+    assert_eq!(
+        parse("gold + 1"),
+        Err(ErrorScript::ConditionType(VarType::Int))
+    );
+    assert!(matches!(
+        parse("1 < gold < 3"),
+        Err(ErrorScript::Syntax(..))
+    ));
+}
+
+#[test]
+fn reads_conditions_from_graphml() {
+    let quest = read_graphml(GUARD).expect("Parsed quest");
+
+    assert_eq!(
+        conditions(&quest),
+        vec![
+            (
+                "bribe",
+                Some("gold >= 10 && !met_guard".to_string()),
+                Some("Нужно 10 золотых")
+            ),
+            (
+                "friend",
+                Some("met_guard || name == \"Страж\"".to_string()),
+                None
+            ),
+            ("walk", None, None),
+        ]
+    );
+    let bribe = quest.graph.raw_edges()[0].weight.condition.as_ref();
+    assert_eq!(bribe.map(|x| x.is_met(&state(12, false))), Some(true));
+    assert_eq!(bribe.map(|x| x.is_met(&state(5, false))), Some(false));
+}
+
+#[test]
+fn reports_condition_errors_with_edge_id() {
+    let quest = GUARD.replace("name == ", "name > ");
+    let diagnostics = read_graphml_diagnostics(&quest);
+
+    let messages = diagnostics
+        .errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("`friend`"), "{}", messages[0]);
+    assert!(
+        messages[0].contains("операция `>` применима к значениям типа int, а не string"),
+        "{}",
+        messages[0]
+    );
+}
+
+#[test]
+fn writes_conditions_back() {
+    let quest = read_graphml(GUARD).expect("Parsed quest");
+    let generated = Quest {
+        source: None,
+        ..quest.clone()
+    };
+
+    for read_back in &[
+        read_graphml(&write_graphml(&generated)).expect("Parsed GraphML"),
+        read_quest_json(&write_quest_json(&quest)).expect("Parsed JSON"),
+        read_dot(&write_dot(&quest)).expect("Parsed DOT"),
+    ] {
+        assert_eq!(conditions(read_back), conditions(&quest));
+    }
+}
+
+#[test]
+fn reports_condition_errors_in_other_formats() {
+    assert!(matches!(
+        read_dot(r#"digraph { variables="gold: int"; a -> b [id="buy", condition="gold"] }"#),
+        Err(ErrorDot::InvalidScript(id, ErrorScript::ConditionType(VarType::Int))) if id == "buy"
+    ));
+    assert!(matches!(
+        read_quest_json(
+            r#"{"start": "a", "scenes": [{"id": "a", "text": ""}],
+                "choices": [{"id": "c", "from": "a", "to": "a", "condition": "key"}]}"#
+        ),
+        Err(ErrorJson::InvalidScript(path, ErrorScript::UnknownVariable(_))) if path == "choices[0].condition"
+    ));
+}
+
+#[test]
+fn limits_nesting_depth() {
+    let parse = |text: &str| parse_condition(text, &variables());
+    let nested = |open: &str, depth: usize, close: &str| {
+        format!("{}met_guard{}", open.repeat(depth), close.repeat(depth))
+    };
+    const TOO_DEEP: &str = "выражение не глубже 256 уровней";
+
+    assert!(parse(&nested("!", 256, "")).is_ok());
+    assert!(parse(&nested("(", 256, ")")).is_ok());
+    assert_eq!(
+        parse(&nested("!", 257, "")),
+        Err(ErrorScript::Syntax(TOO_DEEP, 1, 258))
+    );
+    // Вложенность считается по скобкам и операциям вместе
+    assert_eq!(
+        parse(&nested("(!", 129, ")")),
+        Err(ErrorScript::Syntax(TOO_DEEP, 1, 258))
+    );
+    // Без ограничения такие условия переполняли стек
+    assert!(matches!(
+        parse(&nested("!", 100_000, "")),
+        Err(ErrorScript::Syntax(TOO_DEEP, ..))
+    ));
+    assert!(matches!(
+        parse(&"(".repeat(100_000)),
+        Err(ErrorScript::Syntax(TOO_DEEP, ..))
+    ));
+    // Цепочка бинарных операций тоже строит глубокое дерево
+    assert!(parse(&vec!["met_guard"; 257].join(" && ")).is_ok());
+    assert!(matches!(
+        parse(&vec!["met_guard"; 100_000].join(" && ")),
+        Err(ErrorScript::Syntax(TOO_DEEP, ..))
+    ));
+}
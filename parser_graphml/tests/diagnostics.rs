@@ -0,0 +1,80 @@
+use parser_graphml::parser::*;
+
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+fn broken_quest() -> String {
+    PLAIN_NETWORKX
+        .replace(r#"<data key="d0">Стражник пропускает вас</data>"#, "")
+        .replace(
+            r#"<edge source="n0" target="n2">"#,
+            r#"<edge id="e1" source="n0" target="n9">"#,
+        )
+        .replace(
+            r#"    <node id="n2">"#,
+            "    <node id=\"n0\"><data key=\"d0\">Дубликат</data></node>\n    <node id=\"n2\">",
+        )
+}
+
+#[test]
+fn valid_quest_has_no_diagnostics() {
+    let diagnostics = read_graphml_diagnostics(PLAIN_NETWORKX);
+
+    assert!(diagnostics.errors.is_empty());
+    assert!(diagnostics.start.is_some());
+    assert_eq!(diagnostics.graph.node_count(), 3);
+}
+
+#[test]
+fn collects_every_problem_with_partial_graph() {
+    let diagnostics = read_graphml_diagnostics(&broken_quest());
+    let errors: Vec<String> = diagnostics.errors.iter().map(|x| x.to_string()).collect();
+
+    assert_eq!(
+        errors,
+        vec![
+            "у сцены нет подписи (`n1`, строка 11, столбец 5)",
+            "сцена с таким идентификатором уже есть (`n0`, строка 14, столбец 5)",
+            "не найдена сцена с идентификатором `n9` (`e1`, строка 21, столбец 5)",
+        ]
+    );
+    assert_eq!(diagnostics.graph.node_count(), 3);
+    assert_eq!(diagnostics.graph.edge_count(), 2);
+    assert!(diagnostics.start.is_some());
+}
+
+#[test]
+fn strict_mode_returns_first_problem() {
+    match read_graphml(&broken_quest()) {
+        Err(Error::PrepareGraphml(ErrorPrepareGraphML::NotFoundNodeLabel(location))) => {
+            assert_eq!(location.element_id, Some("n1".to_string()))
+        }
+        other => panic!("Expected missing node label, got {:?}", other),
+    }
+}
+
+#[test]
+fn renders_error_with_source_snippet() {
+    let source = broken_quest();
+    let diagnostics = read_graphml_diagnostics(&source);
+
+    assert_eq!(
+        render_error(&source, &diagnostics.errors[2]),
+        concat!(
+            "ошибка: не найдена сцена с идентификатором `n9`\n",
+            "  --> `e1`, строка 21, столбец 5\n",
+            "   |\n",
+            r#"21 |     <edge id="e1" source="n0" target="n9">"#,
+            "\n",
+            "   |     ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^\n",
+        )
+    );
+}
+
+#[test]
+fn renders_xml_error_position() {
+    let source = "<graphml>\n  <graph>\n</graphml>\n";
+    let diagnostics = read_graphml_diagnostics(source);
+
+    assert_eq!(diagnostics.errors.len(), 1);
+    assert!(render_error(source, &diagnostics.errors[0]).starts_with("ошибка: некорректный XML"));
+}
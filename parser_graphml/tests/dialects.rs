@@ -0,0 +1,95 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::edge_texts;
+
+const YFILES_HTML: &str = include_str!("fixtures/yfiles_html.graphml");
+const YED_DESKTOP: &str = include_str!("fixtures/yed_desktop.graphml");
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+fn detect(xml_doc: &str) -> Dialect {
+    Dialect::detect(&roxmltree::Document::parse(xml_doc).expect("Valid fixture"))
+}
+
+fn vertexes(quest: &Quest) -> Vec<(String, String)> {
+    let graph = &quest.graph;
+    graph
+        .node_indices()
+        .map(|ix| (graph[ix].id.clone(), graph[ix].text.clone()))
+        .collect()
+}
+
+fn edges(quest: &Quest) -> Vec<(String, String, String)> {
+    let mut edges = edge_texts(quest);
+    edges.sort();
+    edges
+}
+
+#[test]
+fn detects_yfiles_html() {
+    assert_eq!(detect(YFILES_HTML), Dialect::YFilesHtml);
+}
+
+#[test]
+fn detects_yed_desktop() {
+    assert_eq!(detect(YED_DESKTOP), Dialect::YedDesktop);
+}
+
+#[test]
+fn detects_yed_desktop_by_namespace() {
+    let without_comment = YED_DESKTOP.replace("<!--Created by yEd 3.20-->", "");
+    assert_eq!(detect(&without_comment), Dialect::YedDesktop);
+}
+
+#[test]
+fn detects_plain() {
+    assert_eq!(detect(PLAIN_NETWORKX), Dialect::Plain);
+}
+
+#[test]
+fn reads_yfiles_html() {
+    let quest = read_graphml(YFILES_HTML).expect("Parsed yFiles for HTML fixture");
+
+    assert_eq!(
+        vertexes(&quest),
+        vec![
+            ("n0".to_string(), "Вы стоите у ворот".to_string()),
+            ("n1".to_string(), "Стражник пропускает вас".to_string()),
+            ("n2".to_string(), "Вы уходите ни с чем".to_string()),
+        ]
+    );
+    assert_eq!(
+        edges(&quest),
+        vec![
+            ("n0".to_string(), "n1".to_string(), "Заплатить".to_string()),
+            ("n0".to_string(), "n2".to_string(), "Уйти".to_string()),
+            ("n1".to_string(), "n2".to_string(), "".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn reads_yed_desktop_same_as_yfiles_html() {
+    let html = read_graphml(YFILES_HTML).expect("Parsed yFiles for HTML fixture");
+    let desktop = read_graphml(YED_DESKTOP).expect("Parsed yEd desktop fixture");
+
+    assert_eq!(vertexes(&desktop), vertexes(&html));
+    assert_eq!(edges(&desktop), edges(&html));
+}
+
+#[test]
+fn reads_plain_same_as_yfiles_html() {
+    let html = read_graphml(YFILES_HTML).expect("Parsed yFiles for HTML fixture");
+    let plain = read_graphml(PLAIN_NETWORKX).expect("Parsed plain GraphML fixture");
+
+    assert_eq!(vertexes(&plain), vertexes(&html));
+    assert_eq!(edges(&plain), edges(&html));
+}
+
+#[test]
+fn reads_plain_text_key_by_fallback_name() {
+    let xml_doc = PLAIN_NETWORKX.replace(r#"attr.name="label""#, r#"attr.name="description""#);
+    let quest = read_graphml(&xml_doc).expect("Parsed plain GraphML with description keys");
+
+    assert_eq!(vertexes(&quest)[0].1, "Вы стоите у ворот");
+}
@@ -0,0 +1,279 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::{find_node, find_vertex, vertexes};
+
+const CHAPTERS: &str = include_str!("fixtures/chapters.graphml");
+
+const SKETCH: &str = r#"// Набросок квеста для проверки
+digraph "У ворот" {
+    node [shape=box];
+    edge [class="draft"];
+
+    gate [label="Вы стоите у ворот\n\nТемнеет.", class="start night"];
+    guard [label=<Стражник <b>смотрит</b> на вас>, tooltip="Заметка автора"];
+    gate -> guard [label="Постучать"];
+    gate -> forest [label="Уйти", id=leave];
+
+    subgraph cluster_city {
+        label="Город";
+        square; market [label="Рынок"]
+        square -> market [label = "Пройти" + " на рынок"];
+    }
+    guard -> square [label="Войти в город"];
+}
+"#;
+
+/// Сцены без данных, которые DOT не хранит.
+fn dot_vertexes(quest: &Quest) -> Vec<Vertex> {
+    vertexes(quest)
+        .into_iter()
+        .map(|x| Vertex {
+            translations: Default::default(),
+            ..x
+        })
+        .collect()
+}
+
+#[test]
+fn reads_labels_as_scenes_and_choices() {
+    let quest = read_dot(SKETCH).expect("Parsed quest");
+    let start = &quest.graph[quest.start];
+
+    assert_eq!(start.id, "gate");
+    assert_eq!(start.blocks_in(None), vec!["Вы стоите у ворот", "Темнеет."]);
+    assert_eq!(start.tags, vec!["start", "night"]);
+
+    let guard = find_vertex(&quest, "guard");
+    assert_eq!(guard.text, "Стражник <b>смотрит</b> на вас");
+    assert_eq!(guard.description.as_deref(), Some("Заметка автора"));
+    // Вершина без подписи называется своим именем, как в Graphviz
+    assert_eq!(find_vertex(&quest, "forest").text, "forest");
+
+    let edges = quest
+        .graph
+        .raw_edges()
+        .iter()
+        .map(|x| {
+            (
+                x.weight.id.as_str(),
+                x.weight.text.as_str(),
+                quest.graph[x.target()].id.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        edges,
+        vec![
+            ("e0", "Постучать", "guard"),
+            ("leave", "Уйти", "forest"),
+            ("e2", "Пройти на рынок", "market"),
+            ("e3", "Войти в город", "square"),
+        ]
+    );
+    assert!(quest
+        .graph
+        .raw_edges()
+        .iter()
+        .all(|x| x.weight.tags == vec!["draft"]));
+}
+
+#[test]
+fn reads_clusters_as_chapters() {
+    let quest = read_dot(SKETCH).expect("Parsed quest");
+
+    assert_eq!(quest.chapters.len(), 1);
+    let chapter = &quest.chapters[0];
+    assert_eq!(chapter.id, "city");
+    assert_eq!(chapter.title, "Город");
+    assert_eq!(
+        chapter.entry.map(|ix| quest.graph[ix].id.as_str()),
+        Some("square")
+    );
+    assert_eq!(find_vertex(&quest, "market").chapter, Some(0));
+    // `guard` не упоминается в кластере и остается вне главы
+    assert_eq!(find_vertex(&quest, "guard").chapter, None);
+}
+
+#[test]
+fn moves_node_into_innermost_cluster_mentioning_it() {
+    let quest = read_dot(
+        "digraph {
+            a; b; c; d
+            subgraph cluster_x {
+                a; b; c
+                subgraph cluster_y { b }
+            }
+            subgraph cluster_z { c; d }
+            d -> a -> b -> c
+        }",
+    )
+    .expect("Parsed quest");
+    let chapter = |id: &str| {
+        find_vertex(&quest, id)
+            .chapter
+            .map(|i| quest.chapters[i].id.as_str())
+    };
+
+    assert_eq!(chapter("a"), Some("x"));
+    assert_eq!(chapter("b"), Some("y"));
+    // Из соседних кластеров остается первый
+    assert_eq!(chapter("c"), Some("x"));
+    assert_eq!(chapter("d"), Some("z"));
+}
+
+#[test]
+fn writes_quest_back() {
+    for quest in &[
+        read_dot(SKETCH).expect("Parsed quest"),
+        read_graphml(CHAPTERS).expect("Parsed quest"),
+    ] {
+        let dot = write_dot(quest);
+        let read_back = read_dot(&dot).expect("Parsed written quest");
+
+        assert_eq!(dot_vertexes(&read_back), dot_vertexes(quest));
+        assert_eq!(
+            read_back
+                .graph
+                .raw_edges()
+                .iter()
+                .map(|x| (x.source(), x.target(), &x.weight))
+                .collect::<Vec<_>>(),
+            quest
+                .graph
+                .raw_edges()
+                .iter()
+                .map(|x| (x.source(), x.target(), &x.weight))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(read_back.start, quest.start);
+        assert_eq!(
+            read_back.chapters,
+            quest
+                .chapters
+                .iter()
+                .map(|x| Chapter {
+                    position: None,
+                    ..x.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn writes_graphviz_syntax() {
+    let quest = read_dot("digraph { a [label=\"Сказал \\\"да\\\"\"]; a -> b }").expect("Parsed");
+
+    assert_eq!(
+        write_dot(&quest),
+        concat!(
+            "digraph quest {\n",
+            "\tnode [shape=box, style=rounded];\n",
+            "\t\"a\" [label=\"Сказал \\\"да\\\"\", start=\"true\"];\n",
+            "\t\"b\" [label=\"b\"];\n",
+            "\t\"a\" -> \"b\" [id=\"e0\"];\n",
+            "}\n"
+        )
+    );
+}
+
+#[test]
+fn reports_errors() {
+    assert_eq!(
+        read_dot("digraph {\n  a -> [label=x]\n}").map(|_| ()),
+        Err(ErrorDot::Syntax("идентификатор", 2, 8))
+    );
+    assert_eq!(
+        read_dot("digraph { a; b }").map(|_| ()),
+        Err(ErrorDot::AmbiguousStartScene(vec![
+            "a".to_string(),
+            "b".to_string()
+        ]))
+    );
+    assert_eq!(
+        read_dot("digraph { quest_start=c; a -> b }").map(|_| ()),
+        Err(ErrorDot::NotFoundScene("c".to_string()))
+    );
+    assert_eq!(
+        read_dot("digraph { a -> b -> a }").map(|_| ()),
+        Err(ErrorDot::NotFoundStartScene)
+    );
+}
+
+#[test]
+fn leaves_graphviz_start_attribute_to_layout() {
+    // `start` графа - это начальное положение вершин при раскладке neato
+    let quest = read_dot("digraph { start=random; a -> b; c -> b }");
+    assert_eq!(
+        quest.map(|_| ()),
+        Err(ErrorDot::AmbiguousStartScene(vec![
+            "a".to_string(),
+            "c".to_string()
+        ]))
+    );
+
+    let quest = read_dot(
+        "digraph { start=self; quest_start=c; a -> b; c -> b; \
+         subgraph cluster_end { quest_start=b; b } }",
+    )
+    .expect("Parsed quest");
+    assert_eq!(quest.graph[quest.start].id, "c");
+    assert_eq!(
+        quest.chapters[0]
+            .entry
+            .map(|ix| quest.graph[ix].id.as_str()),
+        Some("b")
+    );
+
+    let dot = write_dot(&quest);
+    assert!(dot.contains("\t\"c\" [label=\"c\", start=\"true\"];\n"));
+    assert!(dot.contains("\t\tquest_start=\"b\";\n"));
+    assert!(!dot.contains("\tstart="));
+}
+
+#[test]
+fn round_trips_paragraphs() {
+    let mut quest = read_dot("digraph { a -> b }").expect("Parsed quest");
+    let start = quest.start;
+    quest.graph[start].text = "Вы стоите у ворот".to_string();
+    quest.graph[start].paragraphs = vec![
+        "Темнеет.".to_string(),
+        "Стражник зевает\nи смотрит на вас.".to_string(),
+    ];
+
+    let dot = write_dot(&quest);
+    assert!(dot.contains(
+        "label=\"Вы стоите у ворот\\n\\nТемнеет.\\n\\nСтражник зевает\\nи смотрит на вас.\""
+    ));
+
+    let read_back = read_dot(&dot).expect("Parsed written quest");
+    let gate = &read_back.graph[read_back.start];
+    assert_eq!(gate.text, "Вы стоите у ворот");
+    assert_eq!(gate.paragraphs, quest.graph[start].paragraphs);
+}
+
+#[test]
+fn writes_scene_of_missing_chapter_at_top_level() {
+    let mut quest = read_dot(SKETCH).expect("Parsed quest");
+    let market = find_node(&quest, "market");
+    quest.graph[market].chapter = Some(quest.chapters.len() + 10);
+
+    let read_back = read_dot(&write_dot(&quest)).expect("Parsed written quest");
+
+    assert_eq!(find_vertex(&read_back, "market").chapter, None);
+    assert_eq!(read_back.graph.edge_count(), quest.graph.edge_count());
+}
+
+#[test]
+fn reads_html_label_line_breaks_and_drops_layout_tags() {
+    let quest = read_dot(
+        r#"digraph { a [label=<Строка<br>вторая<BR />третья<BR ALIGN="LEFT"/>четвертая<TABLE><TR><TD>ячейка</TD></TR></TABLE> <B>жирно</B> <U>и</U> все>] }"#,
+    )
+    .expect("Parsed quest");
+
+    assert_eq!(
+        find_vertex(&quest, "a").text,
+        "Строка\nвторая\nтретья\nчетвертаяячейка <B>жирно</B> и все"
+    );
+}
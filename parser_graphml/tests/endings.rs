@@ -0,0 +1,150 @@
+use parser_graphml::parser::*;
+
+const SCENES_CHOICES: &str = include_str!("../../console-view/scenes-choices.graphml");
+
+const ENDINGS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+	<key id="d0" for="node" attr.type="string" attr.name="label"/>
+	<key id="d1" for="node" attr.type="string" attr.name="ending"/>
+	<key id="d2" for="node" attr.type="string" attr.name="tags"/>
+	<key id="d3" for="edge" attr.type="string" attr.name="label"/>
+	<graph id="G" edgedefault="directed">
+		<node id="gate"><data key="d0">Вы стоите у ворот</data></node>
+		<node id="throne"><data key="d0">Вас коронуют</data><data key="d1">good: Новый король</data></node>
+		<node id="prison"><data key="d0">Вас бросают в темницу</data><data key="d1">bad</data></node>
+		<node id="home"><data key="d0">Вы возвращаетесь домой</data><data key="d2">ending</data></node>
+		<node id="cellar"><data key="d0">Вы нашли тайный ход</data><data key="d2">night, ending:secret:Тайник</data></node>
+		<node id="forest"><data key="d0">Вы теряетесь в лесу</data><data key="d1">Заблудился</data></node>
+		<edge source="gate" target="throne"><data key="d3">Войти</data></edge>
+		<edge source="gate" target="prison"><data key="d3">Напасть</data></edge>
+		<edge source="gate" target="home"><data key="d3">Уйти</data></edge>
+		<edge source="gate" target="cellar"><data key="d3">Спуститься</data></edge>
+		<edge source="gate" target="forest"><data key="d3">Свернуть</data></edge>
+	</graph>
+</graphml>"#;
+
+fn endings(quest: &Quest) -> Vec<(&str, Option<Ending>)> {
+    quest
+        .graph
+        .raw_nodes()
+        .iter()
+        .map(|x| (x.weight.id.as_str(), x.weight.ending.clone()))
+        .collect()
+}
+
+fn ending(kind: EndingKind, title: Option<&str>) -> Option<Ending> {
+    Some(Ending {
+        kind,
+        title: title.map(String::from),
+    })
+}
+
+#[test]
+fn reads_endings_from_data_and_tags() {
+    let quest = read_graphml(ENDINGS).expect("Parsed quest");
+
+    assert_eq!(
+        endings(&quest),
+        vec![
+            ("gate", None),
+            ("throne", ending(EndingKind::Good, Some("Новый король"))),
+            ("prison", ending(EndingKind::Bad, None)),
+            ("home", ending(EndingKind::Neutral, None)),
+            ("cellar", ending(EndingKind::Secret, Some("Тайник"))),
+            ("forest", ending(EndingKind::Neutral, Some("Заблудился"))),
+        ]
+    );
+    // Концовкам не нужны варианты выбора
+    assert_eq!(validate(&quest), vec![]);
+}
+
+#[test]
+fn names_ending_for_player() {
+    let quest = read_graphml(ENDINGS).expect("Parsed quest");
+    let titles = quest
+        .graph
+        .raw_nodes()
+        .iter()
+        .filter_map(|x| x.weight.ending.as_ref())
+        .map(Ending::display_title)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        titles,
+        vec![
+            "Новый король",
+            "Плохая концовка",
+            "Концовка",
+            "Тайник",
+            "Заблудился"
+        ]
+    );
+}
+
+#[test]
+fn writes_endings_back() {
+    let quest = read_graphml(ENDINGS).expect("Parsed quest");
+    let generated = Quest {
+        source: None,
+        ..quest.clone()
+    };
+
+    for read_back in &[
+        read_graphml(&write_graphml(&generated)).expect("Parsed GraphML"),
+        read_quest_json(&write_quest_json(&quest)).expect("Parsed JSON"),
+        read_dot(&write_dot(&quest)).expect("Parsed DOT"),
+    ] {
+        assert_eq!(endings(read_back), endings(&quest));
+    }
+    // Концовка из тегов не дублируется отдельным полем
+    assert!(write_quest_json(&quest).contains(r#""type": "good""#));
+    assert!(!write_quest_json(&quest).contains(r#""type": "secret""#));
+}
+
+#[test]
+fn reads_endings_from_other_formats() {
+    let quest =
+        read_dot(r#"digraph { a -> b; b [ending="secret"]; a -> c; c [class="ending:bad"] }"#)
+            .expect("Parsed quest");
+    assert_eq!(
+        endings(&quest),
+        vec![
+            ("a", None),
+            ("b", ending(EndingKind::Secret, None)),
+            ("c", ending(EndingKind::Bad, None)),
+        ]
+    );
+
+    let Imported { quest, .. } =
+        read_twee(":: Start\nВы у ворот.\n[[Войти->Город]]\n\n:: Город [ending:good]\nКонец.\n")
+            .expect("Imported story");
+    assert_eq!(
+        endings(&quest),
+        vec![("Start", None), ("Город", ending(EndingKind::Good, None))]
+    );
+
+    assert!(matches!(
+        read_quest_json(
+            r#"{"version": 1, "start": "a", "scenes": [{"id": "a", "text": "", "ending": {"type": "happy"}}]}"#
+        ),
+        Err(ErrorJson::InvalidField(path, _)) if path == "scenes[0].ending.type"
+    ));
+}
+
+#[test]
+fn sample_quest_ends_in_endings() {
+    let quest = read_graphml(SCENES_CHOICES).expect("Parsed sample quest");
+
+    assert_eq!(
+        endings(&quest)[4..],
+        [
+            ("n4", ending(EndingKind::Good, Some("Вы добрались до цели"))),
+            ("n5", ending(EndingKind::Bad, None)),
+            ("n6", ending(EndingKind::Neutral, None)),
+            ("n7", ending(EndingKind::Secret, Some("Тайный выход"))),
+        ]
+    );
+    assert!(validate(&quest)
+        .iter()
+        .all(|x| x.severity != Severity::Error));
+}
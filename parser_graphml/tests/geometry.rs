@@ -0,0 +1,86 @@
+use parser_graphml::parser::*;
+
+const YFILES_HTML: &str = include_str!("fixtures/yfiles_html.graphml");
+const YED_DESKTOP: &str = include_str!("fixtures/yed_desktop.graphml");
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+const CHAPTERS: &str = include_str!("fixtures/chapters.graphml");
+
+fn position(quest: &Quest, id: &str) -> Option<Rect> {
+    quest
+        .graph
+        .node_indices()
+        .map(|ix| &quest.graph[ix])
+        .find(|x| x.id == id)
+        .expect("Scene exists")
+        .position
+}
+
+fn rect(x: f64, y: f64, width: f64, height: f64) -> Option<Rect> {
+    Some(Rect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+#[test]
+fn reads_yfiles_html_geometry() {
+    let quest = read_graphml(YFILES_HTML).expect("Parsed quest");
+
+    assert_eq!(position(&quest, "n0"), rect(100.0, 40.0, 240.0, 60.0));
+    assert_eq!(position(&quest, "n1"), rect(-60.0, 200.0, 240.0, 60.0));
+    assert_eq!(position(&quest, "n2"), rect(260.0, 200.0, 240.0, 60.0));
+}
+
+#[test]
+fn reads_yed_desktop_geometry() {
+    let quest = read_graphml(YED_DESKTOP).expect("Parsed quest");
+
+    assert_eq!(position(&quest, "n0"), rect(100.0, 40.0, 240.0, 60.0));
+    assert_eq!(
+        position(&quest, "n0").map(|x| x.center()),
+        Some((220.0, 70.0))
+    );
+}
+
+#[test]
+fn reads_plain_coordinates() {
+    let xml_doc = PLAIN_NETWORKX
+        .replace(
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />"#,
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />
+  <key id="x" for="node" attr.name="x" attr.type="double" />
+  <key id="y" for="node" attr.name="y" attr.type="double" />"#,
+        )
+        .replace(
+            r#"<data key="d0">Вы стоите у ворот</data>"#,
+            r#"<data key="d0">Вы стоите у ворот</data>
+      <data key="x">12.5</data>
+      <data key="y">-40</data>"#,
+        );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    assert_eq!(position(&quest, "n0"), rect(12.5, -40.0, 0.0, 0.0));
+    assert_eq!(position(&quest, "n1"), None);
+}
+
+#[test]
+fn reads_chapter_geometry() {
+    let quest = read_graphml(CHAPTERS).expect("Parsed quest");
+
+    assert_eq!(quest.chapters[0].position, rect(-30.0, 120.0, 600.0, 150.0));
+    assert_eq!(quest.chapters[1].position, None);
+}
+
+#[test]
+fn written_quest_keeps_authored_geometry() {
+    let mut quest = read_graphml(YED_DESKTOP).expect("Parsed quest");
+    quest.source = None;
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+
+    for id in &["n0", "n1", "n2"] {
+        assert_eq!(position(&read_back, id), position(&quest, id));
+    }
+}
@@ -0,0 +1,139 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::{choices, find_vertex};
+
+// Скомпилированная история:
+//
+// VAR gold = 5
+// -> gate
+// === gate ===
+// Вы стоите у ворот. #night
+// * [Постучать] -> guard
+// * Уйти
+//     Вы уходите в лес.
+//     -> END
+// === guard ===
+// = talk
+// Стражник смотрит на вас.
+// ~ gold = gold - 1
+// * [Заплатить] -> city
+// * [Драться] -> fight ->
+// - -> city
+// === fight ===
+// Вы деретесь.
+// ->->
+// === city ===
+// Вы в городе.
+// -> END
+const GATE: &str = r##"{"inkVersion":21,"root":[[{"->":"gate"},["done",{"#f":5,"#n":"g-0"}],null],"done",{
+"gate":[["^Вы стоите у ворот. ","#","^night","/#","\n","ev","str","^Постучать","/str","/ev",{"*":".^.c-0","flg":20},["ev",{"^->":"gate.0.11.$r1"},{"temp=":"$r"},"str",{"->":".^.s"},[{"#n":"$r1"}],"/str","/ev",{"*":".^.^.c-1","flg":18},{"s":["^Уйти",{"->":"$r","var":true},null]}],{"c-0":["\n",{"->":"guard"},{"#f":5}],"c-1":["ev",{"^->":"gate.0.c-1.$r2"},"/ev",{"temp=":"$r"},{"->":".^.^.11.s"},[{"#n":"$r2"}],"\n","^Вы уходите в лес.","\n","end",{"#f":5}]}],{"#f":1}],
+"guard":[{"->":".^.talk"},{"talk":[["^Стражник смотрит на вас.","\n","ev",{"VAR?":"gold"},1,"-","/ev",{"VAR=":"gold","re":true},"ev","str","^Заплатить","/str","/ev",{"*":".^.c-0","flg":20},"ev","str","^Драться","/str","/ev",{"*":".^.c-1","flg":20},{"c-0":["\n",{"->":"city"},{"#f":5}],"c-1":["\n",{"->t->":"fight"},{"->":".^.^.g-0"},{"#f":5}],"g-0":[{"->":"city"},null]}],{"#f":1}],"#f":1}],
+"fight":["^Вы деретесь.","\n","->->",{"#f":1}],
+"city":["^Вы в городе.","\n","end",{"#f":1}],
+"global decl":["ev",5,{"VAR=":"gold"},"/ev","end",null],
+"#f":1}],"listDefs":{}}"##;
+
+#[test]
+fn reads_knots_stitches_and_choices() {
+    let Imported { quest, .. } = read_ink_json(GATE).expect("Imported story");
+    let start = &quest.graph[quest.start];
+
+    assert_eq!(start.id, "gate");
+    assert_eq!(start.text, "Вы стоите у ворот.");
+    assert_eq!(start.tags, vec!["night"]);
+    assert_eq!(
+        choices(&quest, "gate"),
+        vec![
+            ("Постучать".to_string(), "guard.talk".to_string()),
+            ("Уйти".to_string(), "gate.0.c-1".to_string()),
+        ]
+    );
+    // Драться ведет через сборку, которая сразу переходит в город
+    assert_eq!(
+        choices(&quest, "guard.talk"),
+        vec![
+            ("Драться".to_string(), "city".to_string()),
+            ("Заплатить".to_string(), "city".to_string()),
+        ]
+    );
+    assert_eq!(quest.graph.node_count(), 4);
+}
+
+#[test]
+fn keeps_text_after_choice_and_marks_endings() {
+    let Imported { quest, .. } = read_ink_json(GATE).expect("Imported story");
+    let leave = find_vertex(&quest, "gate.0.c-1");
+
+    // Текст варианта `* Уйти` не повторяется в сцене после выбора
+    assert_eq!(leave.text, "Вы уходите в лес.");
+    assert!(leave.has_tag("ending"));
+    assert!(find_vertex(&quest, "city").has_tag("ending"));
+    assert!(!find_vertex(&quest, "gate").has_tag("ending"));
+}
+
+#[test]
+fn reports_unsupported_features() {
+    let Imported { warnings, .. } = read_ink_json(GATE).expect("Imported story");
+
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|x| (x.element.as_str(), x.message.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("global decl", "переменная `gold` не поддерживается"),
+            ("guard.talk", "переменная `gold` не поддерживается"),
+            ("guard.talk.0.c-1", "туннель в `fight` не поддерживается"),
+        ]
+    );
+    assert_eq!(
+        warnings[2].to_string(),
+        "предупреждение: туннель в `fight` не поддерживается (`guard.talk.0.c-1`)"
+    );
+}
+
+#[test]
+fn reports_threads_and_conditions() {
+    let story = GATE
+        .replace(
+            r#"{"->":"guard"}"#,
+            r#""thread",{"->":"city"},{"->":"guard"}"#,
+        )
+        .replace(r#""flg":18"#, r#""flg":19"#);
+    let Imported { quest, warnings } = read_ink_json(&story).expect("Imported story");
+    let messages = warnings
+        .iter()
+        .map(|x| (x.element.as_str(), x.message.as_str()))
+        .collect::<Vec<_>>();
+
+    assert!(messages.contains(&("gate.0.c-0", "потоки (`<-`) не поддерживаются")));
+    assert_eq!(
+        choices(&quest, "gate"),
+        vec![
+            ("Постучать".to_string(), "guard.talk".to_string()),
+            ("Уйти".to_string(), "gate.0.c-1".to_string()),
+        ]
+    );
+    assert!(messages.contains(&(
+        "gate",
+        "условия не поддерживаются, вариант `Уйти` показывается всегда"
+    )));
+}
+
+#[test]
+fn reports_invalid_story() {
+    assert!(matches!(
+        read_ink_json(&GATE.replace(r#""inkVersion":21"#, r#""inkVersion":10"#)),
+        Err(ErrorInk::UnsupportedVersion(10))
+    ));
+    assert!(matches!(
+        read_ink_json(r#"{"inkVersion":21}"#),
+        Err(ErrorInk::MissingField("root"))
+    ));
+    assert!(matches!(read_ink_json("{"), Err(ErrorInk::Parse(_))));
+
+    let error = read_ink_json(&GATE.replace(r#"{"->":"city"}"#, r#"{"->":"town"}"#))
+        .expect_err("Unknown knot");
+    assert_eq!(error.to_string(), "не найден путь `town`");
+}
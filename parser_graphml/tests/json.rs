@@ -0,0 +1,223 @@
+use parser_graphml::parser::*;
+
+const SCENES_CHOICES: &str = include_str!("../../console-view/scenes-choices.graphml");
+const CHAPTERS: &str = include_str!("fixtures/chapters.graphml");
+const SCHEMA: &str = include_str!("../schema/quest.schema.json");
+
+const GATE: &str = r#"{
+  "version": 1,
+  "locale": "ru",
+  "start": "gate",
+  "scenes": [
+    {
+      "id": "gate",
+      "text": "Вы стоите у ворот",
+      "paragraphs": ["Темнеет."],
+      "tags": ["start"],
+      "translations": {
+        "en": ["You stand at the gate", "It is getting dark."]
+      }
+    },
+    {
+      "id": "city",
+      "text": "Стражник пропускает вас",
+      "description": "Хорошая концовка",
+      "position": {"x": 100.0, "y": 40.0, "width": 240.0, "height": 60.0}
+    }
+  ],
+  "choices": [
+    {
+      "id": "pay",
+      "from": "gate",
+      "to": "city",
+      "text": "Заплатить",
+      "translations": {"en": "Pay"}
+    }
+  ]
+}
+"#;
+
+fn assert_same_quest(actual: &Quest, expected: &Quest) {
+    assert_eq!(actual.start, expected.start);
+    assert_eq!(actual.chapters, expected.chapters);
+    assert_eq!(actual.locale, expected.locale);
+    assert_eq!(
+        actual
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|x| &x.weight)
+            .collect::<Vec<_>>(),
+        expected
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|x| &x.weight)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        actual
+            .graph
+            .raw_edges()
+            .iter()
+            .map(|x| (x.source(), x.target(), &x.weight))
+            .collect::<Vec<_>>(),
+        expected
+            .graph
+            .raw_edges()
+            .iter()
+            .map(|x| (x.source(), x.target(), &x.weight))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn reads_scenes_choices_and_metadata() {
+    let quest = read_quest_json(GATE).expect("Parsed quest");
+    let gate = &quest.graph[quest.start];
+
+    assert_eq!(gate.id, "gate");
+    assert_eq!(
+        gate.blocks_in(Some("en")),
+        vec!["You stand at the gate", "It is getting dark."]
+    );
+    assert_eq!(quest.locale.as_deref(), Some("ru"));
+    assert!(quest.source.is_none());
+
+    let choice = quest.graph.raw_edges().first().expect("Choice");
+    assert_eq!(
+        quest.graph[choice.target()].description.as_deref(),
+        Some("Хорошая концовка")
+    );
+    assert_eq!(choice.weight.text_in(Some("en")), "Pay");
+}
+
+#[test]
+fn writes_read_quest_back() {
+    let quest = read_quest_json(GATE).expect("Parsed quest");
+
+    let written = write_quest_json(&quest);
+
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&written).expect("Written quest is JSON"),
+        serde_json::from_str::<serde_json::Value>(GATE).expect("Source quest is JSON")
+    );
+    // Поля пишутся в порядке схемы, чтобы файлы было удобно сравнивать
+    assert!(
+        written.starts_with("{\n  \"version\": 1,\n  \"locale\": \"ru\",\n  \"start\": \"gate\",")
+    );
+}
+
+#[test]
+fn converts_graphml_losslessly() {
+    for xml_doc in &[SCENES_CHOICES, CHAPTERS] {
+        let quest = read_graphml(xml_doc).expect("Parsed quest");
+        let json = write_quest_json(&quest);
+        let read_back = read_quest_json(&json).expect("Parsed written quest");
+
+        assert_same_quest(&read_back, &quest);
+        assert_eq!(write_quest_json(&read_back), json);
+    }
+}
+
+#[test]
+fn reports_path_of_invalid_value() {
+    let error = read_quest_json(&GATE.replace(r#""text": "Заплатить""#, r#""text": 5"#))
+        .expect_err("Invalid choice text");
+    assert!(matches!(&error, ErrorJson::InvalidField(path, _) if path == "choices[0].text"));
+
+    let error = read_quest_json(&GATE.replace(r#""to": "city""#, r#""to": "forest""#))
+        .expect_err("Unknown scene");
+    assert_eq!(
+        error.to_string(),
+        "не найдена сцена `forest` (`choices[0].to`)"
+    );
+
+    let error = read_quest_json(&GATE.replace(r#""id": "city""#, r#""id": "gate""#))
+        .expect_err("Duplicate scene");
+    assert!(
+        matches!(&error, ErrorJson::DuplicateId(path, id) if path == "scenes[1].id" && id == "gate")
+    );
+
+    let error = read_quest_json(&GATE.replace(r#""version": 1"#, r#""version": 2"#))
+        .expect_err("Unsupported version");
+    assert!(matches!(error, ErrorJson::UnsupportedVersion(2)));
+
+    assert!(matches!(
+        read_quest_json("{ \"start\": "),
+        Err(ErrorJson::Parse(_))
+    ));
+}
+
+#[test]
+fn schema_lists_written_fields() {
+    let schema: serde_json::Value = serde_json::from_str(SCHEMA).expect("Schema is JSON");
+    let written: serde_json::Value = serde_json::from_str(&write_quest_json(
+        &read_graphml(CHAPTERS).expect("Parsed quest"),
+    ))
+    .expect("Written quest is JSON");
+
+    let properties = |path: &str| {
+        schema
+            .pointer(path)
+            .and_then(|x| x.as_object())
+            .expect("Schema properties")
+            .clone()
+    };
+    let assert_described = |value: &serde_json::Value, path: &str| {
+        let properties = properties(path);
+        for name in value.as_object().expect("Object").keys() {
+            assert!(properties.contains_key(name), "{} is not in {}", name, path);
+        }
+    };
+
+    assert_described(&written, "/properties");
+    for (array, definition) in &[
+        ("chapters", "chapter"),
+        ("scenes", "scene"),
+        ("choices", "choice"),
+    ] {
+        for x in written[array].as_array().expect("Array") {
+            assert_described(x, &format!("/definitions/{}/properties", definition));
+        }
+    }
+}
+
+#[test]
+fn rejects_chapter_parent_cycles() {
+    let quest = |chapters: &str| {
+        read_quest_json(&format!(
+            r#"{{"start": "a", "chapters": {},
+                "scenes": [{{"id": "a", "text": "Вы у ворот", "chapter": "c1"}}]}}"#,
+            chapters
+        ))
+    };
+
+    let error = quest(r#"[{"id": "c1", "parent": "c2"}, {"id": "c2", "parent": "c1"}]"#)
+        .expect_err("Cyclic chapters");
+    assert_eq!(
+        error.to_string(),
+        "глава `c1` вложена сама в себя (`chapters[0].parent`)"
+    );
+    assert!(matches!(
+        quest(r#"[{"id": "c1", "parent": "c1"}]"#),
+        Err(ErrorJson::ChapterCycle(path, id)) if path == "chapters[0].parent" && id == "c1"
+    ));
+
+    let quest = quest(r#"[{"id": "c1", "parent": "c2"}, {"id": "c2"}]"#).expect("Nested chapters");
+    assert!(quest.is_in_chapter(quest.start, 1));
+}
+
+#[test]
+fn chapter_lookup_stops_on_parent_cycle() {
+    let mut quest = read_quest_json(
+        r#"{"start": "a", "chapters": [{"id": "c1", "parent": "c2"}, {"id": "c2"}, {"id": "c3"}],
+            "scenes": [{"id": "a", "text": "Вы у ворот", "chapter": "c1"}]}"#,
+    )
+    .expect("Parsed quest");
+    // Квест, собранный в коде или десериализованный, может обойти проверку чтения
+    quest.chapters[1].parent = Some(0);
+
+    assert!(quest.is_in_chapter(quest.start, 1));
+    assert!(!quest.is_in_chapter(quest.start, 2));
+}
@@ -0,0 +1,124 @@
+use parser_graphml::parser::*;
+use roxmltree::Document;
+
+const KEYS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:y="http://www.yworks.com/xml/yfiles-common/3.0">
+	<key id="d0" for="node" attr.type="string" attr.name="label"/>
+	<key id="d1" for="edge" attr.type="string" attr.name="label"/>
+	<key id="d2" for="node" attr.name="NodeLabels" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/NodeLabels"/>
+	<key id="d3" for="all" attr.name="UserTags" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/UserTags"/>
+	<key id="d4" for="node" yfiles.type="nodegraphics"/>
+	<key id="d5" for="graph" attr.type="string" attr.name="start">
+		<default> n1 </default>
+	</key>
+	<key id="d6" for="node" attr.type="string" attr.name="label"/>
+	<key id="d0" for="edge" attr.type="string" attr.name="description"/>
+	<key for="node" attr.type="string" attr.name="url"/>
+	<graph id="G" edgedefault="directed"/>
+</graphml>"#;
+
+fn with_keys<T>(check: impl FnOnce(&KeyTable) -> T) -> T {
+    let doc = Document::parse(KEYS).expect("Parsed XML");
+    check(&KeyTable::from_document(&doc))
+}
+
+fn found_id(key: Option<&GraphMLKey>) -> Option<&str> {
+    key.map(|x| x.id.as_str())
+}
+
+#[test]
+fn reads_key_declarations() {
+    with_keys(|keys| {
+        // Ключ без `id` пропускается
+        assert_eq!(keys.keys().len(), 8);
+
+        let start = keys.get("d5").expect("Declared key");
+        assert_eq!(start.domain, KeyDomain::Graph);
+        assert_eq!(start.name.as_deref(), Some("start"));
+        assert_eq!(start.attr_type.as_deref(), Some("string"));
+        assert_eq!(start.default.as_deref(), Some("n1"));
+    });
+}
+
+#[test]
+fn finds_key_by_attr_name_in_domain() {
+    with_keys(|keys| {
+        assert_eq!(
+            found_id(keys.find_by_name(KeyDomain::Node, "label")),
+            Some("d0")
+        );
+        assert_eq!(
+            found_id(keys.find_by_name(KeyDomain::Edge, "label")),
+            Some("d1")
+        );
+        assert_eq!(found_id(keys.find_by_name(KeyDomain::Graph, "label")), None);
+    });
+}
+
+#[test]
+fn finds_key_by_uri_suffix() {
+    with_keys(|keys| {
+        let labels = keys
+            .find_by_name(KeyDomain::Node, "NodeLabels")
+            .expect("Found key");
+        assert_eq!(labels.id, "d2");
+        assert!(labels.has_name("NodeLabels"));
+        assert!(!labels.has_name("yfiles-common"));
+
+        // Ключ с `for="all"` применим к любым элементам
+        assert_eq!(
+            found_id(keys.find_by_name(KeyDomain::Edge, "UserTags")),
+            Some("d3")
+        );
+        assert_eq!(
+            found_id(keys.find_by_name(KeyDomain::Graph, "UserTags")),
+            Some("d3")
+        );
+    });
+}
+
+#[test]
+fn finds_key_by_yfiles_type() {
+    with_keys(|keys| {
+        assert_eq!(
+            found_id(keys.find_by_yfiles_type(KeyDomain::Node, "nodegraphics")),
+            Some("d4")
+        );
+        assert_eq!(
+            found_id(keys.find_by_yfiles_type(KeyDomain::Edge, "nodegraphics")),
+            None
+        );
+    });
+}
+
+#[test]
+fn unknown_key_is_not_found() {
+    with_keys(|keys| {
+        assert_eq!(found_id(keys.get("d42")), None);
+        assert_eq!(found_id(keys.find_by_name(KeyDomain::Node, "color")), None);
+        assert_eq!(
+            found_id(keys.find_by_yfiles_type(KeyDomain::Node, "edgegraphics")),
+            None
+        );
+    });
+}
+
+#[test]
+fn first_declaration_wins_for_duplicate_keys() {
+    with_keys(|keys| {
+        // Два ключа с именем `label` для сцен - берется объявленный раньше
+        assert_eq!(
+            found_id(keys.find_by_name(KeyDomain::Node, "label")),
+            Some("d0")
+        );
+
+        // Повторный `id` не подменяет первое объявление
+        let key = keys.get("d0").expect("Declared key");
+        assert_eq!(key.domain, KeyDomain::Node);
+        assert_eq!(key.name.as_deref(), Some("label"));
+        assert_eq!(
+            found_id(keys.find_by_name(KeyDomain::Edge, "description")),
+            Some("d0")
+        );
+    });
+}
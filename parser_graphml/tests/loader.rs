@@ -0,0 +1,166 @@
+use parser_graphml::parser::*;
+
+const SCENES_CHOICES: &str = include_str!("../../console-view/scenes-choices.graphml");
+const TWEE: &str = ":: Start\nВы у ворот.\n[[Войти->Город]]\n\n:: Город\nКонец.\n";
+const INK: &str = r##"{"inkVersion":21,"root":[[{"->":"gate"},null],"done",{"gate":["^Ворота.","\n","end",{"#f":1}],"#f":1}]}"##;
+const DOT: &str = "/* набросок */\ndigraph { a -> b [label=\"Войти\"] }";
+
+fn detected<'a>(loaders: &'a QuestLoaders, file_name: &str, text: &str) -> Option<&'a str> {
+    loaders
+        .detect(file_name, text.as_bytes())
+        .ok()
+        .map(|x| x.name())
+}
+
+#[test]
+fn detects_format_by_extension_and_content() {
+    let loaders = QuestLoaders::default();
+    let quest_json = write_quest_json(&read_graphml(SCENES_CHOICES).expect("Parsed quest"));
+
+    assert_eq!(
+        detected(&loaders, "quest.GraphML", SCENES_CHOICES),
+        Some("GraphML")
+    );
+    assert_eq!(detected(&loaders, "story.tw", TWEE), Some("истории Twee"));
+    assert_eq!(detected(&loaders, "sketch.gv", DOT), Some("Graphviz DOT"));
+    // Оба JSON формата используют расширение `.json`
+    assert_eq!(
+        detected(&loaders, "story.ink.json", INK),
+        Some("истории Ink")
+    );
+    assert_eq!(
+        detected(&loaders, "quest.json", &quest_json),
+        Some("JSON квеста")
+    );
+    // Без расширения формат определяется по содержимому
+    assert_eq!(detected(&loaders, "quest", SCENES_CHOICES), Some("GraphML"));
+    assert_eq!(detected(&loaders, "story.txt", TWEE), Some("истории Twee"));
+    assert_eq!(detected(&loaders, "sketch", DOT), Some("Graphviz DOT"));
+    assert_eq!(detected(&loaders, "notes.txt", "просто текст"), None);
+}
+
+#[test]
+fn loads_every_builtin_format() {
+    let loaders = QuestLoaders::default();
+    let graphml = read_graphml(SCENES_CHOICES).expect("Parsed quest");
+
+    for (file_name, text, start) in &[
+        ("scenes-choices.graphml", SCENES_CHOICES.to_string(), "n0"),
+        ("quest.json", write_quest_json(&graphml), "n0"),
+        ("story.json", INK.to_string(), "gate"),
+        ("story.twee", TWEE.to_string(), "Start"),
+        ("sketch.dot", DOT.to_string(), "a"),
+    ] {
+        let Imported { quest, .. } = loaders
+            .load(file_name, text.as_bytes())
+            .unwrap_or_else(|e| panic!("{}: {}", file_name, e));
+        assert_eq!(quest.graph[quest.start].id, *start, "{}", file_name);
+    }
+}
+
+#[test]
+fn loads_quest_json_without_version() {
+    let json = r#"{"start": "gate", "scenes": [{"id": "gate", "text": "Вы у ворот"}]}"#;
+
+    let Imported { quest, .. } = QuestLoaders::default()
+        .load("quest.json", json.as_bytes())
+        .expect("Loaded quest");
+    assert_eq!(quest.graph[quest.start].text, "Вы у ворот");
+
+    // Сцены могут идти после длинного списка глав, за началом файла
+    let chapters = (0..100)
+        .map(|i| format!(r#"{{"id": "c{}"}}"#, i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let json = json.replacen('{', &format!(r#"{{"chapters": [{}], "#, chapters), 1);
+    assert_eq!(
+        detected(&QuestLoaders::default(), "quest.json", &json),
+        Some("JSON квеста")
+    );
+}
+
+#[test]
+fn reports_load_errors() {
+    let loaders = QuestLoaders::default();
+
+    assert!(matches!(
+        loaders.load("notes.txt", "просто текст".as_bytes()),
+        Err(ErrorLoad::UnknownFormat(name)) if name == "notes.txt"
+    ));
+    assert!(matches!(
+        loaders.load("quest.graphml", b"<graphml>\xFF</graphml>"),
+        Err(ErrorLoad::Encoding(_))
+    ));
+
+    let broken = SCENES_CHOICES.replace(r#"target="n1""#, r#"target="n9""#);
+    let error = loaders
+        .load("quest.graphml", broken.as_bytes())
+        .expect_err("Unknown target");
+    assert!(error
+        .to_string()
+        .starts_with("ошибка чтения GraphML: \nошибка: не найдена сцена с идентификатором `n9`"));
+    assert!(matches!(
+        loaders.load("story.json", b"[1, 2]"),
+        Err(ErrorLoad::AmbiguousFormat(name, formats))
+            if name == "story.json" && formats == ["истории Ink", "JSON квеста"]
+    ));
+    assert!(matches!(
+        loaders.load("sketch.dot", b"digraph {"),
+        Err(ErrorLoad::Format("Graphviz DOT", _))
+    ));
+}
+
+/// Загрузчик простого текстового формата: строка - сцена, следующая строка - выбор.
+struct LinesLoader;
+
+impl QuestLoader for LinesLoader {
+    fn name(&self) -> &'static str {
+        "строки"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt", "dot"]
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(b"lines:")
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<Imported, ErrorLoad> {
+        let text = decode_text(bytes)?;
+        let dot = text
+            .lines()
+            .skip(1)
+            .enumerate()
+            .map(|(i, x)| format!("s{} [label=\"{}\"]; s{} -> s{};", i, x, i, i + 1))
+            .collect::<String>();
+        QuestLoaders::default().load("lines.dot", format!("digraph {{ {} }}", dot).as_bytes())
+    }
+}
+
+#[test]
+fn registers_custom_loader() {
+    let mut loaders = QuestLoaders::default();
+    loaders.register(LinesLoader);
+
+    assert_eq!(
+        detected(&loaders, "quest.txt", "lines:\nВорота"),
+        Some("строки")
+    );
+    // Новый загрузчик проверяется раньше встроенного с тем же расширением
+    assert_eq!(
+        detected(&loaders, "quest.dot", "lines:\nВорота"),
+        Some("строки")
+    );
+    // но среди них выбирается тот, кто узнал содержимое
+    assert_eq!(detected(&loaders, "quest.dot", DOT), Some("Graphviz DOT"));
+    assert_eq!(
+        loaders.extensions(),
+        vec!["txt", "dot", "gv", "html", "htm", "twee", "tw", "json", "graphml", "xml"]
+    );
+
+    let Imported { quest, .. } = loaders
+        .load("quest.txt", "lines:\nВорота\nГород".as_bytes())
+        .expect("Loaded quest");
+    assert_eq!(quest.graph[quest.start].text, "Ворота");
+}
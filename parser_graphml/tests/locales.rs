@@ -0,0 +1,149 @@
+use parser_graphml::parser::*;
+use std::collections::BTreeMap;
+
+mod common;
+use common::find_vertex;
+
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+const BILINGUAL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!--Created by yFiles for HTML 2.2.0.2-->
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:y="http://www.yworks.com/xml/yfiles-common/3.0" xmlns:x="http://www.yworks.com/xml/yfiles-common/markup/3.0">
+	<key id="d3" for="node" attr.name="NodeLabels" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/NodeLabels"/>
+	<key id="d10" for="edge" attr.name="EdgeLabels" y:attr.uri="http://www.yworks.com/xml/yfiles-common/2.0/EdgeLabels"/>
+	<key id="d20" for="node" attr.type="string" attr.name="lang"/>
+	<key id="d21" for="graph" attr.type="string" attr.name="lang"/>
+	<graph id="G" edgedefault="directed">
+		<data key="d21">ru</data>
+		<node id="n0">
+			<data key="d3"><x:List>
+				<y:Label><y:Label.Text>Вы стоите у ворот</y:Label.Text></y:Label>
+				<y:Label><y:Label.Text>Темнеет.</y:Label.Text></y:Label>
+				<y:Label><y:Label.Text>[en] You stand at the gate</y:Label.Text></y:Label>
+				<y:Label><y:Label.Text>[en] It is getting dark.</y:Label.Text></y:Label>
+			</x:List></data>
+			<data key="d20">{"de": ["Sie stehen am Tor", "Es wird dunkel."], "en": "ignored"}</data>
+		</node>
+		<node id="n1">
+			<data key="d3"><x:List><y:Label><y:Label.Text>Конец</y:Label.Text></y:Label></x:List></data>
+		</node>
+		<edge id="e0" source="n0" target="n1">
+			<data key="d10"><x:List>
+				<y:Label><y:Label.Text>Войти</y:Label.Text></y:Label>
+				<y:Label><y:Label.Text>[en-US] Enter</y:Label.Text></y:Label>
+			</x:List></data>
+		</edge>
+	</graph>
+</graphml>"#;
+
+#[test]
+fn reads_tagged_labels_and_lang_key() {
+    let quest = read_graphml(BILINGUAL).expect("Parsed quest");
+    let start = find_vertex(&quest, "n0");
+
+    assert_eq!(quest.locale.as_deref(), Some("ru"));
+    assert_eq!(start.paragraphs, vec!["Темнеет."]);
+    assert_eq!(
+        start.translations,
+        vec![
+            (
+                "de".to_string(),
+                vec![
+                    "Sie stehen am Tor".to_string(),
+                    "Es wird dunkel.".to_string()
+                ]
+            ),
+            (
+                "en".to_string(),
+                vec![
+                    "You stand at the gate".to_string(),
+                    "It is getting dark.".to_string()
+                ]
+            ),
+        ]
+        .into_iter()
+        .collect::<BTreeMap<_, _>>()
+    );
+    assert_eq!(find_vertex(&quest, "n1").translations, BTreeMap::new());
+    assert_eq!(quest.locales(), vec!["de", "en", "en-US"]);
+}
+
+#[test]
+fn falls_back_to_default_text() {
+    let quest = read_graphml(BILINGUAL).expect("Parsed quest");
+    let start = find_vertex(&quest, "n0");
+    let end = find_vertex(&quest, "n1");
+    let choice = &quest.graph[quest.graph.edge_indices().next().expect("Choice")];
+
+    assert_eq!(
+        start.blocks_in(Some("en")),
+        vec!["You stand at the gate", "It is getting dark."]
+    );
+    assert_eq!(start.blocks_in(None), vec!["Вы стоите у ворот", "Темнеет."]);
+    assert_eq!(end.blocks_in(Some("en")), vec!["Конец"]);
+    assert_eq!(choice.text_in(Some("en")), "Enter");
+    assert_eq!(choice.text_in(Some("de")), "Войти");
+}
+
+#[test]
+fn picks_locale_with_fallback() {
+    let quest = read_graphml(BILINGUAL).expect("Parsed quest");
+
+    assert_eq!(quest.pick_locale("de_DE.UTF-8"), Some("de".to_string()));
+    assert_eq!(quest.pick_locale("en-US"), Some("en-US".to_string()));
+    assert_eq!(quest.pick_locale("en-GB"), Some("en".to_string()));
+    assert_eq!(quest.pick_locale("ru_RU.UTF-8"), None);
+    assert_eq!(quest.pick_locale("fr"), None);
+}
+
+#[test]
+fn reads_plain_lang_key() {
+    let xml_doc = PLAIN_NETWORKX
+        .replace(
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />"#,
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />
+  <key id="d2" for="edge" attr.name="lang" attr.type="string" />"#,
+        )
+        .replace(
+            r#"<data key="d1">Уйти</data>"#,
+            r#"<data key="d1">Уйти</data>
+      <data key="d2">{"en": "Leave"}</data>"#,
+        );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    assert_eq!(quest.locale, None);
+    assert!(quest
+        .graph
+        .edge_indices()
+        .any(|ix| quest.graph[ix].text_in(Some("en")) == "Leave"));
+}
+
+#[test]
+fn written_quest_keeps_translations() {
+    let mut quest = read_graphml(BILINGUAL).expect("Parsed quest");
+    quest.source = None;
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+
+    assert_eq!(read_back.locale, quest.locale);
+    assert_eq!(
+        find_vertex(&read_back, "n0").translations,
+        find_vertex(&quest, "n0").translations
+    );
+    assert_eq!(read_back.locales(), quest.locales());
+}
+
+#[test]
+fn keeps_bracketed_names_in_text() {
+    let xml_doc = BILINGUAL
+        .replace("[en] It is getting dark.", "[Bob] Hi there")
+        .replace("[en-US] Enter", "[ok] Enter");
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+    let start = find_vertex(&quest, "n0");
+    let choice = &quest.graph[quest.graph.edge_indices().next().expect("Choice")];
+
+    assert_eq!(start.paragraphs, vec!["Темнеет.", "[Bob] Hi there"]);
+    assert_eq!(start.blocks_in(Some("en")), vec!["You stand at the gate"]);
+    assert_eq!(choice.text_in(Some("ok")), "Войти");
+    assert_eq!(quest.locales(), vec!["de", "en"]);
+}
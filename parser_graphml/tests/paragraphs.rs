@@ -0,0 +1,76 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::find_vertex;
+
+const YFILES_HTML: &str = include_str!("fixtures/yfiles_html.graphml");
+const YED_DESKTOP: &str = include_str!("fixtures/yed_desktop.graphml");
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+#[test]
+fn reads_yfiles_html_labels_in_order() {
+    let xml_doc = YFILES_HTML.replacen(
+        "</y:Label>",
+        "</y:Label>
+					<y:Label><y:Label.Text>Стражник: Стой, кто идет?</y:Label.Text></y:Label>
+					<y:Label><y:Label.Text></y:Label.Text></y:Label>
+					<y:Label><y:Label.Text>Ворота закрыты.</y:Label.Text></y:Label>",
+        1,
+    );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    let scene = find_vertex(&quest, "n0");
+    assert_eq!(scene.text, "Вы стоите у ворот");
+    assert_eq!(
+        scene.paragraphs,
+        vec!["Стражник: Стой, кто идет?", "Ворота закрыты."]
+    );
+    assert_eq!(
+        scene.blocks().collect::<Vec<_>>(),
+        vec![
+            "Вы стоите у ворот",
+            "Стражник: Стой, кто идет?",
+            "Ворота закрыты."
+        ]
+    );
+    assert!(find_vertex(&quest, "n1").paragraphs.is_empty());
+}
+
+#[test]
+fn reads_yed_desktop_labels_in_order() {
+    let xml_doc = YED_DESKTOP.replacen(
+        "<y:Shape type=\"rectangle\"/>",
+        "<y:NodeLabel>Стражник: Стой, кто идет?</y:NodeLabel>\n          <y:Shape type=\"rectangle\"/>",
+        1,
+    );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+
+    let scene = find_vertex(&quest, "n0");
+    assert_eq!(scene.text, "Вы стоите у ворот");
+    assert_eq!(scene.paragraphs, vec!["Стражник: Стой, кто идет?"]);
+}
+
+#[test]
+fn plain_graphml_has_single_block() {
+    let quest = read_graphml(PLAIN_NETWORKX).expect("Parsed quest");
+
+    assert_eq!(
+        find_vertex(&quest, "n0").blocks().collect::<Vec<_>>(),
+        vec!["Вы стоите у ворот"]
+    );
+}
+
+#[test]
+fn written_quest_keeps_paragraphs() {
+    let mut quest = read_graphml(PLAIN_NETWORKX).expect("Parsed quest");
+    quest.source = None;
+    let start = quest.start;
+    quest.graph[start].paragraphs = vec!["Стражник: Стой, кто идет?".to_string()];
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+
+    assert_eq!(
+        find_vertex(&read_back, "n0").blocks().collect::<Vec<_>>(),
+        quest.graph[start].blocks().collect::<Vec<_>>()
+    );
+}
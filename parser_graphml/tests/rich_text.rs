@@ -0,0 +1,123 @@
+use parser_graphml::parser::*;
+
+fn text(text: &str, style: Style) -> Span {
+    Span::Text(text.to_string(), style)
+}
+
+fn strong() -> Style {
+    Style {
+        strong: true,
+        ..Style::default()
+    }
+}
+
+#[test]
+fn plain_text_is_single_span() {
+    let rich = RichText::parse("Вы стоите у ворот");
+
+    assert_eq!(
+        rich.spans,
+        vec![text("Вы стоите у ворот", Style::default())]
+    );
+    assert_eq!(rich.plain(), "Вы стоите у ворот");
+}
+
+#[test]
+fn parses_emphasis_strong_and_colors() {
+    let rich = RichText::parse(
+        r##"<b>Стой!</b> <i>шепчет <font color="#c00000">стражник</font></i> <span style="font-size: 12px; color: green">тихо</span>"##,
+    );
+
+    assert_eq!(
+        rich.spans,
+        vec![
+            text("Стой!", strong()),
+            text(" ", Style::default()),
+            text(
+                "шепчет ",
+                Style {
+                    emphasis: true,
+                    ..Style::default()
+                }
+            ),
+            text(
+                "стражник",
+                Style {
+                    emphasis: true,
+                    color: Some("#c00000".to_string()),
+                    ..Style::default()
+                }
+            ),
+            text(" ", Style::default()),
+            text(
+                "тихо",
+                Style {
+                    color: Some("green".to_string()),
+                    ..Style::default()
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn keeps_literal_newlines_and_breaks() {
+    let rich = RichText::parse("Первая строка\r\nВторая<br/>Третья");
+
+    assert_eq!(
+        rich.spans,
+        vec![
+            text("Первая строка", Style::default()),
+            Span::LineBreak,
+            text("Вторая", Style::default()),
+            Span::LineBreak,
+            text("Третья", Style::default()),
+        ]
+    );
+}
+
+#[test]
+fn collapses_whitespace_in_html_labels() {
+    let rich = RichText::parse(
+        "<html>\n  <p>Площадь <b>пуста</b>.</p>\n  <p>Ветер\n  гонит листья.</p>\n</html>",
+    );
+
+    assert_eq!(rich.plain(), "Площадь пуста.\nВетер гонит листья.");
+    assert_eq!(rich.spans[1], text("пуста", strong()));
+}
+
+#[test]
+fn decodes_entities_and_keeps_unknown_markup_as_text() {
+    let rich = RichText::parse("a &lt; b &amp;&amp; c &#x2014; <Имя> & <u>d</u>");
+
+    assert_eq!(rich.plain(), "a < b && c \u{2014} <Имя> & d");
+}
+
+#[test]
+fn renders_ansi_styles() {
+    let rich = RichText::parse(r##"<b>Стой</b>, <font color="#0f8">друг</font><br>Иди"##);
+
+    assert_eq!(
+        rich.to_ansi(),
+        "\x1b[1mСтой\x1b[0m, \x1b[38;2;0;255;136mдруг\x1b[0m\nИди"
+    );
+}
+
+#[test]
+fn scene_and_choice_expose_rich_text() {
+    let vertex = Vertex {
+        text: "<i>Тишина</i>".to_string(),
+        paragraphs: vec!["Шаги".to_string()],
+        ..Vertex::default()
+    };
+    let edge = Edge {
+        text: "<b>Бежать</b>".to_string(),
+        ..Edge::default()
+    };
+
+    assert_eq!(
+        vertex.rich_blocks().map(|x| x.plain()).collect::<Vec<_>>(),
+        vec!["Тишина", "Шаги"]
+    );
+    assert_eq!(edge.rich_text().spans, vec![text("Бежать", strong())]);
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "serde")]
+
+use parser_graphml::parser::*;
+
+const SCENES_CHOICES: &str = include_str!("../../console-view/scenes-choices.graphml");
+const CHAPTERS: &str = include_str!("fixtures/chapters.graphml");
+
+fn round_trip(quest: &Quest) -> Quest {
+    let json = serde_json::to_string(quest).expect("Serialized quest");
+    serde_json::from_str(&json).expect("Deserialized quest")
+}
+
+fn assert_same_quest(actual: &Quest, expected: &Quest) {
+    assert_eq!(actual.start, expected.start);
+    assert_eq!(actual.chapters, expected.chapters);
+    assert_eq!(actual.locale, expected.locale);
+    assert_eq!(
+        actual
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|x| &x.weight)
+            .collect::<Vec<_>>(),
+        expected
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|x| &x.weight)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        actual
+            .graph
+            .raw_edges()
+            .iter()
+            .map(|x| (x.source(), x.target(), &x.weight))
+            .collect::<Vec<_>>(),
+        expected
+            .graph
+            .raw_edges()
+            .iter()
+            .map(|x| (x.source(), x.target(), &x.weight))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn serializes_parsed_quest_with_graph() {
+    let quest = read_graphml(SCENES_CHOICES).expect("Parsed quest");
+
+    assert_same_quest(&round_trip(&quest), &quest);
+}
+
+#[test]
+fn serializes_chapters() {
+    let quest = read_graphml(CHAPTERS).expect("Parsed quest");
+
+    assert_same_quest(&round_trip(&quest), &quest);
+}
+
+#[test]
+fn deserialized_quest_writes_back_losslessly() {
+    let quest = read_graphml(CHAPTERS).expect("Parsed quest");
+
+    assert_eq!(write_graphml(&round_trip(&quest)), CHAPTERS);
+}
@@ -0,0 +1,109 @@
+use parser_graphml::parser::*;
+
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+const NODE_START_KEY: &str = r#"<key id="d2" for="node" attr.name="start" attr.type="boolean"><default>false</default></key>"#;
+const GRAPH_START_KEY: &str = r#"<key id="d3" for="graph" attr.name="start" attr.type="string"/>"#;
+
+fn with_keys(keys: &str) -> String {
+    PLAIN_NETWORKX.replace("<graph ", &format!("{}\n  <graph ", keys))
+}
+
+fn start_id(xml_doc: &str) -> String {
+    let quest = read_graphml(xml_doc).expect("Parsed quest");
+    quest.graph[quest.start].id.clone()
+}
+
+#[test]
+fn single_scene_without_incoming_edges_is_start() {
+    assert_eq!(start_id(PLAIN_NETWORKX), "n0");
+}
+
+#[test]
+fn node_start_flag_wins_over_node_order() {
+    let xml_doc = with_keys(NODE_START_KEY).replace(
+        r#"<node id="n1">"#,
+        r#"<node id="n1"><data key="d2">true</data>"#,
+    );
+
+    assert_eq!(start_id(&xml_doc), "n1");
+}
+
+#[test]
+fn graph_start_attribute_names_start_scene() {
+    let xml_doc = with_keys(GRAPH_START_KEY).replace(
+        r#"<graph edgedefault="directed">"#,
+        r#"<graph edgedefault="directed"><data key="d3">n2</data>"#,
+    );
+
+    assert_eq!(start_id(&xml_doc), "n2");
+}
+
+#[test]
+fn several_start_flags_are_ambiguous() {
+    let xml_doc = with_keys(NODE_START_KEY)
+        .replace(
+            r#"<node id="n1">"#,
+            r#"<node id="n1"><data key="d2">true</data>"#,
+        )
+        .replace(
+            r#"<node id="n2">"#,
+            r#"<node id="n2"><data key="d2">true</data>"#,
+        );
+
+    match read_graphml(&xml_doc) {
+        Err(Error::FormatGraph(ErrorFormatGraph::AmbiguousStartScene(ids, location))) => {
+            assert_eq!(ids, vec!["n1".to_string(), "n2".to_string()]);
+            assert_eq!(location.element_id, Some("n2".to_string()));
+        }
+        other => panic!("Expected ambiguous start scene, got {:?}", other),
+    }
+}
+
+#[test]
+fn scene_marked_twice_is_listed_once() {
+    let xml_doc = with_keys(&format!("{}\n  {}", NODE_START_KEY, GRAPH_START_KEY))
+        .replace(
+            r#"<graph edgedefault="directed">"#,
+            r#"<graph edgedefault="directed"><data key="d3">n2</data>"#,
+        )
+        .replace(
+            r#"<node id="n1">"#,
+            r#"<node id="n1"><data key="d2">true</data>"#,
+        )
+        .replace(
+            r#"<node id="n2">"#,
+            r#"<node id="n2"><data key="d2">true</data>"#,
+        );
+
+    match read_graphml(&xml_doc) {
+        Err(Error::FormatGraph(ErrorFormatGraph::AmbiguousStartScene(ids, _))) => {
+            assert_eq!(ids, vec!["n2".to_string(), "n1".to_string()]);
+        }
+        other => panic!("Expected ambiguous start scene, got {:?}", other),
+    }
+
+    let xml_doc = with_keys(&format!("{}\n  {}", NODE_START_KEY, GRAPH_START_KEY))
+        .replace(
+            r#"<graph edgedefault="directed">"#,
+            r#"<graph edgedefault="directed"><data key="d3">n2</data>"#,
+        )
+        .replace(
+            r#"<node id="n2">"#,
+            r#"<node id="n2"><data key="d2">true</data>"#,
+        );
+    assert_eq!(start_id(&xml_doc), "n2");
+}
+
+#[test]
+fn cycle_without_start_flag_has_no_start_scene() {
+    let xml_doc = PLAIN_NETWORKX.replace(
+        r#"<edge source="n1" target="n2" />"#,
+        r#"<edge source="n1" target="n0" />"#,
+    );
+
+    match read_graphml(&xml_doc) {
+        Err(Error::FormatGraph(ErrorFormatGraph::NotFoundStartScene(_))) => (),
+        other => panic!("Expected missing start scene, got {:?}", other),
+    }
+}
@@ -0,0 +1,176 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::{choices, find_vertex};
+
+const TWEE: &str = r#":: StoryTitle
+У ворот
+
+:: StoryData
+{
+  "ifid": "D674C58C-DEFA-4F70-B7A2-27742230C0FC",
+  "format": "Harlowe",
+  "start": "Ворота"
+}
+
+:: Ворота [start night] {"position":"100,200","size":"200,100"}
+Вы стоите у ворот.
+
+Темнеет.
+[[Постучать->Стражник]]
+[[Город<-Пройти мимо]]
+
+:: Стражник
+(set: $gold to 5)Стражник смотрит на вас.(if: $gold > 3)[Он ждет монету.]
+[[Заплатить|Город]]
+[[Уйти->Лес]]
+
+:: Город
+Вы в городе, у вас $gold монет.
+<<set $visited to true>>Конец.<</set>>
+"#;
+
+const TWINE_HTML: &str = r#"<html><body>
+<tw-storydata name="У ворот" startnode="2" format="SugarCube" hidden>
+<style role="stylesheet" id="twine-user-stylesheet" type="text/twine-css"></style>
+<tw-passagedata pid="1" name="Город" tags="" position="400,200" size="100,100">Вы в городе &amp; все &quot;хорошо&quot;.</tw-passagedata>
+<tw-passagedata pid="2" name="Ворота" tags="start" position="100,200" size="100,100">Вы у ворот.
+[[Войти-&gt;Город]]</tw-passagedata>
+<tw-passagedata pid="3" name="StoryInit" tags="" position="0,0" size="100,100">&lt;&lt;set $gold to 5&gt;&gt;</tw-passagedata>
+</tw-storydata>
+</body></html>"#;
+
+#[test]
+fn reads_passages_and_links() {
+    let Imported { quest, .. } = read_twee(TWEE).expect("Imported story");
+    let start = &quest.graph[quest.start];
+
+    assert_eq!(start.id, "Ворота");
+    assert_eq!(
+        start.blocks_in(None),
+        vec!["Вы стоите у ворот.", "Темнеет."]
+    );
+    assert_eq!(start.tags, vec!["start", "night"]);
+    assert_eq!(
+        start.position,
+        Some(Rect {
+            x: 100.0,
+            y: 200.0,
+            width: 200.0,
+            height: 100.0
+        })
+    );
+    assert_eq!(
+        choices(&quest, "Ворота"),
+        vec![
+            ("Постучать".to_string(), "Стражник".to_string()),
+            ("Пройти мимо".to_string(), "Город".to_string()),
+        ]
+    );
+    assert_eq!(quest.graph.node_count(), 3);
+}
+
+#[test]
+fn strips_macros_with_warnings() {
+    let Imported { quest, warnings } = read_twee(TWEE).expect("Imported story");
+
+    assert_eq!(
+        find_vertex(&quest, "Стражник").text,
+        "Стражник смотрит на вас.Он ждет монету."
+    );
+    assert_eq!(
+        find_vertex(&quest, "Город").blocks_in(None),
+        vec!["Вы в городе, у вас $gold монет.\nКонец."]
+    );
+
+    let messages = warnings
+        .iter()
+        .map(|x| (x.element.as_str(), x.line, x.message.as_str()))
+        .collect::<Vec<_>>();
+    assert!(messages.contains(&(
+        "Стражник",
+        Some(19),
+        "макрос Harlowe `(set:)` не поддерживается"
+    )));
+    assert!(messages.contains(&(
+        "Стражник",
+        Some(19),
+        "макрос Harlowe `(if:)` не поддерживается"
+    )));
+    assert!(messages.contains(&(
+        "Город",
+        Some(25),
+        "макрос SugarCube `<<set>>` не поддерживается"
+    )));
+    assert!(messages.contains(&("Город", Some(24), "переменная `$gold` не поддерживается")));
+}
+
+#[test]
+fn reports_broken_links() {
+    let Imported { quest, warnings } = read_twee(TWEE).expect("Imported story");
+
+    assert_eq!(
+        choices(&quest, "Стражник"),
+        vec![("Заплатить".to_string(), "Город".to_string())]
+    );
+    let warning = warnings
+        .iter()
+        .find(|x| x.message.contains("Лес"))
+        .expect("Broken link warning");
+    assert_eq!(
+        warning.to_string(),
+        "предупреждение: пассаж `Лес` не найден, переход `Уйти` пропущен (`Стражник`, строка 21)"
+    );
+}
+
+#[test]
+fn reports_story_errors() {
+    assert_eq!(
+        read_twee(":: StoryTitle\nПусто\n").map(|_| ()),
+        Err(ErrorTwee::NoPassages)
+    );
+    assert_eq!(
+        read_twee(":: А\nтекст\n:: А\nтекст\n").map(|_| ()),
+        Err(ErrorTwee::DuplicatePassage("А".to_string(), 3))
+    );
+    assert_eq!(
+        read_twee(&TWEE.replace("\"start\": \"Ворота\"", "\"start\": \"Поле\"")).map(|_| ()),
+        Err(ErrorTwee::NotFoundStartPassage("Поле".to_string()))
+    );
+    assert_eq!(
+        read_twine_html("<html></html>").map(|_| ()),
+        Err(ErrorTwee::NotFoundStoryData)
+    );
+}
+
+#[test]
+fn reads_twine_html() {
+    let Imported { quest, warnings } = read_twine_html(TWINE_HTML).expect("Imported story");
+
+    assert_eq!(quest.graph[quest.start].id, "Ворота");
+    assert_eq!(
+        find_vertex(&quest, "Город").text,
+        "Вы в городе & все \"хорошо\"."
+    );
+    assert_eq!(
+        choices(&quest, "Ворота"),
+        vec![("Войти".to_string(), "Город".to_string())]
+    );
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|x| (x.element.as_str(), x.line))
+            .collect::<Vec<_>>(),
+        vec![("StoryInit", Some(7))]
+    );
+}
+
+#[test]
+fn imported_story_writes_to_graphml() {
+    let Imported { quest, .. } = read_twee(TWEE).expect("Imported story");
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+
+    assert_eq!(read_back.graph[read_back.start].text, "Вы стоите у ворот.");
+    assert_eq!(read_back.graph.edge_count(), quest.graph.edge_count());
+}
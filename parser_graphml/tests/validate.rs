@@ -0,0 +1,147 @@
+use parser_graphml::parser::*;
+
+const SKETCH: &str = r#"digraph {
+    gate [label="Вы стоите у ворот", start=true];
+    city [label="Вы в городе", class="ending"];
+    forest [label="Вы в лесу"];
+    cave [label="<b></b>"];
+    ruins [label="Руины"];
+    tower [label="Башня"];
+
+    gate -> city [label="Войти", id=enter];
+    gate -> forest [label=" Уйти ", id=leave];
+    gate -> forest [label="<i>Уйти</i>", id=leave_again];
+    gate -> gate [label="Подождать", id=wait];
+    forest -> cave [label="", id=dig];
+    cave -> ruins [label="Спуститься", id=descend];
+    tower -> city [label="Спуститься", id=down];
+}"#;
+
+fn findings(quest: &Quest) -> Vec<(Severity, String, Problem)> {
+    validate(quest)
+        .into_iter()
+        .map(|x| (x.severity, x.element, x.problem))
+        .collect()
+}
+
+#[test]
+fn reports_problems_with_graphml_ids() {
+    let quest = read_dot(SKETCH).expect("Parsed quest");
+
+    assert_eq!(
+        findings(&quest),
+        vec![
+            (Severity::Error, "cave".to_string(), Problem::EmptySceneText),
+            (Severity::Error, "ruins".to_string(), Problem::DeadEnd),
+            (
+                Severity::Warning,
+                "tower".to_string(),
+                Problem::UnreachableScene
+            ),
+            (
+                Severity::Warning,
+                "leave_again".to_string(),
+                Problem::DuplicateChoice("Уйти".to_string())
+            ),
+            (Severity::Warning, "wait".to_string(), Problem::SelfLoop),
+            (Severity::Error, "dig".to_string(), Problem::EmptyChoiceText),
+        ]
+    );
+}
+
+#[test]
+fn accepts_finished_quest() {
+    let quest = read_dot(
+        r#"digraph {
+            gate [label="Вы стоите у ворот"];
+            city [label="Вы в городе", class="ending"];
+            gate -> city [label="Войти"];
+        }"#,
+    )
+    .expect("Parsed quest");
+
+    assert_eq!(validate(&quest), vec![]);
+}
+
+#[test]
+fn formats_findings() {
+    let quest = read_dot(SKETCH).expect("Parsed quest");
+    let messages = validate(&quest)
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        messages[1],
+        "ошибка: из сцены `ruins` нет вариантов выбора, но она не отмечена концовкой (`ending`)"
+    );
+    assert_eq!(
+        messages[3],
+        "предупреждение: у сцены уже есть вариант выбора `Уйти`, повторяется в `leave_again`"
+    );
+}
+
+#[test]
+fn stops_at_endings() {
+    let quest = read_dot(
+        r#"digraph {
+            gate [label="Вы стоите у ворот", start=true];
+            throne [label="Вас коронуют", ending="good"];
+            exile [label="Вас изгоняют", class="ending"];
+            gate -> throne [label="Войти", id=enter];
+            throne -> exile [label="Отречься", id=abdicate];
+        }"#,
+    )
+    .expect("Parsed quest");
+
+    assert_eq!(
+        findings(&quest),
+        vec![
+            (
+                Severity::Warning,
+                "exile".to_string(),
+                Problem::UnreachableScene
+            ),
+            (
+                Severity::Warning,
+                "abdicate".to_string(),
+                Problem::ChoiceFromEnding
+            ),
+        ]
+    );
+    assert_eq!(
+        validate(&quest)[1].to_string(),
+        "предупреждение: вариант выбора `abdicate` ведет из концовки, игрок его не увидит"
+    );
+}
+
+const GRAPHML_SKETCH: &str = include_str!("fixtures/validate_sketch.graphml");
+
+#[test]
+fn reports_graphml_node_ids() {
+    let quest = read_graphml(GRAPHML_SKETCH).expect("Parsed quest");
+
+    assert_eq!(
+        findings(&quest),
+        vec![
+            (Severity::Error, "n_forest".to_string(), Problem::DeadEnd),
+            (
+                Severity::Warning,
+                "n_tower".to_string(),
+                Problem::UnreachableScene
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reports_graphml_ids_of_generated_documents() {
+    let quest = read_graphml(GRAPHML_SKETCH).expect("Parsed quest");
+    let generated = Quest {
+        source: None,
+        ..quest.clone()
+    };
+    let read_back = read_graphml(&write_graphml(&generated)).expect("Parsed GraphML");
+
+    assert_eq!(findings(&read_back), findings(&quest));
+}
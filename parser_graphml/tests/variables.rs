@@ -0,0 +1,215 @@
+use parser_graphml::parser::*;
+
+const MARKET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+	<key id="d0" for="node" attr.type="string" attr.name="label"/>
+	<key id="d1" for="edge" attr.type="string" attr.name="label"/>
+	<key id="d2" for="edge" attr.type="string" attr.name="description"/>
+	<key id="d3" for="edge" attr.type="string" attr.name="effects"/>
+	<key id="d4" for="graph" attr.type="string" attr.name="variables"/>
+	<key id="d5" for="node" attr.type="boolean" attr.name="start"/>
+	<graph id="G" edgedefault="directed">
+		<data key="d4">gold: int = 5
+met_guard: bool
+name = "Петр"</data>
+		<node id="gate"><data key="d0">Вы стоите у ворот</data><data key="d5">true</data></node>
+		<node id="market"><data key="d0">Вы на рынке</data></node>
+		<edge id="pay" source="gate" target="market"><data key="d1">Заплатить страже</data><data key="d3">gold -= 3; met_guard = true</data></edge>
+		<edge id="sell" source="gate" target="market"><data key="d1">Продать меч</data><data key="d2">Меч покупает кузнец
+~ gold += 10
+~ name = "Безоружный"</data></edge>
+		<edge id="back" source="market" target="gate"><data key="d1">Вернуться</data></edge>
+	</graph>
+</graphml>"#;
+
+fn variable(name: &str, var_type: VarType, initial: Value) -> Variable {
+    Variable {
+        name: name.to_string(),
+        var_type,
+        initial,
+    }
+}
+
+fn effect(variable: &str, op: EffectOp, value: Value) -> Effect {
+    Effect {
+        variable: variable.to_string(),
+        op,
+        value,
+    }
+}
+
+fn effects(quest: &Quest) -> Vec<(&str, Vec<String>)> {
+    quest
+        .graph
+        .raw_edges()
+        .iter()
+        .map(|x| {
+            let effects = x.weight.effects.iter().map(ToString::to_string).collect();
+            (x.weight.id.as_str(), effects)
+        })
+        .collect()
+}
+
+#[test]
+fn parses_variables() {
+    assert_eq!(
+        parse_variables("gold: int = 5; met_guard: bool\nname = \"Петр\"; debt = -2"),
+        Ok(vec![
+            variable("gold", VarType::Int, Value::Int(5)),
+            variable("met_guard", VarType::Bool, Value::Bool(false)),
+            variable("name", VarType::String, Value::String("Петр".to_string())),
+            variable("debt", VarType::Int, Value::Int(-2)),
+        ])
+    );
+    assert_eq!(parse_variables(""), Ok(vec![]));
+}
+
+#[test]
+fn rejects_invalid_variables() {
+    assert_eq!(
+        parse_variables("gold: float"),
+        Err(ErrorScript::Syntax("тип int, bool или string", 1, 7))
+    );
+    assert_eq!(
+        parse_variables("gold: int = true"),
+        Err(ErrorScript::TypeMismatch(
+            "gold".to_string(),
+            VarType::Int,
+            VarType::Bool
+        ))
+    );
+    assert_eq!(
+        parse_variables("gold = 1; gold = 2"),
+        Err(ErrorScript::DuplicateVariable("gold".to_string()))
+    );
+}
+
+#[test]
+fn parses_effects() {
+    let variables = parse_variables("gold: int; met_guard: bool").expect("Parsed variables");
+
+    assert_eq!(
+        parse_effects("gold += 10; met_guard = true\ngold -= 3", &variables),
+        Ok(vec![
+            effect("gold", EffectOp::Add, Value::Int(10)),
+            effect("met_guard", EffectOp::Set, Value::Bool(true)),
+            effect("gold", EffectOp::Sub, Value::Int(3)),
+        ])
+    );
+    assert_eq!(
+        parse_effects("silver += 1", &variables),
+        Err(ErrorScript::UnknownVariable("silver".to_string()))
+    );
+    assert_eq!(
+        parse_effects("met_guard += 1", &variables),
+        Err(ErrorScript::InvalidOperator(
+            "met_guard".to_string(),
+            EffectOp::Add,
+            VarType::Bool
+        ))
+    );
+    assert_eq!(
+        parse_effects("gold = \"много\"", &variables),
+        Err(ErrorScript::TypeMismatch(
+            "gold".to_string(),
+            VarType::Int,
+            VarType::String
+        ))
+    );
+}
+
+#[test]
+fn applies_effects() {
+    let add = effect("gold", EffectOp::Add, Value::Int(10));
+    let sub = effect("gold", EffectOp::Sub, Value::Int(3));
+
+    assert_eq!(add.apply(&Value::Int(5)), Value::Int(15));
+    assert_eq!(sub.apply(&Value::Int(i64::MIN)), Value::Int(i64::MIN));
+}
+
+#[test]
+fn reads_effects_from_data_and_description() {
+    let quest = read_graphml(MARKET).expect("Parsed quest");
+
+    assert_eq!(quest.variables.len(), 3);
+    assert_eq!(
+        effects(&quest),
+        vec![
+            (
+                "pay",
+                vec!["gold -= 3".to_string(), "met_guard = true".to_string()]
+            ),
+            (
+                "sell",
+                vec![
+                    "gold += 10".to_string(),
+                    "name = \"Безоружный\"".to_string()
+                ]
+            ),
+            ("back", vec![]),
+        ]
+    );
+    // Строки изменений не остаются в заметках автора
+    let sell = &quest.graph.raw_edges()[1].weight;
+    assert_eq!(sell.description.as_deref(), Some("Меч покупает кузнец"));
+}
+
+#[test]
+fn reports_effect_errors_with_edge_id() {
+    let quest = MARKET.replace("gold -= 3", "silver -= 3");
+    let diagnostics = read_graphml_diagnostics(&quest);
+
+    let messages = diagnostics
+        .errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("`pay`"), "{}", messages[0]);
+    assert!(
+        messages[0].contains("переменная `silver` не объявлена"),
+        "{}",
+        messages[0]
+    );
+}
+
+#[test]
+fn writes_variables_back() {
+    let quest = read_graphml(MARKET).expect("Parsed quest");
+    let generated = Quest {
+        source: None,
+        ..quest.clone()
+    };
+
+    for read_back in &[
+        read_graphml(&write_graphml(&generated)).expect("Parsed GraphML"),
+        read_quest_json(&write_quest_json(&quest)).expect("Parsed JSON"),
+        read_dot(&write_dot(&quest)).expect("Parsed DOT"),
+    ] {
+        assert_eq!(read_back.variables, quest.variables);
+        assert_eq!(effects(read_back), effects(&quest));
+    }
+}
+
+#[test]
+fn reports_script_errors_in_other_formats() {
+    assert!(matches!(
+        read_dot(r#"digraph { variables="gold: int"; a -> b [id="buy", effects="gold = true"] }"#),
+        Err(ErrorDot::InvalidScript(id, ErrorScript::TypeMismatch(..))) if id == "buy"
+    ));
+    assert!(matches!(
+        read_quest_json(
+            r#"{"start": "a", "variables": [{"name": "gold", "type": "int"}],
+                "scenes": [{"id": "a", "text": ""}],
+                "choices": [{"id": "c", "from": "a", "to": "a", "effects": ["gold += x"]}]}"#
+        ),
+        Err(ErrorJson::InvalidScript(path, ErrorScript::Syntax(..))) if path == "choices[0].effects"
+    ));
+    assert!(matches!(
+        read_quest_json(
+            r#"{"start": "a", "variables": [{"name": "gold", "type": "int", "value": "много"}],
+                "scenes": [{"id": "a", "text": ""}]}"#
+        ),
+        Err(ErrorJson::InvalidField(path, _)) if path == "variables[0].value"
+    ));
+}
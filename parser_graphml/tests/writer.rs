@@ -0,0 +1,466 @@
+use parser_graphml::parser::*;
+
+mod common;
+use common::{edges, find_node, vertexes};
+
+const SCENES_CHOICES: &str = include_str!("../../console-view/scenes-choices.graphml");
+const YED_DESKTOP: &str = include_str!("fixtures/yed_desktop.graphml");
+const PLAIN_NETWORKX: &str = include_str!("fixtures/plain_networkx.graphml");
+
+/// Квест без исходного документа, чтобы проверить запись с нуля.
+fn without_source(mut quest: Quest) -> Quest {
+    quest.source = None;
+    quest
+}
+
+fn assert_round_trip(xml_doc: &str) {
+    let quest = read_graphml(xml_doc).expect("Parsed source quest");
+    assert_eq!(write_graphml(&quest), xml_doc);
+
+    let quest = without_source(quest);
+    let written = write_graphml(&quest);
+    let read_back = read_graphml(&written).expect("Parsed written quest");
+
+    assert_eq!(vertexes(&read_back), vertexes(&quest));
+    for ix in quest.graph.node_indices() {
+        if let Some(position) = quest.graph[ix].position {
+            assert_eq!(read_back.graph[ix].position, Some(position));
+        }
+    }
+    assert_eq!(edges(&read_back), edges(&quest));
+    assert_eq!(
+        read_back.graph[read_back.start].id,
+        quest.graph[quest.start].id
+    );
+    assert_eq!(write_graphml(&read_back), written);
+}
+
+#[test]
+fn round_trips_scenes_choices() {
+    assert_round_trip(SCENES_CHOICES);
+}
+
+#[test]
+fn round_trips_yed_desktop() {
+    assert_round_trip(YED_DESKTOP);
+}
+
+#[test]
+fn round_trips_plain_graphml() {
+    assert_round_trip(PLAIN_NETWORKX);
+}
+
+#[test]
+fn written_quest_is_yfiles_html() {
+    let quest = without_source(read_graphml(SCENES_CHOICES).expect("Parsed source quest"));
+    let written = write_graphml(&quest);
+    let doc = roxmltree::Document::parse(&written).expect("Written quest is valid XML");
+
+    assert_eq!(Dialect::detect(&doc), Dialect::YFilesHtml);
+}
+
+#[test]
+fn keeps_start_scene_reachable_by_cycle() {
+    let xml_doc = PLAIN_NETWORKX.replace(
+        r#"<edge source="n1" target="n2" />"#,
+        r#"<edge source="n1" target="n0" />"#,
+    );
+    let xml_doc = xml_doc.replace(
+        r#"<key id="d0" for="node" attr.name="label" attr.type="string" />"#,
+        r#"<key id="d0" for="node" attr.name="label" attr.type="string" />
+  <key id="d2" for="node" attr.name="start" attr.type="boolean" />"#,
+    );
+    let xml_doc = xml_doc.replace(
+        r#"<node id="n0">"#,
+        r#"<node id="n0"><data key="d2">true</data>"#,
+    );
+
+    assert_round_trip(&xml_doc);
+}
+
+#[test]
+fn escapes_markup_in_text() {
+    let xml_doc = PLAIN_NETWORKX.replace("Заплатить", "Дать &lt;5&gt; монет &amp; уйти");
+    let quest = read_graphml(&xml_doc).expect("Parsed source quest");
+
+    assert_eq!(edges(&quest)[0].2.text, "Дать <5> монет & уйти");
+    assert_round_trip(&xml_doc);
+}
+
+#[test]
+fn changes_only_edited_text() {
+    for xml_doc in &[SCENES_CHOICES, YED_DESKTOP, PLAIN_NETWORKX] {
+        let mut quest = read_graphml(xml_doc).expect("Parsed source quest");
+        let start = quest.start;
+        let old_text = quest.graph[start].text.clone();
+        quest.graph[start].text = "Вы стоите у <закрытых> ворот".to_string();
+        let edge = quest
+            .graph
+            .edge_indices()
+            .next()
+            .expect("Quest has choices");
+        let old_choice = quest.graph[edge].text.clone();
+        quest.graph[edge].text = "Дать взятку".to_string();
+
+        let new_text = if xml_doc.contains("<![CDATA[") {
+            "Вы стоите у <закрытых> ворот"
+        } else {
+            "Вы стоите у &lt;закрытых&gt; ворот"
+        };
+        let expected = xml_doc.replacen(&old_text, new_text, 1).replacen(
+            &format!(">{}<", old_choice),
+            ">Дать взятку<",
+            1,
+        );
+        assert_eq!(write_graphml(&quest), expected);
+    }
+}
+
+#[test]
+fn adds_label_to_unlabeled_choice() {
+    for xml_doc in &[YED_DESKTOP, PLAIN_NETWORKX] {
+        let mut quest = read_graphml(xml_doc).expect("Parsed source quest");
+        let edge = quest
+            .graph
+            .edge_indices()
+            .find(|ix| quest.graph[*ix].text.is_empty())
+            .expect("Quest has unlabeled choice");
+        quest.graph[edge].text = "Пройти дальше".to_string();
+
+        let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+        assert_eq!(edges(&read_back), edges(&quest));
+    }
+}
+
+#[test]
+fn keeps_geometry_when_scenes_added_and_removed() {
+    let mut quest = read_graphml(YED_DESKTOP).expect("Parsed source quest");
+    let n1 = find_node(&quest, "n1");
+    quest.graph.remove_node(n1);
+    let n3 = quest.graph.add_node(Vertex {
+        id: "n3".to_string(),
+        text: "Вы нашли обходной путь".to_string(),
+        ..Vertex::default()
+    });
+    let start = find_node(&quest, "n0");
+    quest.graph.add_edge(
+        start,
+        n3,
+        Edge {
+            id: "e3".to_string(),
+            text: "Обойти".to_string(),
+            ..Edge::default()
+        },
+    );
+
+    let written = write_graphml(&quest);
+    let read_back = read_graphml(&written).expect("Parsed written quest");
+
+    assert!(!written.contains("Стражник пропускает вас"));
+    assert!(written.contains(r#"<y:Geometry height="60.0" width="240.0" x="260.0" y="200.0"/>"#));
+    assert!(written.contains(r#"<y:GenericNode configuration="com.yworks.flowchart.terminator">"#));
+    assert_eq!(
+        vertexes(&read_back)
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>(),
+        vec!["n0", "n2", "n3"]
+    );
+    assert_eq!(
+        edges(&read_back)
+            .into_iter()
+            .map(|(source, target, edge)| (source, target, edge.text))
+            .collect::<Vec<_>>(),
+        vec![
+            ("n0".to_string(), "n2".to_string(), "Уйти".to_string()),
+            ("n0".to_string(), "n3".to_string(), "Обойти".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn round_trips_json_escapes_in_tags_and_translations() {
+    let mut quest = without_source(read_graphml(PLAIN_NETWORKX).expect("Parsed source quest"));
+    let gate = find_node(&quest, "n0");
+    quest.graph[gate].tags = vec![
+        "night".to_string(),
+        "строка\r\nвторая".to_string(),
+        "звонок\u{7}\u{1f}".to_string(),
+        "кавычка \" и \\u0435".to_string(),
+    ];
+    quest.graph[gate].translations.insert(
+        "en".to_string(),
+        vec!["Gate\r\n".to_string(), "Tab\tand \u{1}".to_string()],
+    );
+    let choice = quest.graph.edge_indices().next().expect("Choice exists");
+    quest.graph[choice]
+        .translations
+        .insert("en".to_string(), "Pay \"\u{0}\"\r".to_string());
+
+    let read_back = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+    assert_eq!(vertexes(&read_back), vertexes(&quest));
+    assert_eq!(edges(&read_back), edges(&quest));
+}
+
+#[test]
+fn decodes_unicode_escapes_in_embedded_json() {
+    let xml_doc = PLAIN_NETWORKX
+        .replace(
+            r#"<key id="d0" "#,
+            concat!(
+                r#"<key id="d2" for="node" attr.name="tags"/>"#,
+                r#"<key id="d3" for="node" attr.name="lang"/>"#,
+                r#"<key id="d0" "#
+            ),
+        )
+        .replace(
+            r#"<node id="n0">"#,
+            concat!(
+                r#"<node id="n0"><data key="d2"><y:Json xmlns:y="y">["\u0435nding", "a\"b"]</y:Json></data>"#,
+                r#"<data key="d3">{"en": "Gate é\r\nopen", "de": ["Tor", 1]}</data>"#
+            ),
+        );
+    let quest = read_graphml(&xml_doc).expect("Parsed quest");
+    let gate = &quest.graph[find_node(&quest, "n0")];
+
+    assert_eq!(gate.tags, vec!["еnding".to_string(), "a\"b".to_string()]);
+    assert_eq!(
+        gate.translations.get("en"),
+        Some(&vec!["Gate é\r\nopen".to_string()])
+    );
+    assert_eq!(gate.translations.get("de"), Some(&vec!["Tor".to_string()]));
+}
+
+/// Правка квеста для проверки записи без потерь.
+type Edit = fn(&mut Quest);
+
+/// Записать исправленный квест поверх каждого исходного документа и прочитать обратно.
+///
+/// Правка должна записаться в исходный документ, а не полной перезаписью.
+fn assert_spliced(edit: Edit) {
+    for xml_doc in &[SCENES_CHOICES, YED_DESKTOP, PLAIN_NETWORKX] {
+        let mut quest = read_graphml(xml_doc).expect("Parsed source quest");
+        edit(&mut quest);
+        let written = write_graphml(&quest);
+        let read_back = read_graphml(&written).expect("Parsed written quest");
+
+        assert!(!written.contains("Created by parser_graphml"));
+        assert_eq!(vertexes(&read_back), vertexes(&quest));
+        assert_eq!(edges(&read_back), edges(&quest));
+        assert_eq!(
+            read_back.graph[read_back.start].id,
+            quest.graph[quest.start].id
+        );
+        assert_eq!(read_back.locale, quest.locale);
+        assert_eq!(read_back.variables, quest.variables);
+        assert_eq!(write_graphml(&read_back), written);
+    }
+}
+
+fn first_choice(quest: &Quest) -> petgraph::graph::EdgeIndex {
+    quest
+        .graph
+        .edge_indices()
+        .next()
+        .expect("Quest has choices")
+}
+
+fn add_gold(quest: &mut Quest) {
+    quest.variables = parse_variables("gold: int = 5; met_guard: bool").expect("Parsed variables");
+}
+
+#[test]
+fn splices_start() {
+    assert_spliced(|quest| quest.start = find_node(quest, "n2"));
+}
+
+#[test]
+fn splices_description() {
+    assert_spliced(|quest| {
+        let start = quest.start;
+        quest.graph[start].description = Some("Заметка <автора> & соавтора".to_string());
+        let choice = first_choice(quest);
+        quest.graph[choice].description = Some("Самый короткий путь".to_string());
+    });
+}
+
+#[test]
+fn splices_removed_description() {
+    for xml_doc in &[SCENES_CHOICES, YED_DESKTOP, PLAIN_NETWORKX] {
+        let mut quest = read_graphml(xml_doc).expect("Parsed source quest");
+        let start = quest.start;
+        quest.graph[start].description = Some("Заметка автора".to_string());
+        let mut quest = read_graphml(&write_graphml(&quest)).expect("Parsed written quest");
+        let start = quest.start;
+        quest.graph[start].description = None;
+
+        let written = write_graphml(&quest);
+        assert!(!written.contains("Заметка автора"));
+        assert_eq!(
+            vertexes(&read_graphml(&written).expect("Parsed written quest")),
+            vertexes(&quest)
+        );
+    }
+}
+
+#[test]
+fn splices_url() {
+    assert_spliced(|quest| {
+        let start = quest.start;
+        quest.graph[start].url = Some("https://example.com/gate?a=1&b=2".to_string());
+        let choice = first_choice(quest);
+        quest.graph[choice].url = Some("https://example.com/pay".to_string());
+    });
+}
+
+#[test]
+fn splices_tags() {
+    assert_spliced(|quest| {
+        let start = quest.start;
+        quest.graph[start].tags = vec!["night".to_string(), "двор замка".to_string()];
+        let choice = first_choice(quest);
+        quest.graph[choice].tags = vec!["bribe".to_string()];
+    });
+}
+
+#[test]
+fn splices_ending() {
+    assert_spliced(|quest| {
+        let end = find_node(quest, "n2");
+        quest.graph[end].ending = Some(Ending::parse("good: Победа"));
+    });
+}
+
+#[test]
+fn splices_translations() {
+    assert_spliced(|quest| {
+        let start = quest.start;
+        quest.graph[start]
+            .translations
+            .insert("en".to_string(), vec!["You stand at the gate".to_string()]);
+        let choice = first_choice(quest);
+        quest.graph[choice]
+            .translations
+            .insert("en".to_string(), "Pay".to_string());
+    });
+}
+
+#[test]
+fn splices_locale() {
+    assert_spliced(|quest| quest.locale = Some("ru".to_string()));
+}
+
+#[test]
+fn splices_effects_and_variables() {
+    assert_spliced(|quest| {
+        add_gold(quest);
+        let choice = first_choice(quest);
+        quest.graph[choice].effects =
+            parse_effects("gold -= 1; met_guard = true", &quest.variables).expect("Parsed effects");
+    });
+}
+
+#[test]
+fn splices_condition_and_locked() {
+    assert_spliced(|quest| {
+        add_gold(quest);
+        let choice = first_choice(quest);
+        quest.graph[choice].condition = Some(Condition {
+            expr: parse_condition("gold > 0", &quest.variables).expect("Parsed condition"),
+            locked: Some("Нужно золото".to_string()),
+        });
+        let last = quest
+            .graph
+            .edge_indices()
+            .next_back()
+            .expect("Quest has choices");
+        quest.graph[last].condition = Some(Condition {
+            expr: parse_condition("!met_guard", &quest.variables).expect("Parsed condition"),
+            locked: None,
+        });
+    });
+}
+
+#[test]
+fn splices_new_scene_and_choice_data() {
+    assert_spliced(|quest| {
+        add_gold(quest);
+        let secret = quest.graph.add_node(Vertex {
+            id: "n9".to_string(),
+            text: "Тайный ход".to_string(),
+            description: Some("Только для смелых".to_string()),
+            tags: vec!["secret".to_string()],
+            ending: Some(Ending::parse("secret")),
+            translations: vec![("en".to_string(), vec!["Secret passage".to_string()])]
+                .into_iter()
+                .collect(),
+            ..Vertex::default()
+        });
+        let start = quest.start;
+        let effects = parse_effects("gold += 10", &quest.variables).expect("Parsed effects");
+        let expr = parse_condition("gold < 3", &quest.variables).expect("Parsed condition");
+        quest.graph.add_edge(
+            start,
+            secret,
+            Edge {
+                id: "e9".to_string(),
+                text: "Нырнуть в ход".to_string(),
+                url: Some("https://example.com/secret".to_string()),
+                effects,
+                condition: Some(Condition {
+                    expr,
+                    locked: Some("Слишком богаты".to_string()),
+                }),
+                translations: vec![("en".to_string(), "Dive in".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Edge::default()
+            },
+        );
+    });
+}
+
+#[test]
+fn writes_only_changed_data() {
+    let mut quest = read_graphml(PLAIN_NETWORKX).expect("Parsed source quest");
+    let start = quest.start;
+    quest.graph[start].description = Some("Заметка".to_string());
+    let last = quest
+        .graph
+        .edge_indices()
+        .next_back()
+        .expect("Quest has choices");
+    quest.graph[last].url = Some("https://example.com".to_string());
+
+    let expected = PLAIN_NETWORKX
+        .replace(
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />"#,
+            r#"<key id="d0" for="node" attr.name="label" attr.type="string" />
+  <key id="d2" for="node" attr.type="string" attr.name="description"/>
+  <key id="d3" for="edge" attr.type="string" attr.name="url"/>"#,
+        )
+        .replace(
+            r#"<data key="d0">Вы стоите у ворот</data>"#,
+            r#"<data key="d0">Вы стоите у ворот</data>
+      <data key="d2">Заметка</data>"#,
+        )
+        .replace(
+            r#"<edge source="n1" target="n2" />"#,
+            r#"<edge source="n1" target="n2" ><data key="d3">https://example.com</data></edge>"#,
+        );
+    assert_eq!(write_graphml(&quest), expected);
+}
+
+#[test]
+fn rewrites_paragraphs_in_full() {
+    let mut quest = read_graphml(PLAIN_NETWORKX).expect("Parsed source quest");
+    let start = quest.start;
+    quest.graph[start].paragraphs = vec!["Темнеет.".to_string()];
+
+    let written = write_graphml(&quest);
+    let read_back = read_graphml(&written).expect("Parsed written quest");
+
+    assert!(written.contains("Created by parser_graphml"));
+    assert_eq!(vertexes(&read_back), vertexes(&quest));
+    assert_eq!(edges(&read_back), edges(&quest));
+}
@@ -0,0 +1,207 @@
+//! Движок квеста, общий для консольной и веб-морды: текущая сцена,
+//! доступные варианты выбора, переходы по ним, значения переменных квеста
+//! и условия, от которых зависит доступность вариантов.
+
+use parser_graphml::parser::{Edge, Ending, Quest, Value, Vertex};
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Игра по квесту: сцена, в которой находится игрок, переходы между сценами
+/// и текущие значения переменных.
+#[derive(Debug, Clone)]
+pub struct GameSession {
+    quest: Quest,
+    current: NodeIndex,
+    variables: BTreeMap<String, Value>,
+}
+
+/// Вариант выбора в текущей сцене.
+#[derive(Debug, Clone, Copy)]
+pub struct Choice<'a> {
+    /// Ребро варианта выбора в графе квеста.
+    pub id: EdgeIndex,
+    pub edge: &'a Edge,
+    /// Сцена, в которую ведет вариант выбора.
+    pub target: NodeIndex,
+    pub availability: Availability<'a>,
+}
+
+/// Доступность варианта выбора по его условию.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability<'a> {
+    /// Условия нет или оно выполнено.
+    Available,
+    /// Условие не выполнено: вариант показывается недоступным с причиной.
+    Locked(&'a str),
+    /// Условие не выполнено, а причины нет: вариант не показывается.
+    Hidden,
+}
+
+/// Ошибка выбора варианта.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorChoice {
+    /// Нет варианта с таким номером: номер (с 0) и количество вариантов.
+    OutOfRange(usize, usize),
+    /// Игра закончилась: текущая сцена - концовка или из нее нет вариантов выбора.
+    GameOver,
+    /// Условие варианта не выполнено: номер (с 0) и причина.
+    Locked(usize, String),
+}
+
+impl fmt::Display for ErrorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorChoice::OutOfRange(index, count) => write!(
+                f,
+                "нет варианта с номером {}, можно выбрать от 1 до {}",
+                index + 1,
+                count
+            ),
+            ErrorChoice::GameOver => write!(f, "игра окончена, вариантов выбора больше нет"),
+            ErrorChoice::Locked(index, reason) => {
+                write!(f, "вариант {} недоступен: {}", index + 1, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ErrorChoice {}
+
+impl GameSession {
+    /// Начать игру со стартовой сцены квеста с начальными значениями переменных.
+    pub fn new(quest: Quest) -> GameSession {
+        GameSession {
+            current: quest.start,
+            variables: initial_variables(&quest),
+            quest,
+        }
+    }
+
+    pub fn quest(&self) -> &Quest {
+        &self.quest
+    }
+
+    /// Сцена, в которой находится игрок.
+    pub fn current(&self) -> NodeIndex {
+        self.current
+    }
+
+    pub fn scene(&self) -> &Vertex {
+        &self.quest.graph[self.current]
+    }
+
+    /// Текущие значения переменных квеста по именам.
+    pub fn variables(&self) -> &BTreeMap<String, Value> {
+        &self.variables
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    /// Концовка, до которой дошел игрок, если текущая сцена - концовка.
+    pub fn ending(&self) -> Option<&Ending> {
+        self.scene().ending.as_ref()
+    }
+
+    /// Доступность варианта выбора при текущих значениях переменных.
+    pub fn availability<'a>(&self, edge: &'a Edge) -> Availability<'a> {
+        match &edge.condition {
+            Some(condition) if !condition.is_met(&self.variables) => {
+                match condition.locked.as_deref() {
+                    Some(reason) => Availability::Locked(reason),
+                    None => Availability::Hidden,
+                }
+            }
+            _ => Availability::Available,
+        }
+    }
+
+    /// Варианты выбора текущей сцены, которые видит игрок, в порядке
+    /// объявления в файле квеста: доступные и недоступные с причиной.
+    ///
+    /// В концовке вариантов нет, даже если у сцены есть исходящие ребра.
+    pub fn choices(&self) -> Vec<Choice<'_>> {
+        if self.ending().is_some() {
+            return Vec::new();
+        }
+
+        let mut choices: Vec<Choice<'_>> = self
+            .quest
+            .graph
+            .edges_directed(self.current, Direction::Outgoing)
+            .map(|x| Choice {
+                id: x.id(),
+                edge: x.weight(),
+                target: x.target(),
+                availability: self.availability(x.weight()),
+            })
+            .filter(|x| x.availability != Availability::Hidden)
+            .collect();
+        choices.sort_by_key(|x| x.id);
+        choices
+    }
+
+    /// Закончилась ли игра: дошли до концовки или до сцены без доступных
+    /// вариантов выбора.
+    pub fn is_over(&self) -> bool {
+        !self
+            .choices()
+            .iter()
+            .any(|x| x.availability == Availability::Available)
+    }
+
+    /// Застрял ли игрок: сцена не концовка, и варианты выбора в ней есть,
+    /// но все недоступны. Игра при этом тоже закончилась ([`GameSession::is_over`]).
+    pub fn is_stuck(&self) -> bool {
+        let choices = self.choices();
+        !choices.is_empty()
+            && choices
+                .iter()
+                .all(|x| x.availability != Availability::Available)
+    }
+
+    /// Выбрать вариант с номером `index` (с 0) из [`GameSession::choices`],
+    /// изменить переменные по его действиям и перейти в сцену, в которую он ведет.
+    ///
+    /// Недоступный вариант выбрать нельзя - возвращается ошибка с причиной.
+    pub fn choose(&mut self, index: usize) -> Result<NodeIndex, ErrorChoice> {
+        let choices = self.choices();
+        if choices.is_empty() {
+            return Err(ErrorChoice::GameOver);
+        }
+        let choice = choices
+            .get(index)
+            .ok_or(ErrorChoice::OutOfRange(index, choices.len()))?;
+        if let Availability::Locked(reason) = choice.availability {
+            return Err(ErrorChoice::Locked(index, reason.to_string()));
+        }
+        let (id, target) = (choice.id, choice.target);
+
+        for effect in &self.quest.graph[id].effects {
+            // Переменные проверяются при чтении квеста, поэтому они всегда объявлены
+            if let Some(value) = self.variables.get_mut(&effect.variable) {
+                *value = effect.apply(value);
+            }
+        }
+        self.current = target;
+        Ok(target)
+    }
+
+    /// Начать игру заново со стартовой сцены с начальными значениями переменных.
+    pub fn restart(&mut self) {
+        self.current = self.quest.start;
+        self.variables = initial_variables(&self.quest);
+    }
+}
+
+fn initial_variables(quest: &Quest) -> BTreeMap<String, Value> {
+    quest
+        .variables
+        .iter()
+        .map(|x| (x.name.clone(), x.initial.clone()))
+        .collect()
+}
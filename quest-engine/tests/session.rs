@@ -0,0 +1,248 @@
+use parser_graphml::parser::*;
+use quest_engine::{Availability, ErrorChoice, GameSession};
+
+const GATE: &str = r#"digraph {
+    gate [label="Вы стоите у ворот", start=true];
+    city [label="Вы в городе"];
+    forest [label="Вы в лесу"];
+    throne [label="Вас коронуют", ending="good"];
+    gate -> city [label="Войти"];
+    gate -> forest [label="Уйти"];
+    city -> throne [label="Подняться во дворец"];
+    throne -> gate [label="Отречься"];
+}"#;
+
+fn session() -> GameSession {
+    GameSession::new(read_dot(GATE).expect("Parsed quest"))
+}
+
+fn choices(session: &GameSession) -> Vec<&str> {
+    session
+        .choices()
+        .iter()
+        .map(|x| x.edge.text.as_str())
+        .collect()
+}
+
+#[test]
+fn lists_choices_in_declaration_order() {
+    let session = session();
+
+    assert_eq!(session.scene().id, "gate");
+    assert_eq!(choices(&session), vec!["Войти", "Уйти"]);
+    assert!(!session.is_over());
+    assert!(!session.is_stuck());
+}
+
+#[test]
+fn follows_chosen_choice() {
+    let mut session = session();
+
+    let city = session.choose(0).expect("Valid choice");
+    assert_eq!(session.current(), city);
+    assert_eq!(session.scene().id, "city");
+    assert_eq!(choices(&session), vec!["Подняться во дворец"]);
+}
+
+#[test]
+fn rejects_choice_out_of_range() {
+    let mut session = session();
+
+    // Номер, равный количеству вариантов, уже за границей
+    assert_eq!(session.choose(2), Err(ErrorChoice::OutOfRange(2, 2)));
+    assert_eq!(session.scene().id, "gate");
+    assert_eq!(
+        ErrorChoice::OutOfRange(2, 2).to_string(),
+        "нет варианта с номером 3, можно выбрать от 1 до 2"
+    );
+}
+
+#[test]
+fn reports_reached_ending() {
+    let mut session = session();
+    session.choose(0).expect("Valid choice");
+    session.choose(0).expect("Valid choice");
+
+    assert_eq!(session.scene().id, "throne");
+    assert_eq!(session.ending().map(|x| x.kind), Some(EndingKind::Good));
+    // Из концовки дальше не пройти, даже если у сцены есть ребра
+    assert!(session.is_over());
+    assert!(!session.is_stuck());
+    assert_eq!(session.choose(0), Err(ErrorChoice::GameOver));
+}
+
+#[test]
+fn stops_at_dead_end() {
+    let mut session = session();
+    session.choose(1).expect("Valid choice");
+
+    assert_eq!(session.scene().id, "forest");
+    assert_eq!(session.ending(), None);
+    assert!(session.is_over());
+    assert!(!session.is_stuck());
+    assert_eq!(session.choose(0), Err(ErrorChoice::GameOver));
+}
+
+#[test]
+fn restarts_from_start_scene() {
+    let mut session = session();
+    session.choose(1).expect("Valid choice");
+    session.restart();
+
+    assert_eq!(session.current(), session.quest().start);
+    assert_eq!(choices(&session), vec!["Войти", "Уйти"]);
+}
+
+const MARKET: &str = r#"digraph {
+    variables="gold: int = 5; met_guard: bool; name = \"Петр\"";
+    gate [label="Вы стоите у ворот", start=true];
+    market [label="Вы на рынке"];
+    gate -> market [label="Заплатить страже", effects="gold -= 3; met_guard = true"];
+    gate -> market [label="Продать меч", effects="gold += 10; name = \"Безоружный\""];
+    market -> gate [label="Вернуться"];
+}"#;
+
+fn market() -> GameSession {
+    GameSession::new(read_dot(MARKET).expect("Parsed quest"))
+}
+
+#[test]
+fn starts_with_initial_variables() {
+    let session = market();
+
+    assert_eq!(session.variable("gold"), Some(&Value::Int(5)));
+    assert_eq!(session.variable("met_guard"), Some(&Value::Bool(false)));
+    assert_eq!(
+        session.variable("name"),
+        Some(&Value::String("Петр".to_string()))
+    );
+    assert_eq!(session.variable("silver"), None);
+}
+
+#[test]
+fn applies_choice_effects() {
+    let mut session = market();
+    session.choose(0).expect("Valid choice");
+    session.choose(0).expect("Valid choice");
+    session.choose(1).expect("Valid choice");
+
+    assert_eq!(session.variable("gold"), Some(&Value::Int(12)));
+    assert_eq!(session.variable("met_guard"), Some(&Value::Bool(true)));
+    assert_eq!(
+        session.variable("name"),
+        Some(&Value::String("Безоружный".to_string()))
+    );
+}
+
+#[test]
+fn keeps_variables_on_invalid_choice() {
+    let mut session = market();
+
+    assert!(session.choose(5).is_err());
+    assert_eq!(session.variable("gold"), Some(&Value::Int(5)));
+}
+
+#[test]
+fn resets_variables_on_restart() {
+    let mut session = market();
+    session.choose(1).expect("Valid choice");
+    session.restart();
+
+    assert_eq!(session.variable("gold"), Some(&Value::Int(5)));
+    assert_eq!(session.variables().len(), 3);
+}
+
+const GUARD: &str = r#"digraph {
+    variables="gold: int = 5; met_guard: bool";
+    gate [label="Вы стоите у ворот", start=true];
+    market [label="Вы на рынке"];
+    castle [label="Вы в замке"];
+    gate -> market [label="Продать меч", effects="gold += 10"];
+    gate -> castle [label="Подкупить стражу", condition="gold >= 10 && !met_guard", locked="Нужно 10 золотых", effects="gold -= 10; met_guard = true"];
+    gate -> castle [label="Пройти как знакомый", condition="met_guard"];
+    market -> gate [label="Вернуться"];
+    castle -> gate [label="Выйти"];
+}"#;
+
+fn availability(session: &GameSession) -> Vec<(&str, Availability<'_>)> {
+    session
+        .choices()
+        .iter()
+        .map(|x| (x.edge.text.as_str(), x.availability))
+        .collect()
+}
+
+#[test]
+fn locks_and_hides_choices_by_condition() {
+    let session = GameSession::new(read_dot(GUARD).expect("Parsed quest"));
+
+    assert_eq!(
+        availability(&session),
+        vec![
+            ("Продать меч", Availability::Available),
+            ("Подкупить стражу", Availability::Locked("Нужно 10 золотых")),
+        ]
+    );
+}
+
+#[test]
+fn rejects_locked_choice() {
+    let mut session = GameSession::new(read_dot(GUARD).expect("Parsed quest"));
+
+    assert_eq!(
+        session.choose(1),
+        Err(ErrorChoice::Locked(1, "Нужно 10 золотых".to_string()))
+    );
+    assert_eq!(session.scene().id, "gate");
+}
+
+#[test]
+fn unlocks_choices_when_state_changes() {
+    let mut session = GameSession::new(read_dot(GUARD).expect("Parsed quest"));
+    session.choose(0).expect("Valid choice");
+    session.choose(0).expect("Valid choice");
+
+    assert_eq!(
+        availability(&session),
+        vec![
+            ("Продать меч", Availability::Available),
+            ("Подкупить стражу", Availability::Available),
+        ]
+    );
+    session.choose(1).expect("Valid choice");
+    session.choose(0).expect("Valid choice");
+
+    // Золото потрачено на подкуп, зато стража пропускает знакомого
+    assert_eq!(
+        availability(&session),
+        vec![
+            ("Продать меч", Availability::Available),
+            ("Подкупить стражу", Availability::Locked("Нужно 10 золотых")),
+            ("Пройти как знакомый", Availability::Available),
+        ]
+    );
+}
+
+#[test]
+fn ends_game_when_all_choices_are_locked() {
+    let quest = read_dot(
+        r#"digraph {
+            variables="key: bool";
+            hall [label="Запертый зал", start=true];
+            door [label="За дверью"];
+            hall -> door [label="Открыть дверь", condition="key", locked="Нужен ключ"];
+        }"#,
+    )
+    .expect("Parsed quest");
+    let mut session = GameSession::new(quest);
+
+    assert_eq!(choices(&session), vec!["Открыть дверь"]);
+    assert!(session.is_over());
+    // Вариант виден, но выбрать его нельзя: игрок застрял, а не дошел до конца
+    assert!(session.is_stuck());
+    assert_eq!(
+        session.choose(0),
+        Err(ErrorChoice::Locked(0, "Нужен ключ".to_string()))
+    );
+    assert_eq!(session.scene().id, "hall");
+}
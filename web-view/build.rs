@@ -25,7 +25,7 @@ fn main() {
     let output_css_path = &format!("{}/{}", static_dir, OUTPUT_CSS);
 
     if Path::new(output_css_path).exists() {
-        remove_file(output_css_path);
+        remove_file(output_css_path).expect("Remove previous output css file");
     }
 
     let mut css_file = append_to_file(output_css_path).expect("Not found output css file");
@@ -41,16 +41,12 @@ fn main() {
             .expect("Compile scss to css");
 
             css_file
-                .write(css_data.as_bytes())
+                .write_all(css_data.as_bytes())
                 .expect("Must been write translated css to file");
         });
 }
 
 fn append_to_file<P: AsRef<Path>>(path: P) -> io::Result<File> {
     // println!("cargo:warning={}", path.as_ref().to_str().unwrap());
-    OpenOptions::new()
-        .write(true)
-        .create(true)
-        .append(true)
-        .open(path)
+    OpenOptions::new().create(true).append(true).open(path)
 }
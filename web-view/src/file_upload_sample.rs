@@ -12,7 +12,7 @@ pub struct FileModel {
     tasks: Vec<ReaderTask>,
     files: Vec<String>,
     by_chunks: bool,
-    onloaded: Callback<String>,
+    onloaded: Callback<FileData>,
     title: &'static str,
     error: Option<&'static str>,
 }
@@ -20,8 +20,9 @@ pub struct FileModel {
 #[derive(PartialEq, Properties)]
 pub struct Props {
     pub title: &'static str,
+    /// Загруженный файл: имя нужно, чтобы определить формат квеста.
     #[props(required)]
-    pub onloaded: Callback<String>,
+    pub onloaded: Callback<FileData>,
 }
 
 type Chunks = bool;
@@ -53,18 +54,12 @@ impl Component for FileModel {
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            FileMsg::Loaded(file) => match std::str::from_utf8(&file.content) {
-                Ok(info) => {
-                    let data = info.to_owned();
-                    self.files.push(data.clone());
-                    self.onloaded.emit(data.clone());
-                }
-                Err(e) => {
-                    const MSG: &str = "Не удалось прочитать выбранный файл";
-                    self.error = Some(MSG);
-                    self.console.log(&format!("Msg: {}. Panic: {}", MSG, e));
-                }
-            },
+            FileMsg::Loaded(file) => {
+                self.error = None;
+                self.console.log(&format!("Загружен файл: {}", file.name));
+                self.files.push(file.name.clone());
+                self.onloaded.emit(file);
+            }
             FileMsg::Chunk(chunk) => {
                 let info = format!("chunk: {:?}", chunk);
                 self.files.push(info);
@@ -123,6 +118,8 @@ impl Renderable<FileModel> for FileModel {
 }
 
 impl FileModel {
+    // NOTE: нужно только для отладки
+    #[allow(dead_code)]
     fn view_file(&self, data: &str) -> Html<Self> {
         html! {
             <li>{ data }</li>
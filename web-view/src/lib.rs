@@ -1,72 +1,159 @@
 use parser_graphml::parser::*;
 use petgraph::graph::EdgeReference;
-use petgraph::graph::{Graph, NodeIndex};
+use petgraph::graph::NodeIndex;
 use petgraph::visit::EdgeRef;
-use petgraph::Direction;
+use quest_engine::{Availability, GameSession};
+use stdweb::js;
+use stdweb::unstable::TryInto;
 use yew::services::ConsoleService;
 use yew::{html, Component, ComponentLink, Html, Properties, Renderable, ShouldRender};
 
 pub struct SceneModel {
     console: ConsoleService,
-    description: String,
-    current_scene_id: NodeIndex,
-    graph: Graph<Vertex, Edge>,
-    fisrt_scene_id: NodeIndex,
+    description: Vec<RichText>,
+    session: GameSession,
+    show_map: bool,
+    /// Язык перевода, `None` - основной текст квеста.
+    locale: Option<String>,
 }
 
 pub enum QuestMsg {
     Choice(usize),
     ReloadToFirstScene,
+    ToggleMap,
+    SetLocale(Option<String>),
 }
 
+/// Ширина карты квеста в пикселях.
+const MAP_WIDTH: f64 = 600.0;
+
 #[derive(Clone, Properties)]
 pub struct Props {
-    pub graph: Graph<Vertex, Edge>,
+    #[props(required)]
+    pub quest: Quest,
 }
 
 impl SceneModel {
-    fn new(mut console: ConsoleService, graph: Graph<Vertex, Edge>) -> SceneModel {
-        let (desc, first_scene_id) = SceneModel::init_scene_model(&graph, &mut console);
+    fn new(console: ConsoleService, quest: Quest) -> SceneModel {
+        let locale = SceneModel::browser_locale(&quest);
+        let session = GameSession::new(quest);
 
         SceneModel {
-            console: console,
-            graph: graph,
-            description: desc,
-            current_scene_id: first_scene_id,
-            fisrt_scene_id: first_scene_id,
+            console,
+            description: session.scene().rich_blocks_in(locale.as_deref()),
+            session,
+            show_map: false,
+            locale,
         }
     }
 
-    fn init_scene_model(
-        graph: &Graph<Vertex, Edge>,
-        console: &mut ConsoleService,
-    ) -> (String, NodeIndex) {
-        let first_scene_id: NodeIndex = match graph.node_indices().take(1).next() {
-            Some(vertex) => vertex,
-            None => {
-                const MSG: &str = "Не удалось получить первую вершину в графе";
-                console.log(MSG);
-                panic!(MSG);
-            }
-        };
-
-        let desc = SceneModel::get_scene_desc(graph, first_scene_id);
-
-        (desc, first_scene_id)
+    /// Язык перевода квеста, подходящий к языку браузера.
+    fn browser_locale(quest: &Quest) -> Option<String> {
+        let language: Option<String> = js! { return navigator.language; }.try_into().ok();
+        language.and_then(|x| quest.pick_locale(&x))
     }
 
-    fn get_choices(&self) -> Vec<EdgeReference<'_, Edge>> {
-        self.graph
-            .edges_directed(self.current_scene_id, Direction::Outgoing)
-            .collect()
+    /// Текст текущей сцены на выбранном языке.
+    fn get_scene_description(&self) -> Vec<RichText> {
+        self.session.scene().rich_blocks_in(self.locale.as_deref())
     }
 
-    fn get_scene_desc(graph: &Graph<Vertex, Edge>, scene_id: NodeIndex) -> String {
-        graph[scene_id].text.clone()
+    /// Границы сцен на схеме автора и масштаб, с которым она помещается в карту.
+    ///
+    /// `None`, если ни у одной сцены нет положения.
+    fn map_frame(&self) -> Option<(Rect, f64)> {
+        let graph = &self.session.quest().graph;
+        let (x0, y0, x1, y1) = graph
+            .node_indices()
+            .filter_map(|ix| graph[ix].position)
+            .fold(None, |bounds, rect| {
+                let (x0, y0, x1, y1) = bounds.unwrap_or((rect.x, rect.y, rect.x, rect.y));
+                Some((
+                    x0.min(rect.x),
+                    y0.min(rect.y),
+                    x1.max(rect.x + rect.width),
+                    y1.max(rect.y + rect.height),
+                ))
+            })?;
+        let frame = Rect {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        };
+
+        Some((frame, MAP_WIDTH / frame.width.max(1.0)))
     }
 
-    fn get_scene_description(&self, scene_id: NodeIndex) -> String {
-        SceneModel::get_scene_desc(&self.graph, scene_id)
+    fn view_map(&self) -> Html<Self> {
+        let (frame, scale) = match self.map_frame() {
+            Some(x) if self.show_map => x,
+            _ => return html! {},
+        };
+        let graph = &self.session.quest().graph;
+        let to_map = |(x, y): (f64, f64)| ((x - frame.x) * scale, (y - frame.y) * scale);
+
+        let view_choice = |edge: EdgeReference<'_, Edge>| {
+            let ends = graph[edge.source()]
+                .position
+                .zip(graph[edge.target()].position);
+            let (from, to) = match ends {
+                Some((from, to)) => (to_map(from.center()), to_map(to.center())),
+                None => return html! {},
+            };
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            let style = format!(
+                "left: {}px; top: {}px; width: {}px; transform: rotate({}rad);",
+                from.0,
+                from.1,
+                dx.hypot(dy),
+                dy.atan2(dx)
+            );
+            html! {
+                <div class="quest-game__map-choice" style=style></div>
+            }
+        };
+
+        let view_scene = |ix: NodeIndex| {
+            let scene = &graph[ix];
+            let rect = match scene.position {
+                Some(x) => x,
+                None => return html! {},
+            };
+            let (left, top) = to_map((rect.x, rect.y));
+            let style = format!(
+                "left: {}px; top: {}px; width: {}px; height: {}px;",
+                left,
+                top,
+                rect.width * scale,
+                rect.height * scale
+            );
+            let class = if ix == self.session.current() {
+                "quest-game__map-scene quest-game__map-scene--current"
+            } else {
+                "quest-game__map-scene"
+            };
+            let title = scene
+                .rich_blocks_in(self.locale.as_deref())
+                .first()
+                .map(RichText::plain)
+                .unwrap_or_default();
+            html! {
+                <div class=class style=style title=title></div>
+            }
+        };
+
+        let style = format!(
+            "width: {}px; height: {}px;",
+            MAP_WIDTH,
+            frame.height * scale
+        );
+        html! {
+            <div class="quest-game__map" style=style>
+                { for graph.edge_references().map(view_choice) }
+                { for graph.node_indices().map(view_scene) }
+            </div>
+        }
     }
 }
 
@@ -75,15 +162,13 @@ impl Component for SceneModel {
     type Properties = Props;
 
     fn create(props: Self::Properties, _: ComponentLink<Self>) -> Self {
-        SceneModel::new(ConsoleService::new(), props.graph.clone())
+        SceneModel::new(ConsoleService::new(), props.quest)
     }
 
     fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        self.graph = _props.graph;
-        let (desc, first_scene_id) = SceneModel::init_scene_model(&self.graph, &mut self.console);
-        self.description = desc;
-        self.fisrt_scene_id = first_scene_id;
-        self.current_scene_id = self.fisrt_scene_id;
+        self.locale = SceneModel::browser_locale(&_props.quest);
+        self.session = GameSession::new(_props.quest);
+        self.description = self.get_scene_description();
 
         true
     }
@@ -91,18 +176,31 @@ impl Component for SceneModel {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             QuestMsg::Choice(number) => {
-                if let Some(found_vertex_ix) = self.get_choices().get(number) {
-                    self.current_scene_id = found_vertex_ix.target();
-                    self.description = self.get_scene_description(self.current_scene_id);
-                } else {
-                    let msg = &format!("Не удалось получить вариант по номеру: {:?}", number);
-                    self.console.log(msg);
-                    panic!(msg.clone());
+                if let Err(e) = self.session.choose(number) {
+                    // Сцена остается прежней, кнопки вариантов перерисуются
+                    self.console
+                        .log(&format!("Не удалось выбрать вариант: {}", e));
+                    return true;
+                }
+                self.description = self.get_scene_description();
+                if let Some(ending) = self.session.ending() {
+                    self.console.log(&format!(
+                        "Достигнута концовка `{}`: {}",
+                        self.session.scene().id,
+                        ending
+                    ));
                 }
             }
             QuestMsg::ReloadToFirstScene => {
-                self.current_scene_id = self.fisrt_scene_id;
-                self.description = self.get_scene_description(self.current_scene_id);
+                self.session.restart();
+                self.description = self.get_scene_description();
+            }
+            QuestMsg::ToggleMap => {
+                self.show_map = !self.show_map;
+            }
+            QuestMsg::SetLocale(locale) => {
+                self.locale = locale;
+                self.description = self.get_scene_description();
             }
         }
         true
@@ -111,29 +209,150 @@ impl Component for SceneModel {
 
 impl Renderable<SceneModel> for SceneModel {
     fn view(&self) -> Html<Self> {
-        let choices = self.get_choices();
+        let choices = self.session.choices();
 
         let view_message = |i: usize| {
-            let msg = choices[i].weight().text.clone();
+            let msg = choices[i].edge.rich_text_in(self.locale.as_deref());
+            match choices[i].availability {
+                Availability::Locked(reason) => html! {
+                    <button class="quest-game__scene-choice quest-game__scene-choice--locked btn" augmented-ui="tl-clip br-clip exe" disabled=true title=reason>
+                        { format!("{}.", i+1) }
+                        { view_rich_text(&msg) }
+                        <span class="quest-game__scene-choice-reason">{ reason }</span>
+                    </button>
+                },
+                _ => html! {
+                    <button class="quest-game__scene-choice btn" augmented-ui="tl-clip br-clip exe" onclick=|_| QuestMsg::Choice(i)>
+                        { format!("{}.", i+1) }
+                        { view_rich_text(&msg) }
+                    </button>
+                },
+            }
+        };
+
+        let view_paragraph = |paragraph: &RichText| {
+            html! {
+                <p class="quest-game__scene-paragraph">{ view_rich_text(paragraph) }</p>
+            }
+        };
+
+        let chapter = self
+            .session
+            .quest()
+            .chapter_of(self.session.current())
+            .map_or_else(String::new, |x| x.title.clone());
+
+        let map_toggle = if self.map_frame().is_some() {
+            html! {
+                <button class="quest-game__map-toggle" onclick=|_| QuestMsg::ToggleMap > { "Карта" } </button>
+            }
+        } else {
+            html! {}
+        };
+
+        let quest = self.session.quest();
+        let locales = quest.locales();
+        let view_locale = |locale: Option<&String>| {
+            let title = locale
+                .or(quest.locale.as_ref())
+                .map_or_else(|| "основной".to_string(), Clone::clone);
+            let class = if locale == self.locale.as_ref() {
+                "quest-game__locale quest-game__locale--current"
+            } else {
+                "quest-game__locale"
+            };
+            let locale = locale.cloned();
             html! {
-                <button class="quest-game__scene-choice btn" augmented-ui="tl-clip br-clip exe" onclick=|_| QuestMsg::Choice(i)>
-                    { format!("{}.{}", i+1, msg) }
-                </button>
+                <button class=class onclick=|_| QuestMsg::SetLocale(locale.clone()) > { title } </button>
+            }
+        };
+        let locale_switcher = if locales.is_empty() {
+            html! {}
+        } else {
+            html! {
+                <div class="quest-game__locales">
+                    { view_locale(None) }
+                    { for locales.iter().map(|x| view_locale(Some(x))) }
+                </div>
             }
         };
 
+        let scene = match self.session.ending() {
+            Some(ending) => view_ending(ending),
+            None => html! {
+                <div class="quest-game__scene-choices">
+                        { for (0..choices.len()).map(view_message) }
+                </div>
+            },
+        };
+
         html! {
             <div class="quest-game">
                 <div class="quest-game__menu">
                     <button class="quest-game__reload-game" onclick=|_| QuestMsg::ReloadToFirstScene > { "Начать сначала" } </button>
+                    { map_toggle }
+                    <div class="quest-game__chapter">{chapter}</div>
+                    { locale_switcher }
                 </div>
+                { self.view_map() }
                 <div class="quest-game__scene">
-                    <div class="quest-game__scene-description" augmented-ui="tl-clip t-clip tr-clip r-clip br-clip b-clip bl-clip l-clip exe">{self.description.clone()}</div>
-                    <div class="quest-game__scene-choices">
-                            { for (0..choices.len()).map(view_message) }
+                    <div class="quest-game__scene-description" augmented-ui="tl-clip t-clip tr-clip r-clip br-clip b-clip bl-clip l-clip exe">
+                        { for self.description.iter().map(view_paragraph) }
                     </div>
+                    { scene }
                 </div>
             </div>
         }
     }
 }
+
+/// Экран концовки вместо вариантов выбора: название, тип и кнопка новой игры.
+fn view_ending(ending: &Ending) -> Html<SceneModel> {
+    let class = format!(
+        "quest-game__ending quest-game__ending--{}",
+        ending.kind.name()
+    );
+    let kind = if ending.title.is_some() {
+        html! { <div class="quest-game__ending-kind">{ ending.kind.title() }</div> }
+    } else {
+        html! {}
+    };
+
+    html! {
+        <div class=class>
+            <div class="quest-game__ending-title">{ ending.display_title() }</div>
+            { kind }
+            <button class="quest-game__scene-choice btn" augmented-ui="tl-clip br-clip exe" onclick=|_| QuestMsg::ReloadToFirstScene>
+                { "Играть снова" }
+            </button>
+        </div>
+    }
+}
+
+/// Отрисовать оформленный текст элементами HTML: `<strong>`, `<em>`,
+/// `<span>` с цветом и `<br>`.
+fn view_rich_text(text: &RichText) -> Html<SceneModel> {
+    let view_span = |span: &Span| match span {
+        Span::Text(text, style) => {
+            let mut node = match &style.color {
+                Some(color) => {
+                    let color = format!("color: {}", color);
+                    html! { <span style=color>{text}</span> }
+                }
+                None => html! { {text} },
+            };
+            if style.emphasis {
+                node = html! { <em>{node}</em> };
+            }
+            if style.strong {
+                node = html! { <strong>{node}</strong> };
+            }
+            node
+        }
+        Span::LineBreak => html! { <br/> },
+    };
+
+    html! {
+        <>{ for text.spans.iter().map(view_span) }</>
+    }
+}
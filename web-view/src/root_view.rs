@@ -8,15 +8,16 @@ pub mod quest_game;
 
 use file_upload::FileModel;
 use quest_game::SceneModel;
+use yew::services::reader::FileData;
 
 use parser_graphml::parser::*;
 
 pub struct RootView {
-    loaded_graph_file: Option<String>,
+    loaded_graph_file: Option<FileData>,
 }
 
 pub enum RootMsg {
-    LoadGraph(String),
+    LoadGraph(FileData),
 }
 
 impl Component for RootView {
@@ -39,11 +40,27 @@ impl Component for RootView {
     }
 }
 
+/// Квест из загруженного файла любого известного формата и предупреждения
+/// импорта, либо подробности ошибки.
+fn load_quest(file: &FileData) -> Result<(Quest, Vec<String>), String> {
+    match QuestLoaders::default().load(&file.name, &file.content) {
+        Ok(Imported { quest, warnings }) => {
+            Ok((quest, warnings.iter().map(ToString::to_string).collect()))
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 impl Renderable<RootView> for RootView {
     fn view(&self) -> Html<Self> {
         if let Some(graph_file) = &self.loaded_graph_file {
-            match read_graphml(&graph_file) {
-                Ok(graph) => {
+            match load_quest(graph_file) {
+                Ok((quest, warnings)) => {
+                    let view_warning = |warning: &String| {
+                        html! {
+                            <li class="game__warning">{warning}</li>
+                        }
+                    };
                     html! {
                         <div class="game__container">
                             <div class="game__menu">
@@ -51,17 +68,23 @@ impl Renderable<RootView> for RootView {
                                     <FileModel title="Загрузить файл игры" onloaded=|graph_file| RootMsg::LoadGraph(graph_file) />
                                 </div>
                             </div>
+                            <ul class="game__warnings">
+                                { for warnings.iter().map(view_warning) }
+                            </ul>
                             <div class="game__scene">
-                                <SceneModel graph=graph />
+                                <SceneModel quest=quest />
                             </div>
                         </div>
                     }
                 }
-                Err(e) => {
-                    const MSG: &str = "Ошибка парсинга графа из GraphML формата. Попробуйте заново загрузить файл.";
+                Err(report) => {
+                    const MSG: &str = "Ошибка загрузки квеста. Попробуйте заново загрузить файл.";
                     //TODO: писать еще ошибку в лог.
                     html! {
-                        <div class="error">{MSG}</div>
+                        <div class="error">
+                            <div class="error__message">{MSG}</div>
+                            <pre class="error__details">{report}</pre>
+                        </div>
                     }
                 }
             }